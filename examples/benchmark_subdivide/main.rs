@@ -1,6 +1,10 @@
 extern crate meshlite;
 
+use meshlite::mesh::Mesh;
 use meshlite::primitives::cube;
+#[cfg(feature = "rayon")]
+use meshlite::subdivide::CatmullClarkSubdivider;
+#[cfg(not(feature = "rayon"))]
 use meshlite::subdivide::Subdivide;
 use std::time::{Duration, Instant};
 use std::vec::Vec;
@@ -19,7 +23,7 @@ fn main() {
     println!("----------+-----------+-----------+-----------+-----------+-----------");
     for _ in 0..9 {
         let now = Instant::now();
-        let new_mesh = mesh.subdivide();
+        let new_mesh = subdivide(&mesh);
         let seconds = to_seconds_f64(&now.elapsed());
         let verts_per_second = (new_mesh.vertex_count as f64 / seconds).round();
         all_vps.push(verts_per_second);
@@ -28,7 +32,7 @@ fn main() {
             new_mesh.face_count,
             new_mesh.vertex_count,
             new_mesh.halfedge_count,
-            new_mesh.edges.len(),
+            new_mesh.edge_count(),
             verts_per_second,
             seconds * 1000.0
         );
@@ -42,6 +46,17 @@ fn main() {
     );
 }
 
+/// Runs the parallel path when built with --features rayon.
+#[cfg(feature = "rayon")]
+fn subdivide(mesh: &Mesh) -> Mesh {
+    CatmullClarkSubdivider::new(mesh).generate_parallel()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn subdivide(mesh: &Mesh) -> Mesh {
+    mesh.subdivide()
+}
+
 fn to_seconds_f64(d: &Duration) -> f64 {
     d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9
 }
@@ -30,7 +30,7 @@ fn main() {
             tri_mesh.face_count,
             tri_mesh.vertex_count,
             tri_mesh.halfedge_count,
-            tri_mesh.edges.len(),
+            tri_mesh.edge_count(),
             verts_per_second,
             seconds * 1000.0
         );
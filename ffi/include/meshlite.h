@@ -38,6 +38,7 @@ int meshlite_bmesh_set_round_way(void *context, int bmesh_id, int round_way);
 int meshlite_bmesh_set_deform_thickness(void *context, int bmesh_id, float thickness);
 int meshlite_bmesh_set_deform_width(void *context, int bmesh_id, float width);
 int meshlite_bmesh_enable_debug(void *context, int bmesh_id, int enable);
+int meshlite_bmesh_enable_debug_self_intersections(void *context, int bmesh_id, int enable);
 int meshlite_bmesh_add_node(void *context, int bmesh_id, float x, float y, float z, float radius);
 int meshlite_bmesh_set_node_cut_subdiv_count(void *context, int bmesh_id, int node_id, int subdiv_count);
 int meshlite_bmesh_set_node_round_way(void *context, int bmesh_id, int node_id, int round_way);
@@ -54,6 +55,8 @@ int meshlite_combine_coplanar_faces(void *context, int mesh_id);
 int meshlite_trim(void *context, int mesh_id, int normalize);
 int meshlite_mirror_in_x(void *context, int mesh_id, float center_x);
 int meshlite_mirror_in_z(void *context, int mesh_id, float center_z);
+int meshlite_mirror(void *context, int mesh_id, float plane_x, float plane_y, float plane_z, float normal_x, float normal_y, float normal_z);
+int meshlite_symmetrize(void *context, int mesh_id, float plane_x, float plane_y, float plane_z, float normal_x, float normal_y, float normal_z);
 int meshlite_fix_hole(void *context, int mesh_id);
 int meshlite_skeletonmesh_create(void *context);
 int meshlite_skeletonmesh_set_end_radius(void *context, float radius);
@@ -61,6 +64,15 @@ int meshlite_skeletonmesh_add_bone(void *context, int sklt_id, float from_x, flo
 int meshlite_skeletonmesh_generate_mesh(void *context, int sklt_id);
 int meshlite_smooth_vertices(void *context, int mesh_id, float factor, int *buffer, int max_buffer_len);
 int meshlite_smooth(void *context, int mesh_id, float factor);
+int meshlite_render_buffer_create(void *context, int mesh_id, float crease_angle, const float *uv_buffer, int uv_buffer_len, const float *color_buffer, int color_buffer_len, int include_source);
+int meshlite_render_buffer_get_vertex_count(void *context, int render_buffer_id);
+int meshlite_render_buffer_get_vertex_stride(void *context, int render_buffer_id);
+int meshlite_render_buffer_get_vertex_array(void *context, int render_buffer_id, float *buffer, int max_buffer_len);
+int meshlite_render_buffer_get_index_count(void *context, int render_buffer_id);
+int meshlite_render_buffer_get_index_array(void *context, int render_buffer_id, unsigned int *buffer, int max_buffer_len);
+int meshlite_render_buffer_get_index_array_u16(void *context, int render_buffer_id, unsigned short *buffer, int max_buffer_len);
+int meshlite_render_buffer_optimize(void *context, int render_buffer_id);
+int meshlite_render_buffer_destroy(void *context, int render_buffer_id);
 
 #ifdef __cplusplus
 }
@@ -2,21 +2,30 @@ extern crate cgmath;
 extern crate meshlite;
 
 use std::ffi::CStr;
-use std::os::raw::{c_int, c_char, c_float};
+use std::os::raw::{c_int, c_char, c_float, c_uint, c_ushort};
 
 use cgmath::Point3;
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
 
 use meshlite::bmesh::Bmesh;
+use meshlite::iterator::EdgeIterator;
 use meshlite::iterator::FaceHalfedgeIterator;
+use meshlite::iterator::FaceIterator;
+use meshlite::mesh::EdgeEndpoints;
 use meshlite::mesh::Export;
 use meshlite::mesh::Id;
 use meshlite::mesh::Import;
 use meshlite::mesh::Mesh;
+use meshlite::renderbuffer::RenderBuffer;
+use meshlite::renderbuffer::RenderBufferBuilder;
 use meshlite::skeletonmesh::SkeletonMesh;
 use meshlite::subdivide::Subdivide;
 use meshlite::triangulate::Triangulate;
 
 use std::cmp;
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 const MAGIC_NUM: u32 = 12345678;
@@ -30,6 +39,8 @@ pub struct RustContext {
     free_bmesh_ids: Vec<i32>,
     skeletonmeshes: Vec<SkeletonMesh>,
     free_skeletonmesh_ids: Vec<i32>,
+    render_buffers: Vec<RenderBuffer>,
+    free_render_buffer_ids: Vec<i32>,
 }
 
 fn alloc_mesh_id(ctx: &mut RustContext) -> i32 {
@@ -69,6 +80,19 @@ fn alloc_skeletonmesh_id(ctx: &mut RustContext) -> i32 {
     }
 }
 
+fn alloc_render_buffer_id(ctx: &mut RustContext) -> i32 {
+    if ctx.free_render_buffer_ids.len() > 0 {
+        ctx.free_render_buffer_ids.swap_remove(0)
+    } else {
+        ctx.render_buffers.push(RenderBuffer::default());
+        ctx.render_buffers.len() as i32
+    }
+}
+
+fn free_render_buffer_id(ctx: &mut RustContext, id: i32) {
+    ctx.render_buffers[id as usize - 1] = RenderBuffer::default();
+    ctx.free_render_buffer_ids.push(id);
+}
 
 // Commented out to allow a clean build free from warnings.
 //
@@ -87,6 +111,8 @@ pub extern "C" fn meshlite_create_context() -> *mut RustContext {
         free_bmesh_ids: Vec::new(),
         skeletonmeshes: Vec::new(),
         free_skeletonmesh_ids: Vec::new(),
+        render_buffers: Vec::new(),
+        free_render_buffer_ids: Vec::new(),
     }))
 }
 
@@ -290,7 +316,7 @@ pub extern "C" fn meshlite_get_edge_count(context: *mut RustContext, mesh_id: c_
         &mut *context
     };
     assert_eq!(ctx.magic, MAGIC_NUM);
-    ctx.meshes.get((mesh_id - 1) as usize).unwrap().edges.len() as i32
+    ctx.meshes.get((mesh_id - 1) as usize).unwrap().edge_count() as i32
 }
 
 #[no_mangle]
@@ -304,7 +330,7 @@ pub extern "C" fn meshlite_get_vertex_position_array(context: *mut RustContext,
     let count : isize = cmp::min((mesh.vertices.len() * 3) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
     for vert_idx in 0..mesh.vertices.len() {
-        let position = mesh.vertices[vert_idx].position;
+        let position = mesh.vertices.position[vert_idx];
         if i + 3 > count {
             break;
         }
@@ -329,7 +355,7 @@ pub extern "C" fn meshlite_get_vertex_source_array(context: *mut RustContext, me
     let count : isize = cmp::min((mesh.vertices.len()) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
     for vert_idx in 0..mesh.vertices.len() {
-        let source = mesh.vertices[vert_idx].source;
+        let source = mesh.vertices.source[vert_idx];
         if i + 1 > count {
             break;
         }
@@ -351,12 +377,9 @@ pub extern "C" fn meshlite_get_halfedge_index_array(context: *mut RustContext, m
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
     let count : isize = cmp::min((mesh.halfedge_count * 2) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for face in mesh.faces.iter() {
-        if !face.alive {
-            continue;
-        }
+    for face_id in FaceIterator::new(mesh) {
         let mut vert_ids = Vec::new();
-        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face.id).unwrap()) {
+        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
             vert_ids.push(mesh.halfedge_start_vertex_id(halfedge_id).unwrap());
         }
         if i + vert_ids.len() as isize * 2 > count {
@@ -385,12 +408,9 @@ pub extern "C" fn meshlite_get_halfedge_normal_array(context: *mut RustContext,
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
     let count : isize = cmp::min((mesh.halfedge_count * 3) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for face in mesh.faces.iter() {
-        if !face.alive {
-            continue;
-        }
-        let norm = mesh.face_norm(face.id);
-        let face_halfedge_count = FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face.id).unwrap()).into_vec().len();
+    for face_id in FaceIterator::new(mesh) {
+        let norm = mesh.face_norm(face_id);
+        let face_halfedge_count = FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()).into_vec().len();
         if i + face_halfedge_count as isize * 3 > count {
             break;
         }
@@ -416,15 +436,12 @@ pub extern "C" fn meshlite_get_triangle_index_array(context: *mut RustContext, m
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
     let count : isize = cmp::min((mesh.face_count * 3) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for face in mesh.faces.iter() {
-        if !face.alive {
-            continue;
-        }
+    for face_id in FaceIterator::new(mesh) {
         if i + 3 > count {
             break;
         }
         let mut j = 0;
-        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face.id).unwrap()) {
+        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
             if j >= 3 {
                 break;
             }
@@ -449,12 +466,9 @@ pub extern "C" fn meshlite_get_face_index_array(context: *mut RustContext, mesh_
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
     let count : isize = max_buffer_len as isize;
     let mut i : isize = 0;
-    for face in mesh.faces.iter() {
-        if !face.alive {
-            continue;
-        }
+    for face_id in FaceIterator::new(mesh) {
         let mut vert_ids = Vec::new();
-        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face.id).unwrap()) {
+        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
             let vert_id = mesh.halfedge_start_vertex_id(halfedge_id).unwrap() as c_int;
             vert_ids.push(vert_id);
         }
@@ -487,14 +501,11 @@ pub extern "C" fn meshlite_get_triangle_normal_array(context: *mut RustContext,
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
     let count : isize = cmp::min((mesh.face_count * 3) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for face in mesh.faces.iter() {
-        if !face.alive {
-            continue;
-        }
+    for face_id in FaceIterator::new(mesh) {
         if i + 3 > count {
             break;
         }
-        let norm = mesh.face_norm(face.id);
+        let norm = mesh.face_norm(face_id);
         unsafe {
             *buffer.offset(i + 0) = norm.x;
             *buffer.offset(i + 1) = norm.y;
@@ -513,12 +524,15 @@ pub extern "C" fn meshlite_get_edge_index_array(context: *mut RustContext, mesh_
     };
     assert_eq!(ctx.magic, MAGIC_NUM);
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
-    let count : isize = cmp::min((mesh.edges.len() * 2) as usize, max_buffer_len as usize) as isize;
+    let count : isize = cmp::min((mesh.edge_count() * 2) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for (edge, _) in mesh.edges.iter() {
+    for halfedge_id in EdgeIterator::new(mesh) {
         if i + 2 > count {
             break;
         }
+        let next_halfedge_id = mesh.halfedge_next_id(halfedge_id).unwrap();
+        let edge = EdgeEndpoints::new(mesh.halfedge_start_vertex_id(halfedge_id).unwrap(),
+            mesh.halfedge_start_vertex_id(next_halfedge_id).unwrap());
         unsafe {
             *buffer.offset(i + 0) = edge.low as c_int - 1;
             *buffer.offset(i + 1) = edge.high as c_int - 1;
@@ -536,9 +550,9 @@ pub extern "C" fn meshlite_get_edge_normal_array(context: *mut RustContext, mesh
     };
     assert_eq!(ctx.magic, MAGIC_NUM);
     let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
-    let count : isize = cmp::min((mesh.edges.len() * 3) as usize, max_buffer_len as usize) as isize;
+    let count : isize = cmp::min((mesh.edge_count() * 3) as usize, max_buffer_len as usize) as isize;
     let mut i : isize = 0;
-    for (_, &halfedge_id) in mesh.edges.iter() {
+    for halfedge_id in EdgeIterator::new(mesh) {
         if i + 3 > count {
             break;
         }
@@ -745,6 +759,18 @@ pub extern "C" fn meshlite_bmesh_enable_debug(context: *mut RustContext, bmesh_i
     0
 }
 
+#[no_mangle]
+pub extern "C" fn meshlite_bmesh_enable_debug_self_intersections(context: *mut RustContext, bmesh_id: c_int, enable: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let bmesh = ctx.bmeshes.get_mut((bmesh_id - 1) as usize).unwrap();
+    bmesh.enable_debug_self_intersections(enable != 0);
+    0
+}
+
 #[no_mangle]
 pub extern "C" fn meshlite_bmesh_add_seam_requirement(context: *mut RustContext, bmesh_id: c_int) -> c_int {
     let ctx = unsafe {
@@ -880,6 +906,32 @@ pub extern "C" fn meshlite_mirror_in_z(context: *mut RustContext, mesh_id: c_int
     new_mesh_id
 }
 
+#[no_mangle]
+pub extern "C" fn meshlite_mirror(context: *mut RustContext, mesh_id: c_int, plane_x: c_float, plane_y: c_float, plane_z: c_float, normal_x: c_float, normal_y: c_float, normal_z: c_float) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let new_mesh_id = alloc_mesh_id(ctx);
+    let new_mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap().mirror(Point3 {x: plane_x, y: plane_y, z: plane_z}, Vector3 {x: normal_x, y: normal_y, z: normal_z});
+    ctx.meshes[(new_mesh_id - 1) as usize] = new_mesh;
+    new_mesh_id
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_symmetrize(context: *mut RustContext, mesh_id: c_int, plane_x: c_float, plane_y: c_float, plane_z: c_float, normal_x: c_float, normal_y: c_float, normal_z: c_float) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let new_mesh_id = alloc_mesh_id(ctx);
+    let new_mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap().symmetrize(Point3 {x: plane_x, y: plane_y, z: plane_z}, Vector3 {x: normal_x, y: normal_y, z: normal_z});
+    ctx.meshes[(new_mesh_id - 1) as usize] = new_mesh;
+    new_mesh_id
+}
+
 #[no_mangle]
 pub extern "C" fn meshlite_fix_hole(context: *mut RustContext, mesh_id: c_int) -> c_int {
     let ctx = unsafe {
@@ -970,3 +1022,155 @@ pub extern "C" fn meshlite_smooth_vertices(context: *mut RustContext, mesh_id: c
     ctx.meshes.get_mut((mesh_id - 1) as usize).unwrap().smooth(factor, Some(&vertex_ids));
     0
 }
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_create(context: *mut RustContext, mesh_id: c_int, crease_angle: c_float, uv_buffer: *const c_float, uv_buffer_len: c_int, color_buffer: *const c_float, color_buffer_len: c_int, include_source: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let render_buffer = {
+        let mesh = ctx.meshes.get((mesh_id - 1) as usize).unwrap();
+        // The uv and color buffers hold one value per face corner, in the
+        // same order as the face index array.
+        let mut uvs = HashMap::new();
+        let mut colors = HashMap::new();
+        let mut corner : isize = 0;
+        for face_id in FaceIterator::new(mesh) {
+            for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+                if !uv_buffer.is_null() && (corner + 1) * 2 <= uv_buffer_len as isize {
+                    uvs.insert(halfedge_id, unsafe {
+                        Vector2::new(*uv_buffer.offset(corner * 2 + 0), *uv_buffer.offset(corner * 2 + 1))
+                    });
+                }
+                if !color_buffer.is_null() && (corner + 1) * 4 <= color_buffer_len as isize {
+                    colors.insert(halfedge_id, unsafe {
+                        Vector4::new(*color_buffer.offset(corner * 4 + 0), *color_buffer.offset(corner * 4 + 1),
+                            *color_buffer.offset(corner * 4 + 2), *color_buffer.offset(corner * 4 + 3))
+                    });
+                }
+                corner += 1;
+            }
+        }
+        let mut builder = RenderBufferBuilder::new(mesh)
+            .crease_angle(crease_angle)
+            .source(include_source != 0);
+        if !uv_buffer.is_null() {
+            builder = builder.uvs(&uvs);
+        }
+        if !color_buffer.is_null() {
+            builder = builder.colors(&colors);
+        }
+        builder.build()
+    };
+    let new_render_buffer_id = alloc_render_buffer_id(ctx);
+    ctx.render_buffers[(new_render_buffer_id - 1) as usize] = render_buffer;
+    new_render_buffer_id
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_vertex_count(context: *mut RustContext, render_buffer_id: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap().vertex_count() as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_vertex_stride(context: *mut RustContext, render_buffer_id: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap().layout.stride() as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_vertex_array(context: *mut RustContext, render_buffer_id: c_int, buffer: *mut c_float, max_buffer_len: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let render_buffer = ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap();
+    let count = cmp::min(render_buffer.vertices.len(), max_buffer_len as usize);
+    for i in 0..count {
+        unsafe {
+            *buffer.offset(i as isize) = render_buffer.vertices[i];
+        }
+    }
+    count as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_index_count(context: *mut RustContext, render_buffer_id: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap().indices.len() as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_index_array(context: *mut RustContext, render_buffer_id: c_int, buffer: *mut c_uint, max_buffer_len: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let render_buffer = ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap();
+    let count = cmp::min(render_buffer.indices.len(), max_buffer_len as usize);
+    for i in 0..count {
+        unsafe {
+            *buffer.offset(i as isize) = render_buffer.indices[i];
+        }
+    }
+    count as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_get_index_array_u16(context: *mut RustContext, render_buffer_id: c_int, buffer: *mut c_ushort, max_buffer_len: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    let indices = match ctx.render_buffers.get((render_buffer_id - 1) as usize).unwrap().indices_u16() {
+        Some(indices) => indices,
+        None => return -1,
+    };
+    let count = cmp::min(indices.len(), max_buffer_len as usize);
+    for i in 0..count {
+        unsafe {
+            *buffer.offset(i as isize) = indices[i];
+        }
+    }
+    count as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_optimize(context: *mut RustContext, render_buffer_id: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    ctx.render_buffers.get_mut((render_buffer_id - 1) as usize).unwrap().optimize();
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn meshlite_render_buffer_destroy(context: *mut RustContext, render_buffer_id: c_int) -> c_int {
+    let ctx = unsafe {
+        assert!(!context.is_null());
+        &mut *context
+    };
+    assert_eq!(ctx.magic, MAGIC_NUM);
+    free_render_buffer_id(ctx, render_buffer_id);
+    0
+}
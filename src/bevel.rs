@@ -0,0 +1,437 @@
+use cgmath::Point3;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use mesh::EdgeEndpoints;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use util::*;
+
+/// Lower bound of the sines used to turn the bevel width into offsets along
+/// the edges, which keeps the offsets of very sharp corners finite.
+const MIN_OFFSET_SINE: f32 = 0.2;
+
+/// Chamfers or rounds selected edges or vertices. The width is the distance
+/// of the new border from the beveled edge (or from the vertex, measured
+/// along its edges), segments is the number of faces across the bevel and
+/// profile shapes them: 0 gives a flat chamfer, 0.5 a round profile and 1 an
+/// almost square one. Only edges and vertices with a single closed fan of
+/// faces around them are beveled, the others are left as they are.
+pub trait Bevel {
+    fn bevel_edges(&self, edges: &[(Id, Id)], width: f32, segments: usize, profile: f32) -> Self;
+    fn bevel_vertices(&self, vertices: &[Id], width: f32, segments: usize, profile: f32) -> Self;
+}
+
+/// One face around a vertex, the outgoing halfedge is on the edge shared
+/// with the next corner of the fan.
+struct FanCorner {
+    face: Id,
+    outgoing: Id,
+}
+
+fn vertex_fan(mesh: &Mesh, vert_id: Id) -> Option<Vec<FanCorner>> {
+    let vertex = mesh.vertex(vert_id)?;
+    let start = *vertex.halfedges.first()?;
+    let mut fan = Vec::new();
+    let mut outgoing = start;
+    loop {
+        fan.push(FanCorner {
+            face: mesh.halfedge_face_id(outgoing)?,
+            outgoing: outgoing,
+        });
+        if fan.len() > vertex.halfedges.len() {
+            return None;
+        }
+        outgoing = mesh.halfedge_next_id(mesh.halfedge_opposite_id(outgoing)?)?;
+        if outgoing == start {
+            break;
+        }
+    }
+    if fan.len() != vertex.halfedges.len() {
+        return None;
+    }
+    Some(fan)
+}
+
+/// Removes repeated points and spikes (a, b, a) from the closed ring, which
+/// appear where two bevels share their profile.
+fn simplify_ring(mut ring: Vec<usize>) -> Vec<usize> {
+    loop {
+        let count = ring.len();
+        ring.dedup();
+        while ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() >= 3 {
+            for i in 0..ring.len() {
+                if ring[i] == ring[(i + 2) % ring.len()] {
+                    let middle = (i + 1) % ring.len();
+                    let last = (i + 2) % ring.len();
+                    let (first_removed, second_removed) = if middle < last { (last, middle) } else { (middle, last) };
+                    ring.remove(first_removed);
+                    ring.remove(second_removed);
+                    break;
+                }
+            }
+        }
+        if ring.len() == count || ring.len() < 3 {
+            return ring;
+        }
+    }
+}
+
+struct BevelBuilder<'a> {
+    mesh: &'a Mesh,
+    width: f32,
+    segments: usize,
+    profile: f32,
+    points: Vec<(Point3<f32>, i32)>,
+    vertex_points: HashMap<Id, usize>,
+
+    /// Points replacing the corner of a face at a beveled vertex, keyed by
+    /// face and vertex.
+    corner_points: HashMap<(Id, Id), Vec<usize>>,
+
+    /// Profile at the start vertex of a beveled halfedge, from the side of its
+    /// face to the side of the opposite face.
+    profiles: HashMap<Id, Vec<usize>>,
+
+    polygons: Vec<Vec<usize>>,
+}
+
+impl<'a> BevelBuilder<'a> {
+    fn new(mesh: &'a Mesh, width: f32, segments: usize, profile: f32) -> Self {
+        BevelBuilder {
+            mesh: mesh,
+            width: width,
+            segments: segments.max(1),
+            profile: profile,
+            points: Vec::new(),
+            vertex_points: HashMap::new(),
+            corner_points: HashMap::new(),
+            profiles: HashMap::new(),
+            polygons: Vec::new(),
+        }
+    }
+
+    fn add_point(&mut self, position: Point3<f32>, source: i32) -> usize {
+        self.points.push((position, source));
+        self.points.len() - 1
+    }
+
+    fn vertex_point(&mut self, vert_id: Id) -> usize {
+        if let Some(&point) = self.vertex_points.get(&vert_id) {
+            return point;
+        }
+        let position = self.mesh.vertex_position(vert_id).unwrap();
+        let source = self.mesh.vertex_source(vert_id).unwrap();
+        let point = self.add_point(position, source);
+        self.vertex_points.insert(vert_id, point);
+        point
+    }
+
+    /// Unit direction and length of the edge of the halfedge, seen from its start.
+    fn edge_direction(&self, halfedge_id: Id) -> (Vector3<f32>, f32) {
+        let direct = self.mesh.halfedge_direct(halfedge_id);
+        let length = direct.magnitude();
+        if length > SMALL_NUM {
+            (direct / length, length)
+        } else {
+            (direct, length)
+        }
+    }
+
+    /// Points from one end of a bevel to the other, both ends included,
+    /// bending towards the corner according to the profile.
+    fn profile_points(&mut self, from: usize, to: usize, corner: Point3<f32>, source: i32) -> Vec<usize> {
+        let start = self.points[from].0;
+        let end = self.points[to].0;
+        let middle = Point3::midpoint(start, end);
+        let control = middle + (corner - middle) * (2.0 * self.profile);
+        let mut result = vec![from];
+        for i in 1..self.segments {
+            let t = i as f32 / self.segments as f32;
+            let position = Point3::from_vec(start.to_vec() * ((1.0 - t) * (1.0 - t)) +
+                control.to_vec() * (2.0 * t * (1.0 - t)) +
+                end.to_vec() * (t * t));
+            result.push(self.add_point(position, source));
+        }
+        result.push(to);
+        result
+    }
+
+    /// Fills the hole left by a beveled vertex, larger patches are split
+    /// around a center point which follows the profile.
+    fn add_cap(&mut self, cap: Vec<usize>, corner: Point3<f32>, source: i32) {
+        if cap.len() < 3 {
+            return;
+        }
+        if cap.len() <= 4 {
+            self.polygons.push(cap);
+            return;
+        }
+        let positions : Vec<Point3<f32>> = cap.iter().map(|&point| self.points[point].0).collect();
+        let centroid = Point3::centroid(&positions);
+        let center = self.add_point(centroid + (corner - centroid) * self.profile, source);
+        if cap.len() % 2 == 1 {
+            for i in 0..cap.len() {
+                self.polygons.push(vec![center, cap[i], cap[(i + 1) % cap.len()]]);
+            }
+        } else {
+            for i in (0..cap.len()).step_by(2) {
+                self.polygons.push(vec![center, cap[i], cap[i + 1], cap[(i + 2) % cap.len()]]);
+            }
+        }
+    }
+
+    fn bevel_vertex_of_edges(&mut self, vert_id: Id, fan: &[FanCorner], beveled_edges: &HashSet<EdgeEndpoints>) {
+        let count = fan.len();
+        let position = self.mesh.vertex_position(vert_id).unwrap();
+        let source = self.mesh.vertex_source(vert_id).unwrap();
+        let is_beveled : Vec<bool> = fan.iter().map(|corner| {
+            let to_vert_id = self.mesh.halfedge_start_vertex_id(self.mesh.halfedge_next_id(corner.outgoing).unwrap()).unwrap();
+            beveled_edges.contains(&EdgeEndpoints::new(vert_id, to_vert_id))
+        }).collect();
+        let directions : Vec<(Vector3<f32>, f32)> = fan.iter().map(|corner| self.edge_direction(corner.outgoing)).collect();
+
+        // Faces with a beveled edge on one side slide their corner along the other edge
+        let mut slide_sums = vec![(0.0, 0); count];
+        for i in 0..count {
+            let before = (i + count - 1) % count;
+            if is_beveled[before] == is_beveled[i] {
+                continue;
+            }
+            let sine = directions[before].0.cross(directions[i].0).magnitude().max(MIN_OFFSET_SINE);
+            let other = if is_beveled[i] { before } else { i };
+            slide_sums[other].0 += self.width / sine;
+            slide_sums[other].1 += 1;
+        }
+        let mut slide_points : Vec<Option<usize>> = Vec::new();
+        for i in 0..count {
+            let (sum, used) = slide_sums[i];
+            if used == 0 {
+                slide_points.push(None);
+                continue;
+            }
+            let (direction, length) = directions[i];
+            let distance = (sum / used as f32).min(length * 0.5);
+            slide_points.push(Some(self.add_point(position + direction * distance, source)));
+        }
+
+        let mut corners : Vec<Vec<usize>> = Vec::new();
+        for i in 0..count {
+            let before = (i + count - 1) % count;
+            let points = match (is_beveled[before], is_beveled[i]) {
+                (true, true) => {
+                    let (before_direction, _) = directions[before];
+                    let (direction, _) = directions[i];
+                    let bisector = before_direction + direction;
+                    let offset = if bisector.magnitude2() > SMALL_NUM {
+                        let half_sine = (0.5 * (1.0 - before_direction.dot(direction))).max(0.0).sqrt().max(MIN_OFFSET_SINE);
+                        bisector.normalize() * (self.width / half_sine)
+                    } else {
+                        self.mesh.face_norm(fan[i].face).cross(direction) * self.width
+                    };
+                    vec![self.add_point(position + offset, source)]
+                },
+                (false, true) => vec![slide_points[before].unwrap()],
+                (true, false) => vec![slide_points[i].unwrap()],
+                (false, false) => {
+                    let mut points = Vec::new();
+                    points.extend(slide_points[before]);
+                    points.push(self.vertex_point(vert_id));
+                    points.extend(slide_points[i]);
+                    points
+                },
+            };
+            corners.push(points);
+        }
+
+        let mut profile_lists : Vec<Option<Vec<usize>>> = Vec::new();
+        for i in 0..count {
+            if !is_beveled[i] {
+                profile_lists.push(None);
+                continue;
+            }
+            let from = *corners[i].last().unwrap();
+            let to = corners[(i + 1) % count][0];
+            // Two bevels between the same pair of points share their profile
+            let shared = profile_lists.iter().filter_map(|list| list.as_ref()).find(|list| {
+                list[0] == to && *list.last().unwrap() == from
+            }).map(|list| list.iter().rev().cloned().collect::<Vec<usize>>());
+            let points = match shared {
+                Some(points) => points,
+                None => self.profile_points(from, to, position, source),
+            };
+            self.profiles.insert(fan[i].outgoing, points.clone());
+            profile_lists.push(Some(points));
+        }
+
+        let mut ring = Vec::new();
+        for i in 0..count {
+            ring.extend(corners[i].iter().cloned());
+            if let Some(ref points) = profile_lists[i] {
+                ring.extend(points[1..points.len() - 1].iter().cloned());
+            }
+        }
+        let mut cap = simplify_ring(ring);
+        cap.reverse();
+        let kept_point = self.vertex_points.get(&vert_id).cloned();
+        let keeping : Vec<usize> = (0..count).filter(|&i| kept_point.is_some() && corners[i].contains(&kept_point.unwrap())).collect();
+        let mut absorbed = false;
+        if cap.len() >= 3 && keeping.len() == 1 && corners[keeping[0]].len() == 3 {
+            // The only face still using the vertex takes over the cap
+            let corner = &corners[keeping[0]];
+            let (first, last) = (corner[0], corner[2]);
+            if let Some(start) = cap.iter().position(|&point| point == first) {
+                let mut path = Vec::new();
+                for offset in 0..cap.len() {
+                    let point = cap[(start + offset) % cap.len()];
+                    path.push(point);
+                    if point == last {
+                        break;
+                    }
+                }
+                if *path.last().unwrap() == last && !path.contains(&kept_point.unwrap()) {
+                    corners[keeping[0]] = path;
+                    absorbed = true;
+                }
+            }
+        }
+        if !absorbed {
+            self.add_cap(cap, position, source);
+        }
+        for (i, points) in corners.into_iter().enumerate() {
+            self.corner_points.insert((fan[i].face, vert_id), points);
+        }
+    }
+
+    fn bevel_single_vertex(&mut self, vert_id: Id, fan: &[FanCorner]) {
+        let count = fan.len();
+        let position = self.mesh.vertex_position(vert_id).unwrap();
+        let source = self.mesh.vertex_source(vert_id).unwrap();
+        let mut slide_points = Vec::new();
+        for corner in fan.iter() {
+            let (direction, length) = self.edge_direction(corner.outgoing);
+            slide_points.push(self.add_point(position + direction * self.width.min(length * 0.5), source));
+        }
+        let mut ring = Vec::new();
+        for i in 0..count {
+            let before = (i + count - 1) % count;
+            let points = self.profile_points(slide_points[before], slide_points[i], position, source);
+            ring.extend(points.iter().cloned());
+            self.corner_points.insert((fan[i].face, vert_id), points);
+        }
+        let mut cap = simplify_ring(ring);
+        cap.reverse();
+        self.add_cap(cap, position, source);
+    }
+
+    fn add_strips(&mut self, halfedges: &[Id]) {
+        for &halfedge_id in halfedges {
+            let opposite_id = self.mesh.halfedge_opposite_id(halfedge_id).unwrap();
+            let start_profile = self.profiles[&halfedge_id].clone();
+            let end_profile : Vec<usize> = self.profiles[&opposite_id].iter().rev().cloned().collect();
+            for i in 0..start_profile.len() - 1 {
+                let mut polygon = vec![end_profile[i], start_profile[i], start_profile[i + 1], end_profile[i + 1]];
+                polygon.dedup();
+                if polygon.len() >= 3 && polygon.first() != polygon.last() {
+                    self.polygons.push(polygon);
+                }
+            }
+        }
+    }
+
+    fn add_faces(&mut self) {
+        for face_id in FaceIterator::new(self.mesh) {
+            let mut polygon = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(self.mesh, self.mesh.face_first_halfedge_id(face_id).unwrap()) {
+                let vert_id = self.mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
+                match self.corner_points.get(&(face_id, vert_id)) {
+                    Some(points) => polygon.extend(points.iter().cloned()),
+                    None => polygon.push(self.vertex_point(vert_id)),
+                }
+            }
+            polygon.dedup();
+            while polygon.len() > 1 && polygon.first() == polygon.last() {
+                polygon.pop();
+            }
+            if polygon.len() >= 3 {
+                self.polygons.push(polygon);
+            }
+        }
+    }
+
+    fn build(self) -> Mesh {
+        let mut mesh = Mesh::new();
+        let mut point_vert_map : HashMap<usize, Id> = HashMap::new();
+        for polygon in self.polygons.iter() {
+            let mut added_vertices = Vec::new();
+            for &point in polygon.iter() {
+                let vert_id = *point_vert_map.entry(point).or_insert_with(|| {
+                    let vert_id = mesh.add_vertex(self.points[point].0);
+                    mesh.set_vertex_source(vert_id, self.points[point].1);
+                    vert_id
+                });
+                added_vertices.push(vert_id);
+            }
+            mesh.add_vertices(added_vertices);
+        }
+        mesh
+    }
+}
+
+impl Bevel for Mesh {
+    fn bevel_edges(&self, edges: &[(Id, Id)], width: f32, segments: usize, profile: f32) -> Self {
+        let mut fans : HashMap<Id, Option<Vec<FanCorner>>> = HashMap::new();
+        let mut beveled_edges : HashSet<EdgeEndpoints> = HashSet::new();
+        let mut halfedges = Vec::new();
+        for &(first, second) in edges {
+            let endpoints = EdgeEndpoints::new(first, second);
+            let halfedge_id = match self.edge_halfedge_id(first, second) {
+                Some(halfedge_id) => halfedge_id,
+                None => continue,
+            };
+            if self.halfedge(halfedge_id).is_none() || self.halfedge_opposite_id(halfedge_id).is_none() ||
+                    beveled_edges.contains(&endpoints) {
+                continue;
+            }
+            let is_manifold = [first, second].iter().all(|&vert_id| {
+                fans.entry(vert_id).or_insert_with(|| vertex_fan(self, vert_id)).is_some()
+            });
+            if is_manifold {
+                beveled_edges.insert(endpoints);
+                halfedges.push(halfedge_id);
+            }
+        }
+        let mut builder = BevelBuilder::new(self, width, segments, profile);
+        let mut vert_ids : Vec<Id> = fans.keys().cloned().collect();
+        vert_ids.sort();
+        for vert_id in vert_ids {
+            if let Some(ref fan) = fans[&vert_id] {
+                builder.bevel_vertex_of_edges(vert_id, fan, &beveled_edges);
+            }
+        }
+        builder.add_faces();
+        builder.add_strips(&halfedges);
+        builder.build()
+    }
+
+    fn bevel_vertices(&self, vertices: &[Id], width: f32, segments: usize, profile: f32) -> Self {
+        let mut builder = BevelBuilder::new(self, width, segments, profile);
+        let mut visited : HashSet<Id> = HashSet::new();
+        for &vert_id in vertices {
+            if !visited.insert(vert_id) {
+                continue;
+            }
+            if let Some(fan) = vertex_fan(self, vert_id) {
+                builder.bevel_single_vertex(vert_id, &fan);
+            }
+        }
+        builder.add_faces();
+        builder.build()
+    }
+}
@@ -0,0 +1,255 @@
+use cgmath::Point3;
+use iterator::FaceIterator;
+use iterator::FaceVertexIterator;
+use iterator::VertexIterator;
+use mesh::Id;
+use mesh::MAX_FACE_VERTEX_COUNT;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+
+const MAGIC: &[u8; 4] = b"MLMB";
+
+/// Version written by write_binary, read_binary accepts this and all older
+/// versions.
+pub const BINARY_FORMAT_VERSION: u16 = 1;
+
+const SOURCE_ATTRIBUTE: &[u8; 4] = b"SRCE";
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Upper bound for preallocating from the counts in the header, so a broken
+/// file fails on reading instead of on allocating.
+const MAX_PREALLOCATE: usize = 1 << 16;
+
+/// Reads and writes meshes in the native binary format, all values little
+/// endian:
+///
+/// - magic "MLMB", u16 version, u16 flags (reserved, zero)
+/// - u32 vertex count, u32 face count, u32 index count
+/// - vertex positions as f32 x, y, z
+/// - u32 vertex count of each face, then the u32 vertex indices of all faces
+/// - u32 attribute count, each attribute a four byte tag, a u32 byte length
+///   and the data; readers skip tags they do not know
+/// - u32 FNV-1a checksum of all the bytes before it
+///
+/// Only alive elements are written, the vertices are renumbered in order.
+/// The source of each vertex is kept in the "SRCE" attribute as i32.
+///
+/// Both directions are buffered internally, so read_binary may consume bytes
+/// past the end of the mesh from the reader.
+pub trait BinaryFormat {
+    fn write_binary<W: Write>(&self, writer: W) -> io::Result<()>;
+    fn read_binary<R: Read>(reader: R) -> io::Result<Self> where Self: Sized;
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn fnv_update(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u32).wrapping_mul(FNV_PRIME))
+}
+
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    hash: u32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hash = fnv_update(self.hash, bytes);
+        self.inner.write_all(bytes)
+    }
+
+    fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    fn write_f32(&mut self, value: f32) -> io::Result<()> {
+        self.write_bytes(&value.to_bits().to_le_bytes())
+    }
+}
+
+struct ChecksumReader<R: Read> {
+    inner: R,
+    hash: u32,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(bytes)?;
+        self.hash = fnv_update(self.hash, bytes);
+        Ok(())
+    }
+
+    fn read_u16(&mut self) -> io::Result<u16> {
+        let mut bytes = [0; 2];
+        self.read_bytes(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.read_bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    fn skip(&mut self, length: usize) -> io::Result<()> {
+        let mut remaining = length;
+        let mut buffer = [0; 256];
+        while remaining > 0 {
+            let count = remaining.min(buffer.len());
+            self.read_bytes(&mut buffer[..count])?;
+            remaining -= count;
+        }
+        Ok(())
+    }
+}
+
+fn to_u32(value: usize) -> io::Result<u32> {
+    if value > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "mesh too large for the binary format"));
+    }
+    Ok(value as u32)
+}
+
+impl BinaryFormat for Mesh {
+    fn write_binary<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = ChecksumWriter { inner: BufWriter::new(writer), hash: FNV_OFFSET_BASIS };
+        let vertices = VertexIterator::new(self).into_vec();
+        let vertex_index_map : HashMap<Id, u32> = vertices.iter().enumerate().map(|(index, &vert_id)| (vert_id, index as u32)).collect();
+        let faces : Vec<Vec<Id>> = FaceIterator::new(self).map(|face_id| FaceVertexIterator::new(self, face_id).into_vec()).collect();
+        let index_count : usize = faces.iter().map(|face| face.len()).sum();
+        writer.write_bytes(MAGIC)?;
+        writer.write_u16(BINARY_FORMAT_VERSION)?;
+        writer.write_u16(0)?;
+        writer.write_u32(to_u32(vertices.len())?)?;
+        writer.write_u32(to_u32(faces.len())?)?;
+        writer.write_u32(to_u32(index_count)?)?;
+        for &vert_id in vertices.iter() {
+            let position = self.vertex_position(vert_id).unwrap();
+            writer.write_f32(position.x)?;
+            writer.write_f32(position.y)?;
+            writer.write_f32(position.z)?;
+        }
+        for face in faces.iter() {
+            writer.write_u32(face.len() as u32)?;
+        }
+        for face in faces.iter() {
+            for vert_id in face.iter() {
+                writer.write_u32(vertex_index_map[vert_id])?;
+            }
+        }
+        writer.write_u32(1)?;
+        writer.write_bytes(SOURCE_ATTRIBUTE)?;
+        writer.write_u32(to_u32(vertices.len() * 4)?)?;
+        for &vert_id in vertices.iter() {
+            writer.write_bytes(&self.vertex_source(vert_id).unwrap().to_le_bytes())?;
+        }
+        let checksum = writer.hash;
+        writer.inner.write_all(&checksum.to_le_bytes())?;
+        writer.inner.flush()
+    }
+
+    fn read_binary<R: Read>(reader: R) -> io::Result<Self> {
+        let mut reader = ChecksumReader { inner: BufReader::new(reader), hash: FNV_OFFSET_BASIS };
+        let mut magic = [0; 4];
+        reader.read_bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a meshlite binary mesh"));
+        }
+        let version = reader.read_u16()?;
+        if version == 0 || version > BINARY_FORMAT_VERSION {
+            return Err(invalid_data("unsupported binary mesh version"));
+        }
+        let _flags = reader.read_u16()?;
+        let vertex_count = reader.read_u32()? as usize;
+        let face_count = reader.read_u32()? as usize;
+        let index_count = reader.read_u32()? as usize;
+        let mut positions = Vec::with_capacity(vertex_count.min(MAX_PREALLOCATE));
+        for _ in 0..vertex_count {
+            let x = reader.read_f32()?;
+            let y = reader.read_f32()?;
+            let z = reader.read_f32()?;
+            positions.push(Point3::new(x, y, z));
+        }
+        let mut face_sizes = Vec::with_capacity(face_count.min(MAX_PREALLOCATE));
+        let mut total = 0;
+        for _ in 0..face_count {
+            let size = reader.read_u32()? as usize;
+            if size < 3 {
+                return Err(invalid_data("face with less than three vertices"));
+            }
+            if size >= MAX_FACE_VERTEX_COUNT {
+                return Err(invalid_data("face with too many vertices"));
+            }
+            total += size;
+            face_sizes.push(size);
+        }
+        if total != index_count {
+            return Err(invalid_data("face sizes do not match the index count"));
+        }
+        let mut indices = Vec::with_capacity(index_count.min(MAX_PREALLOCATE));
+        for _ in 0..index_count {
+            let index = reader.read_u32()? as usize;
+            if index >= vertex_count {
+                return Err(invalid_data("vertex index out of range"));
+            }
+            indices.push(index);
+        }
+        let mut sources : Option<Vec<i32>> = None;
+        let attribute_count = reader.read_u32()?;
+        for _ in 0..attribute_count {
+            let mut tag = [0; 4];
+            reader.read_bytes(&mut tag)?;
+            let length = reader.read_u32()? as usize;
+            if &tag == SOURCE_ATTRIBUTE && length == vertex_count * 4 {
+                let mut values = Vec::with_capacity(vertex_count.min(MAX_PREALLOCATE));
+                for _ in 0..vertex_count {
+                    values.push(reader.read_u32()? as i32);
+                }
+                sources = Some(values);
+            } else {
+                reader.skip(length)?;
+            }
+        }
+        let expected = reader.hash;
+        let mut checksum = [0; 4];
+        reader.inner.read_exact(&mut checksum)?;
+        if u32::from_le_bytes(checksum) != expected {
+            return Err(invalid_data("binary mesh checksum mismatch"));
+        }
+
+        let mut mesh = Mesh::new();
+        let mut vert_ids = Vec::with_capacity(positions.len());
+        for (index, &position) in positions.iter().enumerate() {
+            let vert_id = mesh.add_vertex(position);
+            if let Some(ref sources) = sources {
+                mesh.set_vertex_source(vert_id, sources[index]);
+            }
+            vert_ids.push(vert_id);
+        }
+        let mut offset = 0;
+        for size in face_sizes {
+            if 0 == mesh.add_vertices(indices[offset..offset + size].iter().map(|&index| vert_ids[index]).collect()) {
+                return Err(invalid_data("face could not be added"));
+            }
+            offset += size;
+        }
+        Ok(mesh)
+    }
+}
@@ -6,12 +6,14 @@ use mesh::Id;
 use mesh::Mesh;
 use petgraph::Graph;
 use petgraph::prelude::*;
+use selfintersect::SelfIntersect;
 use std::collections::HashMap;
 use subdivide::Subdivide;
 use triangulate::Triangulate;
 use util::*;
 use wrap::GiftWrapper;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Node {
     radius: f32,
     position: Point3<f32>,
@@ -27,10 +29,12 @@ struct Node {
     insert_order: isize,
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Edge {
     cuts: Vec<(Vec<Id>, Vector3<f32>)>,
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Bmesh {
     graph : Graph<Node, Edge, Undirected>,
     mesh: Mesh,
@@ -39,6 +43,7 @@ pub struct Bmesh {
     wrap_error_count: i32,
     node_count: usize,
     debug_enabled: bool,
+    debug_self_intersections: bool,
     cut_subdiv_count: usize,
     round_way: i32,
     deform_thickness: f32,
@@ -61,6 +66,7 @@ impl Bmesh {
             wrap_error_count: 0,
             node_count: 0,
             debug_enabled: false,
+            debug_self_intersections: false,
             cut_subdiv_count: 0,
             round_way: 0,
             deform_thickness: 1.0,
@@ -94,6 +100,12 @@ impl Bmesh {
         self.debug_enabled = enable;
     }
 
+    /// Also adds the intersecting face pairs of the generated mesh to the
+    /// debug output, which takes a self intersection test of the whole mesh.
+    pub fn enable_debug_self_intersections(&mut self, enable: bool) {
+        self.debug_self_intersections = enable;
+    }
+
     pub fn add_seam_requirement(&mut self) {
         self.seam_required = true;
     }
@@ -224,12 +236,18 @@ impl Bmesh {
 
     fn output_debug_info_if_enabled(&mut self) {
         if self.debug_enabled {
+            let intersections = if self.debug_self_intersections {
+                self.mesh.self_intersections()
+            } else {
+                Vec::new()
+            };
             for &(node_id, _, _) in self.neighbor_count_vec.iter() {
                 let node_index = NodeIndex::new(node_id);
                 let node_origin = self.graph.node_weight(node_index).unwrap().position;
                 let base_norm = self.graph.node_weight(node_index).unwrap().base_norm;
                 self.mesh.add_debug_norm(node_origin, base_norm);
             }
+            self.mesh.add_debug_intersections(&intersections);
         }
     }
 
@@ -492,7 +510,7 @@ impl Bmesh {
             for &vertex_id in cut.0.iter() {
                 self.vertex_node_map.insert(vertex_id, node_index);
                 self.vertex_cut_direct_map.insert(vertex_id, cut.1);
-                self.mesh.vertex_mut(vertex_id).unwrap().source = user_node_id as i32;
+                self.mesh.set_vertex_source(vertex_id, user_node_id as i32);
                 generated_vertices.push(vertex_id);
             }
             {
@@ -523,21 +541,22 @@ impl Bmesh {
     }
 
     fn resolve_deform(&mut self) {
-        for vert in self.mesh.vertices.iter_mut() {
+        for (index, position) in self.mesh.vertices.position.iter_mut().enumerate() {
+            let vert_id = index + 1;
             let node_index = if self.vertex_node_map.is_empty() {
                 NodeIndex::new(self.last_node_id)
             } else {
-                self.vertex_node_map[&vert.id]
+                self.vertex_node_map[&vert_id]
             };
             let node_base_norm = self.graph.node_weight(node_index).unwrap().base_norm;
             let node_position = self.graph.node_weight(node_index).unwrap().position;
-            let vert_ray = vert.position - node_position;
+            let vert_ray = *position - node_position;
             let mut sum_x = 0.0;
             let mut sum_y = 0.0;
             let mut sum_z = 0.0;
             let mut num = 0;
             if (self.deform_thickness - 1.0).abs() > SMALL_NUM {
-                let thickness_deformed_position = calculate_deform_position(vert.position,
+                let thickness_deformed_position = calculate_deform_position(*position,
                     vert_ray, node_base_norm, self.deform_thickness);
                 sum_x += thickness_deformed_position.x;
                 sum_y += thickness_deformed_position.y;
@@ -545,8 +564,8 @@ impl Bmesh {
                 num += 1;
             }
             if (self.deform_width - 1.0).abs() > SMALL_NUM {
-                if let Some(&cut_direct) = self.vertex_cut_direct_map.get(&vert.id) {
-                    let width_deformed_position = calculate_deform_position(vert.position,
+                if let Some(&cut_direct) = self.vertex_cut_direct_map.get(&vert_id) {
+                    let width_deformed_position = calculate_deform_position(*position,
                         vert_ray, node_base_norm.cross(cut_direct), self.deform_width);
                     sum_x += width_deformed_position.x;
                     sum_y += width_deformed_position.y;
@@ -555,9 +574,9 @@ impl Bmesh {
                 }
             }
             if num > 0 {
-                vert.position.x = sum_x / num as f32;
-                vert.position.y = sum_y / num as f32;
-                vert.position.z = sum_z / num as f32;
+                position.x = sum_x / num as f32;
+                position.y = sum_y / num as f32;
+                position.z = sum_z / num as f32;
             }
         }
     }
@@ -566,7 +585,7 @@ impl Bmesh {
         // Check seam_resolved in caller, don't check it here
         self.graph.node_weight_mut(node_index).unwrap().seam_resolved = true;
         seam.push(from_vertex_id);
-        let halfedges = self.mesh.vertex(from_vertex_id).unwrap().halfedges.clone();
+        let halfedges : Vec<Id> = self.mesh.vertices.halfedges[from_vertex_id - 1].iter().map(|&halfedge_id| halfedge_id as Id).collect();
         let mut ids = Vec::new();
         for halfedge_id in halfedges {
             let next_halfedge_id = self.mesh.halfedge_next_id(halfedge_id);
@@ -574,7 +593,7 @@ impl Bmesh {
                 continue;
             }
             let vertex_id = self.mesh.halfedge_start_vertex_id(next_halfedge_id.unwrap()).unwrap();
-            let next_node_id = self.mesh.vertex_mut(vertex_id).unwrap().source as usize;
+            let next_node_id = self.mesh.vertex_source(vertex_id).unwrap() as usize;
             if next_node_id == node_index.index() {
                 continue;
             }
@@ -585,14 +604,14 @@ impl Bmesh {
         }
         let mut positions = Vec::new();
         for &vert_id in ids.iter() {
-            positions.push(self.mesh.vertex(vert_id).unwrap().position);
+            positions.push(self.mesh.vertex_position(vert_id).unwrap());
         }
         if positions.is_empty() {
             return;
         }
         let vertex_index = pick_most_not_obvious_vertex(positions);
         let next_from_vertex_id = ids[vertex_index];
-        let next_node_index = NodeIndex::new(self.mesh.vertex_mut(next_from_vertex_id).unwrap().source as usize);
+        let next_node_index = NodeIndex::new(self.mesh.vertex_source(next_from_vertex_id).unwrap() as usize);
         if self.neighbor_count_map[&next_node_index.index()] != 2 {
             return;
         }
@@ -612,7 +631,7 @@ impl Bmesh {
                 for vertices in generated_vertices {
                     let mut positions = Vec::new();
                     for &vert_id in vertices.iter() {
-                        positions.push(self.mesh.vertex(vert_id).unwrap().position);
+                        positions.push(self.mesh.vertex_position(vert_id).unwrap());
                     }
                     if positions.is_empty() {
                         continue;
@@ -661,8 +680,8 @@ impl Bmesh {
                 let subdived_mesh = self.mesh.subdivide();
                 self.mesh = subdived_mesh;
             }
-            for vertex in self.mesh.vertices.iter_mut() {
-                vertex.source = root_node_id as i32;
+            for source in self.mesh.vertices.source.iter_mut() {
+                *source = root_node_id as i32;
             }
             if (self.deform_thickness - 1.0).abs() > SMALL_NUM ||
                     (self.deform_width - 1.0).abs() > SMALL_NUM {
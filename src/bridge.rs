@@ -0,0 +1,123 @@
+use cgmath::Point3;
+use cgmath::prelude::*;
+use mesh::Id;
+use mesh::Mesh;
+use std::f32;
+
+/// Connects two boundary loops of the same mesh with a band of faces. The
+/// loops are given as their boundary halfedges in order, as returned by
+/// Mesh::boundary_loops. The second loop is rotated to the alignment with the
+/// smallest distance between paired vertices, then shifted by twist vertices.
+/// When the vertex counts differ, the missing vertices are made up for with
+/// triangles spread evenly around the loop next to the smaller one. Segments
+/// is the number of rings of faces between the loops. Returns the added faces.
+pub trait Bridge {
+    fn bridge_loops(&mut self, first: &[Id], second: &[Id], segments: usize, twist: isize) -> Vec<Id>;
+}
+
+/// Index in the smaller loop which is paired with the index in the larger one.
+fn paired_index(index: usize, large_count: usize, small_count: usize) -> usize {
+    index * small_count / large_count
+}
+
+/// Position at the fractional index along the closed loop.
+fn loop_position(points: &[Point3<f32>], index: f32) -> Point3<f32> {
+    let count = points.len();
+    let base = index.floor() as usize % count;
+    let t = index - index.floor();
+    points[base] + (points[(base + 1) % count] - points[base]) * t
+}
+
+/// Adds the faces between two aligned rings, the upper ring is walked backward
+/// by the new faces and the lower ring forward.
+fn zip_rings(mesh: &mut Mesh, upper: &[Id], lower: &[Id], added_faces: &mut Vec<Id>) {
+    let upper_count = upper.len();
+    let lower_count = lower.len();
+    let steps = upper_count.max(lower_count);
+    for k in 0..steps {
+        let (i, next_i) = if upper_count >= lower_count {
+            (k, k + 1)
+        } else {
+            (paired_index(k, lower_count, upper_count), paired_index(k + 1, lower_count, upper_count))
+        };
+        let (j, next_j) = if lower_count >= upper_count {
+            (k, k + 1)
+        } else {
+            (paired_index(k, upper_count, lower_count), paired_index(k + 1, upper_count, lower_count))
+        };
+        let mut vertices = Vec::new();
+        if next_i != i {
+            vertices.push(upper[next_i % upper_count]);
+        }
+        vertices.push(upper[i % upper_count]);
+        vertices.push(lower[j % lower_count]);
+        if next_j != j {
+            vertices.push(lower[next_j % lower_count]);
+        }
+        added_faces.push(mesh.add_vertices(vertices));
+    }
+}
+
+impl Bridge for Mesh {
+    fn bridge_loops(&mut self, first: &[Id], second: &[Id], segments: usize, twist: isize) -> Vec<Id> {
+        let mut added_faces = Vec::new();
+        if first.len() < 3 || second.len() < 3 {
+            return added_faces;
+        }
+        let upper : Vec<Id> = first.iter().map(|&halfedge_id| self.halfedge_start_vertex_id(halfedge_id).unwrap()).collect();
+        // Boundaries facing each other run in opposite directions
+        let mut lower : Vec<Id> = second.iter().rev().map(|&halfedge_id| self.halfedge_start_vertex_id(halfedge_id).unwrap()).collect();
+        if upper.iter().any(|vert_id| lower.contains(vert_id)) {
+            return added_faces;
+        }
+        let upper_points : Vec<Point3<f32>> = upper.iter().map(|&vert_id| self.vertex_position(vert_id).unwrap()).collect();
+        let lower_points : Vec<Point3<f32>> = lower.iter().map(|&vert_id| self.vertex_position(vert_id).unwrap()).collect();
+        let upper_count = upper.len();
+        let lower_count = lower.len();
+        let steps = upper_count.max(lower_count);
+        let mut best_shift = 0;
+        let mut best_cost = f32::MAX;
+        for shift in 0..lower_count {
+            let mut cost = 0.0;
+            for k in 0..steps {
+                let i = if upper_count >= lower_count { k } else { paired_index(k, lower_count, upper_count) };
+                let j = if lower_count >= upper_count { k } else { paired_index(k, upper_count, lower_count) };
+                cost += upper_points[i].distance(lower_points[(j + shift) % lower_count]);
+            }
+            if cost < best_cost {
+                best_cost = cost;
+                best_shift = shift;
+            }
+        }
+        let shift = (best_shift as isize + twist).rem_euclid(lower_count as isize) as usize;
+        lower.rotate_left(shift);
+        let lower_points : Vec<Point3<f32>> = lower.iter().map(|&vert_id| self.vertex_position(vert_id).unwrap()).collect();
+
+        // Intermediate rings follow the larger loop, so only the band next to
+        // the smaller loop has to deal with the vertex count mismatch
+        let segments = segments.max(1);
+        let upper_is_large = upper_count >= lower_count;
+        let (large_points, small_points) = if upper_is_large {
+            (&upper_points, &lower_points)
+        } else {
+            (&lower_points, &upper_points)
+        };
+        let mut rings : Vec<Vec<Id>> = vec![upper.clone()];
+        for segment in 1..segments {
+            let t = segment as f32 / segments as f32;
+            let t = if upper_is_large { t } else { 1.0 - t };
+            let mut ring = Vec::new();
+            for (k, &large_position) in large_points.iter().enumerate() {
+                let small_index = k as f32 * small_points.len() as f32 / steps as f32;
+                let small_position = loop_position(small_points, small_index);
+                ring.push(self.add_vertex(large_position + (small_position - large_position) * t));
+            }
+            rings.push(ring);
+        }
+        rings.push(lower);
+        for i in 0..rings.len() - 1 {
+            zip_rings(self, &rings[i], &rings[i + 1], &mut added_faces);
+        }
+        added_faces
+    }
+}
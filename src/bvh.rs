@@ -0,0 +1,243 @@
+use cgmath::Point3;
+use cgmath::Vector3;
+use std::f32;
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// An inverted box which becomes valid after the first extend().
+    pub fn new() -> Self {
+        Aabb {
+            min: Point3 {x: f32::MAX, y: f32::MAX, z: f32::MAX},
+            max: Point3 {x: f32::MIN, y: f32::MIN, z: f32::MIN},
+        }
+    }
+
+    pub fn from_points(points: &[Point3<f32>]) -> Self {
+        let mut aabb = Aabb::new();
+        for &point in points {
+            aabb.extend(point);
+        }
+        aabb
+    }
+
+    pub fn extend(&mut self, point: Point3<f32>) {
+        self.min.x = self.min.x.min(point.x);
+        self.min.y = self.min.y.min(point.y);
+        self.min.z = self.min.z.min(point.z);
+        self.max.x = self.max.x.max(point.x);
+        self.max.y = self.max.y.max(point.y);
+        self.max.z = self.max.z.max(point.z);
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        aabb.extend(other.min);
+        aabb.extend(other.max);
+        aabb
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        Point3 {
+            x: (self.min.x + self.max.x) * 0.5,
+            y: (self.min.y + self.max.y) * 0.5,
+            z: (self.min.z + self.max.z) * 0.5,
+        }
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Returns 0, 1 or 2 for the x, y or z axis.
+    pub fn longest_axis(&self) -> usize {
+        let size = self.size();
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+            self.min.y <= other.max.y && self.max.y >= other.min.y &&
+            self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    /// Squared distance from the point to the box, zero if the point is inside.
+    pub fn distance2_to_point(&self, point: Point3<f32>) -> f32 {
+        let dx = (self.min.x - point.x).max(0.0).max(point.x - self.max.x);
+        let dy = (self.min.y - point.y).max(0.0).max(point.y - self.max.y);
+        let dz = (self.min.z - point.z).max(0.0).max(point.z - self.max.z);
+        dx * dx + dy * dy + dz * dz
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::new()
+    }
+}
+
+fn axis_value(point: Point3<f32>, axis: usize) -> f32 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+/// Leaves have a non zero count and refer to items[first..first + count],
+/// inner nodes have two children. The root is always node 0, so a zero child
+/// index is never a valid child.
+#[derive(Debug)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    pub left: usize,
+    pub right: usize,
+    pub first: usize,
+    pub count: usize,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+const BVH_LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy over a list of item bounds, the items are
+/// referenced by their index in the list passed to new().
+#[derive(Debug)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub items: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(item_bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            items: (0..item_bounds.len()).collect(),
+        };
+        if !item_bounds.is_empty() {
+            bvh.nodes.reserve(item_bounds.len() * 2 / BVH_LEAF_SIZE + 1);
+            bvh.build_node(item_bounds, 0, item_bounds.len());
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, item_bounds: &[Aabb], first: usize, count: usize) -> usize {
+        let mut bounds = Aabb::new();
+        let mut center_bounds = Aabb::new();
+        for &item in self.items[first..first + count].iter() {
+            bounds = bounds.union(&item_bounds[item]);
+            center_bounds.extend(item_bounds[item].center());
+        }
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: bounds,
+            left: 0,
+            right: 0,
+            first: first,
+            count: count,
+        });
+        if count <= BVH_LEAF_SIZE {
+            return node_index;
+        }
+        // Median split along the longest axis of the item centers
+        let axis = center_bounds.longest_axis();
+        self.items[first..first + count].sort_by(|&a, &b| {
+            let a_value = axis_value(item_bounds[a].center(), axis);
+            let b_value = axis_value(item_bounds[b].center(), axis);
+            a_value.partial_cmp(&b_value).unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        let half = count / 2;
+        let left = self.build_node(item_bounds, first, half);
+        let right = self.build_node(item_bounds, first + half, count - half);
+        let node = &mut self.nodes[node_index];
+        node.left = left;
+        node.right = right;
+        node.count = 0;
+        node_index
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        if self.nodes.is_empty() {
+            return Aabb::new();
+        }
+        self.nodes[0].bounds
+    }
+
+    /// Collects the items whose bounds intersect with the given box.
+    pub fn query_aabb(&self, item_bounds: &[Aabb], bounds: &Aabb, result: &mut Vec<usize>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.intersects(bounds) {
+                continue;
+            }
+            if node.is_leaf() {
+                for &item in self.items[node.first..node.first + node.count].iter() {
+                    if item_bounds[item].intersects(bounds) {
+                        result.push(item);
+                    }
+                }
+            } else {
+                stack.push(node.left);
+                stack.push(node.right);
+            }
+        }
+    }
+
+    /// Finds the item with the smallest squared distance to the point, the
+    /// distance of each item is measured by the given function.
+    pub fn nearest<F>(&self, point: Point3<f32>, mut item_distance2: F) -> Option<(usize, f32)>
+        where F: FnMut(usize) -> f32 {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut best : Option<(usize, f32)> = None;
+        let mut best_distance2 = f32::MAX;
+        let mut stack = vec![(0, self.nodes[0].bounds.distance2_to_point(point))];
+        while let Some((node_index, node_distance2)) = stack.pop() {
+            if node_distance2 > best_distance2 {
+                continue;
+            }
+            let node = &self.nodes[node_index];
+            if node.is_leaf() {
+                for &item in self.items[node.first..node.first + node.count].iter() {
+                    let distance2 = item_distance2(item);
+                    if distance2 < best_distance2 {
+                        best_distance2 = distance2;
+                        best = Some((item, distance2));
+                    }
+                }
+            } else {
+                let left_distance2 = self.nodes[node.left].bounds.distance2_to_point(point);
+                let right_distance2 = self.nodes[node.right].bounds.distance2_to_point(point);
+                // Push the farther child first so the nearer one is visited first
+                if left_distance2 < right_distance2 {
+                    stack.push((node.right, right_distance2));
+                    stack.push((node.left, left_distance2));
+                } else {
+                    stack.push((node.left, left_distance2));
+                    stack.push((node.right, right_distance2));
+                }
+            }
+        }
+        best
+    }
+}
@@ -1,9 +1,13 @@
 use cgmath::{Point3, Vector3};
+use cgmath::prelude::*;
 use mesh::Mesh;
+use selfintersect::FaceIntersection;
 use util::*;
 
 pub trait Debug {
     fn add_debug_norm(&mut self, origin: Point3<f32>, norm: Vector3<f32>);
+    fn add_debug_segment(&mut self, from: Point3<f32>, to: Point3<f32>);
+    fn add_debug_intersections(&mut self, intersections: &[FaceIntersection]);
 }
 
 impl Debug for Mesh {
@@ -14,4 +18,24 @@ impl Debug for Mesh {
         m.extrude_face(face_id, norm, 0.5);
         self.add_mesh(&m);
     }
+
+    fn add_debug_segment(&mut self, from: Point3<f32>, to: Point3<f32>) {
+        let direct = to - from;
+        let length = direct.magnitude();
+        if length <= SMALL_NUM {
+            return;
+        }
+        let direct = direct / length;
+        let mut m = Mesh::new();
+        let quad = make_quad(from, direct, 0.005, direct);
+        let face_id = m.add_positions(quad);
+        m.extrude_face(face_id, direct, length);
+        self.add_mesh(&m);
+    }
+
+    fn add_debug_intersections(&mut self, intersections: &[FaceIntersection]) {
+        for intersection in intersections {
+            self.add_debug_segment(intersection.segment.0, intersection.segment.1);
+        }
+    }
 }
@@ -0,0 +1,362 @@
+use cgmath::Point3;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use iterator::VertexNeighborIterator;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::f32;
+use util::*;
+
+/// Liepa's density control factor, a triangle is split when its centroid is
+/// farther than the local edge scale divided by this factor from its corners.
+const REFINE_DENSITY: f32 = f32::consts::SQRT_2;
+
+const MAX_REFINE_ROUNDS: usize = 20;
+
+const MAX_RELAX_ROUNDS: usize = 50;
+
+/// Fills holes with triangulated patches, following Filling Holes in Meshes,
+/// Peter Liepa 2003: each boundary loop gets a minimum weight triangulation
+/// (minimizing the largest dihedral angle first, then the area), the patch
+/// is then refined to the edge density of the surrounding surface and faired
+/// so its curvature blends with the neighboring faces.
+pub struct HoleFiller {
+    max_hole_edges: usize,
+    refine: bool,
+    fair_iterations: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+struct TriangulationWeight {
+    angle: f32,
+    area: f32,
+}
+
+impl TriangulationWeight {
+    fn zero() -> Self {
+        TriangulationWeight {angle: 0.0, area: 0.0}
+    }
+
+    fn infinity() -> Self {
+        TriangulationWeight {angle: f32::MAX, area: f32::MAX}
+    }
+
+    fn add(&self, other: &TriangulationWeight) -> Self {
+        TriangulationWeight {
+            angle: self.angle.max(other.angle),
+            area: self.area + other.area,
+        }
+    }
+}
+
+/// A hole patch in local indices, the first loop_len points are the boundary
+/// loop and map to existing vertices, the rest are newly inserted.
+struct Patch {
+    points: Vec<Point3<f32>>,
+    scales: Vec<f32>,
+    triangles: Vec<[usize; 3]>,
+    loop_len: usize,
+}
+
+fn dihedral_angle(first_norm: Vector3<f32>, second_norm: Vector3<f32>) -> f32 {
+    if !is_valid_norm(first_norm) || !is_valid_norm(second_norm) {
+        return f32::consts::PI;
+    }
+    first_norm.dot(second_norm).clamp(-1.0, 1.0).acos()
+}
+
+fn angle_at(corner: Point3<f32>, a: Point3<f32>, b: Point3<f32>) -> f32 {
+    let u = (a - corner).normalize();
+    let v = (b - corner).normalize();
+    u.dot(v).clamp(-1.0, 1.0).acos()
+}
+
+impl HoleFiller {
+    pub fn new() -> Self {
+        HoleFiller {
+            max_hole_edges: 0,
+            refine: true,
+            fair_iterations: 100,
+        }
+    }
+
+    /// Holes with more boundary edges than this are left open, 0 means no limit.
+    pub fn set_max_hole_size(&mut self, max_hole_edges: usize) {
+        self.max_hole_edges = max_hole_edges;
+    }
+
+    pub fn set_refine(&mut self, refine: bool) {
+        self.refine = refine;
+    }
+
+    /// Fairing is skipped when the iterations is 0 or refine is disabled.
+    pub fn set_fair_iterations(&mut self, iterations: usize) {
+        self.fair_iterations = iterations;
+    }
+
+    pub fn fill_holes(&self, input: &Mesh) -> Mesh {
+        let mut mesh = Mesh::new();
+        mesh.add_mesh(input);
+        for boundary in mesh.boundary_loops() {
+            if boundary.len() < 3 {
+                continue;
+            }
+            if self.max_hole_edges > 0 && boundary.len() > self.max_hole_edges {
+                continue;
+            }
+            self.fill_hole(&mut mesh, &boundary);
+        }
+        mesh
+    }
+
+    fn vertex_edge_scale(&self, mesh: &Mesh, vertex_id: Id) -> f32 {
+        let position = mesh.vertex_position(vertex_id).unwrap();
+        let mut sum = 0.0;
+        let mut count = 0;
+        for neighbor_id in VertexNeighborIterator::new(mesh, vertex_id) {
+            sum += mesh.vertex_position(neighbor_id).unwrap().distance(position);
+            count += 1;
+        }
+        if count > 0 {
+            sum / count as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn fill_hole(&self, mesh: &mut Mesh, boundary: &[Id]) {
+        // The patch runs against the boundary halfedges so it pairs with them
+        let mut loop_vertices = Vec::new();
+        let mut outside_norms = Vec::new();
+        for &halfedge_id in boundary.iter().rev() {
+            let next_id = mesh.halfedge_next_id(halfedge_id).unwrap();
+            loop_vertices.push(mesh.halfedge_start_vertex_id(next_id).unwrap());
+            outside_norms.push(mesh.face_norm(mesh.halfedge_face_id(halfedge_id).unwrap()));
+        }
+        let mut patch = Patch {
+            points: loop_vertices.iter().map(|&v| mesh.vertex_position(v).unwrap()).collect(),
+            scales: loop_vertices.iter().map(|&v| self.vertex_edge_scale(mesh, v)).collect(),
+            triangles: Vec::new(),
+            loop_len: loop_vertices.len(),
+        };
+        patch.triangles = self.triangulate_loop(&patch.points, &outside_norms);
+        if patch.triangles.is_empty() {
+            return;
+        }
+        if self.refine {
+            self.refine_patch(&mut patch);
+            if self.fair_iterations > 0 && patch.points.len() > patch.loop_len {
+                self.fair_patch(mesh, &loop_vertices, &mut patch);
+            }
+        }
+        let mut vertex_ids = loop_vertices.clone();
+        for i in patch.loop_len..patch.points.len() {
+            vertex_ids.push(mesh.add_vertex(patch.points[i]));
+        }
+        for triangle in patch.triangles.iter() {
+            mesh.add_vertices(vec![vertex_ids[triangle[0]], vertex_ids[triangle[1]], vertex_ids[triangle[2]]]);
+        }
+    }
+
+    /// Minimum weight triangulation by dynamic programming over the loop,
+    /// outside_norms[i] is the norm of the existing face on the edge (i, i + 1).
+    fn triangulate_loop(&self, points: &[Point3<f32>], outside_norms: &[Vector3<f32>]) -> Vec<[usize; 3]> {
+        let n = points.len();
+        let mut weights = vec![vec![TriangulationWeight::zero(); n]; n];
+        let mut lambdas = vec![vec![0; n]; n];
+        // Norm of the triangle picked inside (i, k), looked up by the triangle
+        // which is later built on the edge (i, k).
+        let mut inner_norms = vec![vec![Vector3::zero(); n]; n];
+        for i in 0..n - 1 {
+            inner_norms[i][i + 1] = outside_norms[i];
+        }
+        for span in 2..n {
+            for i in 0..n - span {
+                let k = i + span;
+                let mut best = TriangulationWeight::infinity();
+                let mut best_m = 0;
+                let mut best_norm = Vector3::zero();
+                for m in i + 1..k {
+                    let triangle_norm = norm(points[i], points[m], points[k]);
+                    let mut angle = dihedral_angle(triangle_norm, inner_norms[i][m])
+                        .max(dihedral_angle(triangle_norm, inner_norms[m][k]));
+                    if i == 0 && k == n - 1 {
+                        angle = angle.max(dihedral_angle(triangle_norm, outside_norms[n - 1]));
+                    }
+                    let area = (points[m] - points[i]).cross(points[k] - points[i]).magnitude() * 0.5;
+                    let weight = weights[i][m].add(&weights[m][k]).add(&TriangulationWeight {angle, area});
+                    if weight < best {
+                        best = weight;
+                        best_m = m;
+                        best_norm = triangle_norm;
+                    }
+                }
+                weights[i][k] = best;
+                lambdas[i][k] = best_m;
+                inner_norms[i][k] = best_norm;
+            }
+        }
+        let mut triangles = Vec::new();
+        let mut pending = vec![(0, n - 1)];
+        while let Some((i, k)) = pending.pop() {
+            if k - i < 2 {
+                continue;
+            }
+            let m = lambdas[i][k];
+            if m <= i || m >= k {
+                return Vec::new();
+            }
+            triangles.push([i, m, k]);
+            pending.push((i, m));
+            pending.push((m, k));
+        }
+        triangles
+    }
+
+    fn refine_patch(&self, patch: &mut Patch) {
+        for _ in 0..MAX_REFINE_ROUNDS {
+            let mut split = false;
+            let mut new_triangles = Vec::new();
+            for &[a, b, c] in patch.triangles.clone().iter() {
+                let center = Point3::centroid(&[patch.points[a], patch.points[b], patch.points[c]]);
+                let center_scale = (patch.scales[a] + patch.scales[b] + patch.scales[c]) / 3.0;
+                let need_split = [a, b, c].iter().all(|&v| {
+                    let distance = center.distance(patch.points[v]) * REFINE_DENSITY;
+                    distance > center_scale && distance > patch.scales[v]
+                });
+                if need_split {
+                    let center_index = patch.points.len();
+                    patch.points.push(center);
+                    patch.scales.push(center_scale);
+                    new_triangles.push([a, b, center_index]);
+                    new_triangles.push([b, c, center_index]);
+                    new_triangles.push([c, a, center_index]);
+                    split = true;
+                } else {
+                    new_triangles.push([a, b, c]);
+                }
+            }
+            patch.triangles = new_triangles;
+            if !split {
+                break;
+            }
+            self.relax_patch(patch);
+        }
+        self.relax_patch(patch);
+    }
+
+    /// Flips interior edges whose opposite angles sum over 180 degrees, the
+    /// three dimensional version of the Delaunay criterion.
+    fn relax_patch(&self, patch: &mut Patch) {
+        for _ in 0..MAX_RELAX_ROUNDS {
+            let mut flipped = false;
+            let mut edge_map : HashMap<(usize, usize), usize> = HashMap::new();
+            for (t, triangle) in patch.triangles.iter().enumerate() {
+                for i in 0..3 {
+                    edge_map.insert((triangle[i], triangle[(i + 1) % 3]), t);
+                }
+            }
+            let mut touched : HashSet<usize> = HashSet::new();
+            for t in 0..patch.triangles.len() {
+                for i in 0..3 {
+                    let triangle = patch.triangles[t];
+                    let (a, b, c) = (triangle[i], triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+                    let other = match edge_map.get(&(b, a)) {
+                        Some(&other) => other,
+                        None => continue,
+                    };
+                    if touched.contains(&t) || touched.contains(&other) {
+                        continue;
+                    }
+                    let other_triangle = patch.triangles[other];
+                    let d = *other_triangle.iter().find(|&&v| v != a && v != b).unwrap();
+                    if edge_map.contains_key(&(c, d)) || edge_map.contains_key(&(d, c)) {
+                        continue;
+                    }
+                    let angle_sum = angle_at(patch.points[c], patch.points[a], patch.points[b]) +
+                        angle_at(patch.points[d], patch.points[a], patch.points[b]);
+                    if angle_sum <= f32::consts::PI + 0.0001 {
+                        continue;
+                    }
+                    patch.triangles[t] = [a, d, c];
+                    patch.triangles[other] = [d, b, c];
+                    touched.insert(t);
+                    touched.insert(other);
+                    flipped = true;
+                    break;
+                }
+            }
+            if !flipped {
+                break;
+            }
+        }
+    }
+
+    /// Minimizes the umbrella operator squared on the inserted vertices, using
+    /// the iteration from Interactive Multi-Resolution Modeling on Arbitrary
+    /// Meshes, Kobbelt et al. 1998. The loop vertices and their neighbors in
+    /// the mesh stay fixed and act as the boundary condition.
+    fn fair_patch(&self, mesh: &Mesh, loop_vertices: &[Id], patch: &mut Patch) {
+        let mut points = patch.points.clone();
+        let mut neighbors : Vec<HashSet<usize>> = vec![HashSet::new(); points.len()];
+        for triangle in patch.triangles.iter() {
+            for i in 0..3 {
+                neighbors[triangle[i]].insert(triangle[(i + 1) % 3]);
+                neighbors[triangle[(i + 1) % 3]].insert(triangle[i]);
+            }
+        }
+        let mut mesh_vertex_index : HashMap<Id, usize> = HashMap::new();
+        for (i, &vertex_id) in loop_vertices.iter().enumerate() {
+            mesh_vertex_index.insert(vertex_id, i);
+        }
+        for (i, &vertex_id) in loop_vertices.iter().enumerate() {
+            for neighbor_id in VertexNeighborIterator::new(mesh, vertex_id) {
+                let index = *mesh_vertex_index.entry(neighbor_id).or_insert_with(|| {
+                    points.push(mesh.vertex_position(neighbor_id).unwrap());
+                    neighbors.push(HashSet::new());
+                    points.len() - 1
+                });
+                neighbors[i].insert(index);
+            }
+        }
+        let free = patch.loop_len..patch.points.len();
+        let umbrella = |points: &[Point3<f32>], v: usize| -> Vector3<f32> {
+            if neighbors[v].is_empty() {
+                return Vector3::zero();
+            }
+            let sum = neighbors[v].iter().fold(Vector3::zero(), |sum, &u| sum + points[u].to_vec());
+            sum / neighbors[v].len() as f32 - points[v].to_vec()
+        };
+        for _ in 0..self.fair_iterations {
+            let umbrellas : Vec<Vector3<f32>> = (0..points.len()).map(|v| {
+                // Only the patch and the loop need the first umbrella
+                if v < patch.points.len() {
+                    umbrella(&points, v)
+                } else {
+                    Vector3::zero()
+                }
+            }).collect();
+            for v in free.clone() {
+                let n = neighbors[v].len() as f32;
+                if n == 0.0 {
+                    continue;
+                }
+                let umbrella_sum = neighbors[v].iter().fold(Vector3::zero(), |sum, &u| sum + umbrellas[u]);
+                let umbrella2 = umbrella_sum / n - umbrellas[v];
+                let diagonal = 1.0 + neighbors[v].iter().fold(0.0, |sum, &u| sum + 1.0 / neighbors[u].len().max(1) as f32) / n;
+                points[v] += -umbrella2 / diagonal;
+            }
+        }
+        for v in free {
+            patch.points[v] = points[v];
+        }
+    }
+}
+
+impl Default for HoleFiller {
+    fn default() -> Self {
+        HoleFiller::new()
+    }
+}
@@ -1,30 +1,40 @@
+use cgmath::BaseFloat;
 use mesh::Mesh;
 use mesh::Id;
 
-pub struct FaceIterator<'a> {
+pub struct FaceIterator<'a, S: 'a = f32> {
     index: usize,
-    mesh: &'a Mesh,
+    remaining: usize,
+    mesh: &'a Mesh<S>,
 }
 
-impl<'a> Iterator for FaceIterator<'a> {
+impl<'a, S: BaseFloat> Iterator for FaceIterator<'a, S> {
     type Item = Id;
 
     fn next(&mut self) -> Option<Id> {
         while self.index < self.mesh.faces.len() {
-            let face = &self.mesh.faces[self.index];
+            let alive = self.mesh.faces.alive[self.index];
             self.index += 1;
-            if face.alive {
-                return Some(face.id)
+            if alive {
+                self.remaining = self.remaining.saturating_sub(1);
+                return Some(self.index)
             }
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
 
-impl<'a> FaceIterator<'a> {
-    pub fn new(mesh: &'a Mesh) -> FaceIterator<'a> {
+impl<'a, S: BaseFloat> ExactSizeIterator for FaceIterator<'a, S> {}
+
+impl<'a, S: BaseFloat> FaceIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>) -> FaceIterator<'a, S> {
         FaceIterator {
             index: 0,
+            remaining: mesh.face_count,
             mesh: mesh,
         }
     }
@@ -38,36 +48,162 @@ impl<'a> FaceIterator<'a> {
     }
 }
 
-pub struct FaceHalfedgeIterator<'a> {
+/// Walks the halfedges of a face loop from the start halfedge. The loop can
+/// be walked from both ends, the back starts at the halfedge before the
+/// start one.
+pub struct FaceHalfedgeIterator<'a, S: 'a = f32> {
     stop_id: Id,
     current_id: Id,
+    back_id: Id,
     index: usize,
-    mesh: &'a Mesh,
+    finished: bool,
+    mesh: &'a Mesh<S>,
 }
 
-impl<'a> Iterator for FaceHalfedgeIterator<'a> {
+impl<'a, S: BaseFloat> Iterator for FaceHalfedgeIterator<'a, S> {
     type Item = Id;
 
     fn next(&mut self) -> Option<Id> {
+        if self.finished {
+            return None;
+        }
         let id = self.current_id;
-        if self.mesh.halfedge_start_vertex(id).is_none() {
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
             return None;
         }
         self.current_id = self.mesh.halfedge(self.current_id).unwrap().next;
-        if id == self.stop_id && self.index > 0 {
+        if id == self.stop_id && self.index > 0 && self.back_id == 0 {
+            self.finished = true;
             return None;
         }
+        if id == self.back_id {
+            self.finished = true;
+        }
         self.index += 1;
         Some(id)
     }
 }
 
-impl<'a> FaceHalfedgeIterator<'a> {
-    pub fn new(mesh: &'a Mesh, start_id: Id) -> FaceHalfedgeIterator<'a> {
+impl<'a, S: BaseFloat> DoubleEndedIterator for FaceHalfedgeIterator<'a, S> {
+    fn next_back(&mut self) -> Option<Id> {
+        if self.finished {
+            return None;
+        }
+        if self.back_id == 0 {
+            if self.current_id == self.stop_id && self.index > 0 {
+                self.finished = true;
+                return None;
+            }
+            self.back_id = self.mesh.halfedge_prev_id(self.stop_id)?;
+        }
+        let id = self.back_id;
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
+            return None;
+        }
+        if id == self.current_id {
+            self.finished = true;
+        }
+        self.back_id = self.mesh.halfedge(id).unwrap().prev;
+        Some(id)
+    }
+}
+
+impl<'a, S: BaseFloat> FaceHalfedgeIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, start_id: Id) -> FaceHalfedgeIterator<'a, S> {
         FaceHalfedgeIterator {
             stop_id: start_id,
             current_id: start_id,
+            back_id: 0,
             index: 0,
+            finished: false,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Walks the vertices of a face loop, the start vertices of
+/// FaceHalfedgeIterator.
+pub struct FaceVertexIterator<'a, S: 'a = f32> {
+    halfedges: FaceHalfedgeIterator<'a, S>,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for FaceVertexIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let halfedge_id = self.halfedges.next()?;
+        self.mesh.halfedge_start_vertex_id(halfedge_id)
+    }
+}
+
+impl<'a, S: BaseFloat> DoubleEndedIterator for FaceVertexIterator<'a, S> {
+    fn next_back(&mut self) -> Option<Id> {
+        let halfedge_id = self.halfedges.next_back()?;
+        self.mesh.halfedge_start_vertex_id(halfedge_id)
+    }
+}
+
+impl<'a, S: BaseFloat> FaceVertexIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, face_id: Id) -> FaceVertexIterator<'a, S> {
+        FaceVertexIterator {
+            halfedges: FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap_or(0)),
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Walks the faces sharing an edge with a face, in the order of its face
+/// loop. Boundary edges are skipped.
+pub struct FaceNeighborIterator<'a, S: 'a = f32> {
+    halfedges: FaceHalfedgeIterator<'a, S>,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for FaceNeighborIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        loop {
+            let halfedge_id = self.halfedges.next()?;
+            if let Some(face_id) = self.mesh.halfedge_opposite_face_id(halfedge_id) {
+                return Some(face_id);
+            }
+        }
+    }
+}
+
+impl<'a, S: BaseFloat> DoubleEndedIterator for FaceNeighborIterator<'a, S> {
+    fn next_back(&mut self) -> Option<Id> {
+        loop {
+            let halfedge_id = self.halfedges.next_back()?;
+            if let Some(face_id) = self.mesh.halfedge_opposite_face_id(halfedge_id) {
+                return Some(face_id);
+            }
+        }
+    }
+}
+
+impl<'a, S: BaseFloat> FaceNeighborIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, face_id: Id) -> FaceNeighborIterator<'a, S> {
+        FaceNeighborIterator {
+            halfedges: FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap_or(0)),
             mesh: mesh,
         }
     }
@@ -81,19 +217,155 @@ impl<'a> FaceHalfedgeIterator<'a> {
     }
 }
 
-pub struct VertexHalfedgeIterator<'a> {
+pub struct VertexIterator<'a, S: 'a = f32> {
+    index: usize,
+    remaining: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for VertexIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        while self.index < self.mesh.vertices.len() {
+            let alive = self.mesh.vertices.alive[self.index];
+            self.index += 1;
+            if alive {
+                self.remaining = self.remaining.saturating_sub(1);
+                return Some(self.index)
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, S: BaseFloat> ExactSizeIterator for VertexIterator<'a, S> {}
+
+impl<'a, S: BaseFloat> VertexIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>) -> VertexIterator<'a, S> {
+        VertexIterator {
+            index: 0,
+            remaining: mesh.vertex_count,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+pub struct HalfedgeIterator<'a, S: 'a = f32> {
+    index: usize,
+    remaining: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for HalfedgeIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        while self.index < self.mesh.halfedges.len() {
+            let alive = self.mesh.halfedges.alive[self.index];
+            self.index += 1;
+            if alive {
+                self.remaining = self.remaining.saturating_sub(1);
+                return Some(self.index)
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, S: BaseFloat> ExactSizeIterator for HalfedgeIterator<'a, S> {}
+
+impl<'a, S: BaseFloat> HalfedgeIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>) -> HalfedgeIterator<'a, S> {
+        HalfedgeIterator {
+            index: 0,
+            remaining: mesh.halfedge_count,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Yields one halfedge for each undirected edge, the one with the smaller id
+/// when the edge has both halfedges.
+pub struct EdgeIterator<'a, S: 'a = f32> {
+    index: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for EdgeIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        while self.index < self.mesh.halfedges.len() {
+            let opposite = self.mesh.halfedges.opposite[self.index] as Id;
+            let alive = self.mesh.halfedges.alive[self.index];
+            self.index += 1;
+            if alive && (opposite == 0 || self.index < opposite) {
+                return Some(self.index)
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.mesh.halfedges.len() - self.index))
+    }
+}
+
+impl<'a, S: BaseFloat> EdgeIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>) -> EdgeIterator<'a, S> {
+        EdgeIterator {
+            index: 0,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+pub struct VertexHalfedgeIterator<'a, S: 'a = f32> {
     stop_id: Id,
     current_id: Id,
     index: usize,
-    mesh: &'a Mesh,
+    mesh: &'a Mesh<S>,
 }
 
-impl<'a> Iterator for VertexHalfedgeIterator<'a> {
+impl<'a, S: BaseFloat> Iterator for VertexHalfedgeIterator<'a, S> {
     type Item = Id;
 
     fn next(&mut self) -> Option<Id> {
         let id = self.current_id;
-        if self.mesh.halfedge_start_vertex(id).is_none() {
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
             return None;
         }
         self.current_id = {
@@ -111,8 +383,8 @@ impl<'a> Iterator for VertexHalfedgeIterator<'a> {
     }
 }
 
-impl<'a> VertexHalfedgeIterator<'a> {
-    pub fn new(mesh: &'a Mesh, start_id: Id) -> VertexHalfedgeIterator<'a> {
+impl<'a, S: BaseFloat> VertexHalfedgeIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, start_id: Id) -> VertexHalfedgeIterator<'a, S> {
         VertexHalfedgeIterator {
             stop_id: start_id,
             current_id: start_id,
@@ -129,3 +401,315 @@ impl<'a> VertexHalfedgeIterator<'a> {
         vec
     }
 }
+
+/// Walks an edge loop from the start halfedge, going straight through every
+/// vertex with four edges. The walk stops after returning to the start, at a
+/// vertex with another number of edges (a pole) or at the mesh boundary. The
+/// halfedges run in the walk direction, so walk from the opposite of the start
+/// halfedge to get the other half of an open loop.
+pub struct EdgeLoopIterator<'a, S: 'a = f32> {
+    stop_id: Id,
+    current_id: Id,
+    index: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for EdgeLoopIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let id = self.current_id;
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
+            return None;
+        }
+        if (id == self.stop_id && self.index > 0) || self.index > self.mesh.halfedges.len() {
+            return None;
+        }
+        self.index += 1;
+        self.current_id = self.mesh.halfedge_next_id(id)
+            .and_then(|next_id| {
+                let end_vertex_id = self.mesh.halfedge_start_vertex_id(next_id)?;
+                if !self.mesh.is_vertex_alive(end_vertex_id) {
+                    return None;
+                }
+                // A boundary vertex has one more edge than outgoing halfedges
+                let outgoing = &self.mesh.vertices.halfedges[end_vertex_id - 1];
+                let on_boundary = outgoing.iter().any(|&halfedge_id| self.mesh.halfedge_opposite_id(halfedge_id as Id).is_none());
+                if outgoing.len() + if on_boundary { 1 } else { 0 } != 4 {
+                    return None;
+                }
+                self.mesh.halfedge_opposite_id(next_id)
+            })
+            .and_then(|opposite_id| self.mesh.halfedge_next_id(opposite_id))
+            .unwrap_or(0);
+        Some(id)
+    }
+}
+
+impl<'a, S: BaseFloat> EdgeLoopIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, start_id: Id) -> EdgeLoopIterator<'a, S> {
+        EdgeLoopIterator {
+            stop_id: start_id,
+            current_id: start_id,
+            index: 0,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Walks an edge ring from the start halfedge, stepping to the opposite edge
+/// of each quad and across it into the next face. The walk stops after
+/// returning to the start, at a face which is not a quad or at the mesh
+/// boundary.
+pub struct EdgeRingIterator<'a, S: 'a = f32> {
+    stop_id: Id,
+    current_id: Id,
+    index: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for EdgeRingIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let id = self.current_id;
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
+            return None;
+        }
+        if (id == self.stop_id && self.index > 0) || self.index > self.mesh.halfedges.len() {
+            return None;
+        }
+        self.index += 1;
+        self.current_id = if FaceHalfedgeIterator::new(self.mesh, id).count() == 4 {
+            self.mesh.halfedge_next_id(id)
+                .and_then(|next_id| self.mesh.halfedge_next_id(next_id))
+                .and_then(|across_id| self.mesh.halfedge_opposite_id(across_id))
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        Some(id)
+    }
+}
+
+impl<'a, S: BaseFloat> EdgeRingIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, start_id: Id) -> EdgeRingIterator<'a, S> {
+        EdgeRingIterator {
+            stop_id: start_id,
+            current_id: start_id,
+            index: 0,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Circulates the outgoing halfedges of a vertex, rotating from each one to
+/// the next outgoing halfedge of the neighboring face. On a boundary vertex
+/// the walk starts at the outgoing halfedge following the open side, so the
+/// whole fan is visited up to the outgoing boundary halfedge.
+pub struct VertexOutgoingIterator<'a, S: 'a = f32> {
+    stop_id: Id,
+    current_id: Id,
+    index: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for VertexOutgoingIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let id = self.current_id;
+        if self.mesh.halfedge_start_vertex_position(id).is_none() {
+            return None;
+        }
+        if (id == self.stop_id && self.index > 0) || self.index > self.mesh.halfedges.len() {
+            return None;
+        }
+        self.index += 1;
+        self.current_id = self.mesh.halfedge_opposite_id(id)
+            .and_then(|opposite_id| self.mesh.halfedge_next_id(opposite_id))
+            .unwrap_or(0);
+        Some(id)
+    }
+}
+
+impl<'a, S: BaseFloat> VertexOutgoingIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, vertex_id: Id) -> VertexOutgoingIterator<'a, S> {
+        let start_id = if mesh.is_vertex_alive(vertex_id) {
+            let halfedges = &mesh.vertices.halfedges[vertex_id - 1];
+            halfedges.iter().map(|&halfedge_id| halfedge_id as Id)
+                .find(|&halfedge_id| mesh.halfedge_prev_id(halfedge_id)
+                    .and_then(|prev_id| mesh.halfedge_opposite_id(prev_id))
+                    .is_none())
+                .or_else(|| halfedges.first().map(|&halfedge_id| halfedge_id as Id))
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        VertexOutgoingIterator {
+            stop_id: start_id,
+            current_id: start_id,
+            index: 0,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Circulates the vertices connected to a vertex by an edge, in the order of
+/// VertexOutgoingIterator. A boundary vertex also yields the neighbor across
+/// the incoming boundary edge last.
+pub struct VertexNeighborIterator<'a, S: 'a = f32> {
+    halfedges: VertexOutgoingIterator<'a, S>,
+    first_id: Id,
+    finished: bool,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for VertexNeighborIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        if self.finished {
+            return None;
+        }
+        match self.halfedges.next() {
+            Some(halfedge_id) => {
+                if self.first_id == 0 {
+                    self.first_id = halfedge_id;
+                }
+                self.mesh.halfedge_next_id(halfedge_id)
+                    .and_then(|next_id| self.mesh.halfedge_start_vertex_id(next_id))
+            },
+            None => {
+                self.finished = true;
+                let prev_id = self.mesh.halfedge_prev_id(self.first_id)?;
+                if self.mesh.halfedge_opposite_id(prev_id).is_some() {
+                    return None;
+                }
+                self.mesh.halfedge_start_vertex_id(prev_id)
+            },
+        }
+    }
+}
+
+impl<'a, S: BaseFloat> VertexNeighborIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, vertex_id: Id) -> VertexNeighborIterator<'a, S> {
+        VertexNeighborIterator {
+            halfedges: VertexOutgoingIterator::new(mesh, vertex_id),
+            first_id: 0,
+            finished: false,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Circulates the faces around a vertex, in the order of
+/// VertexOutgoingIterator.
+pub struct VertexFaceIterator<'a, S: 'a = f32> {
+    halfedges: VertexOutgoingIterator<'a, S>,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for VertexFaceIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let halfedge_id = self.halfedges.next()?;
+        self.mesh.halfedge_face_id(halfedge_id)
+    }
+}
+
+impl<'a, S: BaseFloat> VertexFaceIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, vertex_id: Id) -> VertexFaceIterator<'a, S> {
+        VertexFaceIterator {
+            halfedges: VertexOutgoingIterator::new(mesh, vertex_id),
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
+
+/// Walks the boundary loop of a halfedge without opposite, in the direction
+/// of its face. The walk stops after returning to the start, or when the
+/// loop cannot be followed.
+pub struct BoundaryLoopIterator<'a, S: 'a = f32> {
+    stop_id: Id,
+    current_id: Id,
+    index: usize,
+    mesh: &'a Mesh<S>,
+}
+
+impl<'a, S: BaseFloat> Iterator for BoundaryLoopIterator<'a, S> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        let id = self.current_id;
+        if self.mesh.halfedge_start_vertex_position(id).is_none() || self.mesh.halfedge_opposite_id(id).is_some() {
+            return None;
+        }
+        if (id == self.stop_id && self.index > 0) || self.index > self.mesh.halfedges.len() {
+            return None;
+        }
+        self.index += 1;
+        self.current_id = self.mesh.next_boundary_halfedge(id).unwrap_or(0);
+        Some(id)
+    }
+}
+
+impl<'a, S: BaseFloat> BoundaryLoopIterator<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>, start_id: Id) -> BoundaryLoopIterator<'a, S> {
+        BoundaryLoopIterator {
+            stop_id: start_id,
+            current_id: start_id,
+            index: 0,
+            mesh: mesh,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Id> {
+        let mut vec = Vec::new();
+        for id in self {
+            vec.push(id);
+        }
+        vec
+    }
+}
@@ -0,0 +1,261 @@
+use cgmath::BaseFloat;
+use mesh::Face;
+use mesh::Halfedge;
+use mesh::Id;
+use mesh::Mesh;
+use mesh::Vertex;
+use std::collections::HashSet;
+
+/// One element level change, holding what is needed to revert it. Applying a
+/// change returns the change which reverts it again.
+#[derive(Debug, Clone)]
+pub enum Change<S = f32> {
+    PopVertex,
+    PopHalfedge,
+    PopFace,
+    PushVertex(Vertex<S>),
+    PushHalfedge(Halfedge),
+    PushFace(Face),
+    SetVertex(Vertex<S>),
+    SetHalfedge(Halfedge),
+    SetFace(Face),
+    SetCounts(usize, usize, usize),
+}
+
+/// Records the changes made through the Mesh mutators, grouped by
+/// checkpoints. Only the first state of an element in a group is kept, which
+/// is all that rolling the group back needs. Writes to the public Mesh fields
+/// that bypass the mutators are not recorded.
+#[derive(Debug)]
+pub struct Journal<S = f32> {
+    changes: Vec<Change<S>>,
+    checkpoints: Vec<usize>,
+    redo_groups: Vec<Vec<Change<S>>>,
+    touched_vertices: HashSet<Id>,
+    touched_halfedges: HashSet<Id>,
+    touched_faces: HashSet<Id>,
+}
+
+impl<S> Default for Journal<S> {
+    fn default() -> Self {
+        Journal {
+            changes: Vec::new(),
+            checkpoints: Vec::new(),
+            redo_groups: Vec::new(),
+            touched_vertices: HashSet::new(),
+            touched_halfedges: HashSet::new(),
+            touched_faces: HashSet::new(),
+        }
+    }
+}
+
+impl<S> Journal<S> {
+    pub fn new() -> Self {
+        Journal::default()
+    }
+
+    fn group_start(&self) -> usize {
+        self.checkpoints.last().cloned().unwrap_or(0)
+    }
+
+    fn clear_touched(&mut self) {
+        self.touched_vertices.clear();
+        self.touched_halfedges.clear();
+        self.touched_faces.clear();
+    }
+
+    fn push(&mut self, change: Change<S>, counts: (usize, usize, usize)) {
+        if self.changes.len() == self.group_start() {
+            self.changes.push(Change::SetCounts(counts.0, counts.1, counts.2));
+        }
+        self.redo_groups.clear();
+        self.changes.push(change);
+    }
+
+    fn close_group(&mut self) {
+        if self.changes.len() > self.group_start() {
+            self.checkpoints.push(self.changes.len());
+        }
+        self.clear_touched();
+    }
+}
+
+fn counts<S>(mesh: &Mesh<S>) -> (usize, usize, usize) {
+    (mesh.vertex_count, mesh.halfedge_count, mesh.face_count)
+}
+
+pub fn record_vertex<S: BaseFloat>(mesh: &mut Mesh<S>, id: Id) {
+    let counts = counts(mesh);
+    if let Some(ref mut journal) = mesh.journal {
+        if journal.touched_vertices.insert(id) {
+            journal.push(Change::SetVertex(mesh.vertices.get(id)), counts);
+        }
+    }
+}
+
+pub fn record_halfedge<S: BaseFloat>(mesh: &mut Mesh<S>, id: Id) {
+    let counts = counts(mesh);
+    if let Some(ref mut journal) = mesh.journal {
+        if journal.touched_halfedges.insert(id) {
+            journal.push(Change::SetHalfedge(mesh.halfedges.get(id)), counts);
+        }
+    }
+}
+
+pub fn record_face<S: BaseFloat>(mesh: &mut Mesh<S>, id: Id) {
+    let counts = counts(mesh);
+    if let Some(ref mut journal) = mesh.journal {
+        if journal.touched_faces.insert(id) {
+            journal.push(Change::SetFace(mesh.faces.get(id)), counts);
+        }
+    }
+}
+
+/// Records an element pushed by add_vertex, add_halfedge or add_face, the
+/// change is one of the Pop variants.
+pub fn record_added<S: BaseFloat>(mesh: &mut Mesh<S>, change: Change<S>, id: Id) {
+    let counts = counts(mesh);
+    if let Some(ref mut journal) = mesh.journal {
+        match change {
+            Change::PopVertex => journal.touched_vertices.insert(id),
+            Change::PopHalfedge => journal.touched_halfedges.insert(id),
+            _ => journal.touched_faces.insert(id),
+        };
+        journal.push(change, counts);
+    }
+}
+
+fn apply<S: BaseFloat>(mesh: &mut Mesh<S>, change: Change<S>) -> Change<S> {
+    match change {
+        Change::PopVertex => Change::PushVertex(mesh.vertices.pop().unwrap()),
+        Change::PopHalfedge => Change::PushHalfedge(mesh.halfedges.pop().unwrap()),
+        Change::PopFace => Change::PushFace(mesh.faces.pop().unwrap()),
+        Change::PushVertex(vertex) => {
+            mesh.vertices.push(vertex);
+            Change::PopVertex
+        },
+        Change::PushHalfedge(halfedge) => {
+            mesh.halfedges.push(halfedge);
+            Change::PopHalfedge
+        },
+        Change::PushFace(face) => {
+            mesh.faces.push(face);
+            Change::PopFace
+        },
+        Change::SetVertex(vertex) => {
+            let old = mesh.vertices.get(vertex.id);
+            mesh.vertices.set(vertex);
+            Change::SetVertex(old)
+        },
+        Change::SetHalfedge(halfedge) => {
+            let old = mesh.halfedges.get(halfedge.id);
+            mesh.halfedges.set(halfedge);
+            Change::SetHalfedge(old)
+        },
+        Change::SetFace(face) => {
+            let old = mesh.faces.get(face.id);
+            mesh.faces.set(face);
+            Change::SetFace(old)
+        },
+        Change::SetCounts(vertex_count, halfedge_count, face_count) => {
+            let old = Change::SetCounts(mesh.vertex_count, mesh.halfedge_count, mesh.face_count);
+            mesh.vertex_count = vertex_count;
+            mesh.halfedge_count = halfedge_count;
+            mesh.face_count = face_count;
+            old
+        },
+    }
+}
+
+/// Undo and redo for edits made through the Mesh mutators, without cloning
+/// the mesh. Recording starts with start_journal, and checkpoint closes the
+/// group of changes the next undo rolls back. Changes made after an undo
+/// drop the redo history.
+pub trait Undo {
+    fn start_journal(&mut self);
+    fn stop_journal(&mut self);
+    fn checkpoint(&mut self);
+    fn can_undo(&self) -> bool;
+    fn can_redo(&self) -> bool;
+    /// Rolls back the changes since the previous checkpoint, including the
+    /// ones not closed by a checkpoint yet. Returns false when there is
+    /// nothing to undo.
+    fn undo(&mut self) -> bool;
+    /// Replays the last undone group. Returns false when there is nothing to
+    /// redo.
+    fn redo(&mut self) -> bool;
+}
+
+impl<S: BaseFloat> Undo for Mesh<S> {
+    fn start_journal(&mut self) {
+        self.journal = Some(Journal::new());
+    }
+
+    fn stop_journal(&mut self) {
+        self.journal = None;
+    }
+
+    fn checkpoint(&mut self) {
+        if let Some(ref mut journal) = self.journal {
+            journal.close_group();
+        }
+    }
+
+    fn can_undo(&self) -> bool {
+        match self.journal {
+            Some(ref journal) => !journal.changes.is_empty(),
+            None => false,
+        }
+    }
+
+    fn can_redo(&self) -> bool {
+        match self.journal {
+            Some(ref journal) => !journal.redo_groups.is_empty(),
+            None => false,
+        }
+    }
+
+    fn undo(&mut self) -> bool {
+        let mut journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return false,
+        };
+        journal.close_group();
+        let undone = match journal.checkpoints.pop() {
+            Some(end) => {
+                let start = journal.group_start();
+                let mut redo_group : Vec<Change<S>> = Vec::new();
+                for change in journal.changes.drain(start..end).rev() {
+                    redo_group.push(apply(self, change));
+                }
+                redo_group.reverse();
+                journal.redo_groups.push(redo_group);
+                true
+            },
+            None => false,
+        };
+        self.journal = Some(journal);
+        undone
+    }
+
+    fn redo(&mut self) -> bool {
+        let mut journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return false,
+        };
+        journal.close_group();
+        let redone = match journal.redo_groups.pop() {
+            Some(redo_group) => {
+                for change in redo_group {
+                    let undo_change = apply(self, change);
+                    journal.changes.push(undo_change);
+                }
+                journal.close_group();
+                true
+            },
+            None => false,
+        };
+        self.journal = Some(journal);
+        redone
+    }
+}
@@ -1,16 +1,34 @@
 extern crate cgmath;
 extern crate fnv;
 extern crate petgraph;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 extern crate smallvec;
 
+pub mod bevel;
+pub mod binary;
 pub mod bmesh;
+pub mod bridge;
+pub mod bvh;
 pub mod debug;
+pub mod holefill;
 pub mod iterator;
+pub mod journal;
+pub mod loopcut;
 pub mod mesh;
 pub mod primitives;
+pub mod query;
+pub mod renderbuffer;
+pub mod repair;
+pub mod selection;
+pub mod selfintersect;
 pub mod skeletonmesh;
 pub mod subdivide;
 pub mod triangulate;
 pub mod util;
+pub mod vertexcache;
 pub mod wavefront;
 pub mod wrap;
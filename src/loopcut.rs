@@ -0,0 +1,154 @@
+use iterator::EdgeRingIterator;
+use iterator::FaceHalfedgeIterator;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Inserts new edge loops across the quad ring of the start halfedge. Every
+/// edge crossed by the ring is split and every quad on the ring is divided,
+/// faces next to the ends of an open ring get the new vertices inserted on
+/// their shared edge. Returns the vertices of each new loop.
+pub trait LoopCut {
+    /// Cuts one loop at the fraction along the start halfedge.
+    fn loop_cut(&mut self, halfedge_id: Id, fraction: f32) -> Vec<Id>;
+    /// Cuts the given number of evenly spaced loops.
+    fn loop_cut_multiple(&mut self, halfedge_id: Id, cuts: usize) -> Vec<Vec<Id>>;
+}
+
+fn is_quad(mesh: &Mesh, halfedge_id: Id) -> bool {
+    FaceHalfedgeIterator::new(mesh, halfedge_id).count() == 4
+}
+
+/// The halfedges entering each quad on the ring, all running the same way as
+/// the start halfedge.
+fn collect_ring(mesh: &Mesh, halfedge_id: Id) -> (Vec<Id>, bool) {
+    let forward : Vec<Id> = EdgeRingIterator::new(mesh, halfedge_id)
+        .filter(|&ring_id| is_quad(mesh, ring_id))
+        .collect();
+    let closed = match forward.last() {
+        Some(&last_id) => mesh.halfedge_next_id(last_id)
+            .and_then(|next_id| mesh.halfedge_next_id(next_id))
+            .and_then(|across_id| mesh.halfedge_opposite_id(across_id)) == Some(halfedge_id),
+        None => false,
+    };
+    if closed {
+        return (forward, true);
+    }
+    let mut ring : Vec<Id> = Vec::new();
+    if let Some(opposite_id) = mesh.halfedge_opposite_id(halfedge_id) {
+        // Walking back from the opposite halfedge enters each quad by its far edge
+        ring = EdgeRingIterator::new(mesh, opposite_id)
+            .filter(|&ring_id| is_quad(mesh, ring_id))
+            .map(|ring_id| mesh.halfedge_next_id(mesh.halfedge_next_id(ring_id).unwrap()).unwrap())
+            .collect();
+        ring.reverse();
+    }
+    ring.extend(forward);
+    (ring, false)
+}
+
+fn cut_loops(mesh: &mut Mesh, halfedge_id: Id, fractions: &[f32]) -> Vec<Vec<Id>> {
+    if fractions.is_empty() || mesh.halfedge_start_vertex_position(halfedge_id).is_none() {
+        return Vec::new();
+    }
+    let (ring, closed) = collect_ring(mesh, halfedge_id);
+    let ring_faces : HashSet<Id> = ring.iter().map(|&ring_id| mesh.halfedge_face_id(ring_id).unwrap()).collect();
+    if ring.is_empty() || ring_faces.len() != ring.len() {
+        // A ring crossing one quad twice has no consistent cut
+        return Vec::new();
+    }
+    let mut quads : Vec<[Id; 4]> = Vec::new();
+    for &ring_id in ring.iter() {
+        let vertices = FaceHalfedgeIterator::new(mesh, ring_id).into_vec();
+        quads.push([mesh.halfedge_start_vertex_id(vertices[0]).unwrap(),
+            mesh.halfedge_start_vertex_id(vertices[1]).unwrap(),
+            mesh.halfedge_start_vertex_id(vertices[2]).unwrap(),
+            mesh.halfedge_start_vertex_id(vertices[3]).unwrap()]);
+    }
+
+    // Split points keyed by the directed edge, ordered from its start
+    let mut split_map : HashMap<(Id, Id), Vec<Id>> = HashMap::new();
+    let mut touched_faces : Vec<Id> = Vec::new();
+    for (&ring_id, quad) in ring.iter().zip(quads.iter()) {
+        let across_id = mesh.halfedge_next_id(mesh.halfedge_next_id(ring_id).unwrap()).unwrap();
+        for &(edge_id, from_vert_id, to_vert_id) in [(ring_id, quad[0], quad[1]), (across_id, quad[3], quad[2])].iter() {
+            if split_map.contains_key(&(from_vert_id, to_vert_id)) {
+                continue;
+            }
+            let from_position = mesh.vertex_position(from_vert_id).unwrap();
+            let to_position = mesh.vertex_position(to_vert_id).unwrap();
+            let mut split_vertices = Vec::new();
+            for &fraction in fractions {
+                let source = mesh.vertex_source(from_vert_id).unwrap();
+                let split_vert_id = mesh.add_vertex(from_position + (to_position - from_position) * fraction);
+                mesh.set_vertex_source(split_vert_id, source);
+                split_vertices.push(split_vert_id);
+            }
+            let mut reversed_vertices = split_vertices.clone();
+            reversed_vertices.reverse();
+            split_map.insert((from_vert_id, to_vert_id), split_vertices);
+            split_map.insert((to_vert_id, from_vert_id), reversed_vertices);
+            if let Some(opposite_face_id) = mesh.halfedge_opposite_face_id(edge_id) {
+                if !ring_faces.contains(&opposite_face_id) && !touched_faces.contains(&opposite_face_id) {
+                    touched_faces.push(opposite_face_id);
+                }
+            }
+        }
+    }
+
+    let mut polygons : Vec<Vec<Id>> = Vec::new();
+    for &face_id in touched_faces.iter() {
+        let mut vertices = Vec::new();
+        for edge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            let from_vert_id = mesh.halfedge_start_vertex_id(edge_id).unwrap();
+            let to_vert_id = mesh.halfedge_start_vertex_id(mesh.halfedge_next_id(edge_id).unwrap()).unwrap();
+            vertices.push(from_vert_id);
+            if let Some(split_vertices) = split_map.get(&(from_vert_id, to_vert_id)) {
+                vertices.extend(split_vertices.iter().cloned());
+            }
+        }
+        polygons.push(vertices);
+    }
+    for quad in quads.iter() {
+        let mut first = split_map[&(quad[0], quad[1])].clone();
+        let mut second = split_map[&(quad[3], quad[2])].clone();
+        first.insert(0, quad[0]);
+        first.push(quad[1]);
+        second.insert(0, quad[3]);
+        second.push(quad[2]);
+        for i in 0..first.len() - 1 {
+            polygons.push(vec![first[i], first[i + 1], second[i + 1], second[i]]);
+        }
+    }
+
+    for &face_id in ring_faces.iter().chain(touched_faces.iter()) {
+        mesh.remove_face(face_id);
+    }
+    for vertices in polygons.iter() {
+        for &vert_id in vertices.iter() {
+            mesh.revive_vertex(vert_id);
+        }
+    }
+    for vertices in polygons {
+        mesh.add_vertices(vertices);
+    }
+
+    let quad_count = if closed { quads.len() - 1 } else { quads.len() };
+    let mut loop_edges = vec![&split_map[&(quads[0][0], quads[0][1])]];
+    for quad in quads.iter().take(quad_count) {
+        loop_edges.push(&split_map[&(quad[3], quad[2])]);
+    }
+    (0..fractions.len()).map(|cut| loop_edges.iter().map(|split_vertices| split_vertices[cut]).collect()).collect()
+}
+
+impl LoopCut for Mesh {
+    fn loop_cut(&mut self, halfedge_id: Id, fraction: f32) -> Vec<Id> {
+        cut_loops(self, halfedge_id, &[fraction]).pop().unwrap_or_default()
+    }
+
+    fn loop_cut_multiple(&mut self, halfedge_id: Id, cuts: usize) -> Vec<Vec<Id>> {
+        let fractions : Vec<f32> = (1..cuts + 1).map(|cut| cut as f32 / (cuts + 1) as f32).collect();
+        cut_loops(self, halfedge_id, &fractions)
+    }
+}
@@ -1,3 +1,4 @@
+use cgmath::BaseFloat;
 use cgmath::Point3;
 use cgmath::Vector3;
 use cgmath::prelude::*;
@@ -8,14 +9,21 @@ use std::vec::Vec;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use fnv::FnvHashSet;
-use fnv::FnvHashMap;
+use iterator::BoundaryLoopIterator;
+use iterator::EdgeIterator;
 use iterator::FaceHalfedgeIterator;
 use iterator::FaceIterator;
+use journal;
+use journal::Change;
+use journal::Journal;
+use selection::Selection;
+use selection::remap_selections;
 use util::*;
 use smallvec::SmallVec;
 use std::ops::Add;
 use std::ops::AddAssign;
-use std::f32;
+use std::ops::Deref;
+use std::ops::DerefMut;
 
 pub type Id = usize;
 
@@ -29,32 +37,35 @@ pub type Id = usize;
 // generics and/or macros to specialize some parts of the implementation?
 const VERTEX_HALFEDGE_INLINE_COUNT: usize = 4;
 
-#[derive(Debug)]
-pub struct Vertex {
-    pub id: Id,
-    pub position: Point3<f32>,
+/// Faces given to add_vertices must have fewer vertices than this.
+pub const MAX_FACE_VERTEX_COUNT: usize = 1000;
 
-    /// This is at the time of writing used as a set but is implemented using a
-    /// SmallVec. Finding or implementing something like a "SmallSet" could
-    /// provide a better API.
-    pub halfedges: SmallVec<[Id; VERTEX_HALFEDGE_INLINE_COUNT]>,
+/// Vertices within this distance to the symmetry plane are welded by symmetrize.
+const SYMMETRIZE_SEAM_DISTANCE: f32 = 0.0001;
 
-    pub prev: Id,
-    pub next: Id,
+/// Vertex position updates done after the normals have been filtered by denoise.
+const DENOISE_VERTEX_ITERATIONS: usize = 10;
+
+/// A copy of one vertex, assembled from the vertex arrays of the mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vertex<S = f32> {
+    pub id: Id,
+    pub position: Point3<S>,
+    pub halfedges: SmallVec<[Id; VERTEX_HALFEDGE_INLINE_COUNT]>,
     pub alive: bool,
     pub source: i32,
 }
 
-#[derive(Debug)]
+/// A copy of one face, assembled from the face arrays of the mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Face {
     pub id: Id,
     pub halfedge: Id,
-    pub prev: Id,
-    pub next: Id,
     pub alive: bool,
 }
 
-#[derive(Debug)]
+/// A copy of one halfedge, assembled from the halfedge arrays of the mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Halfedge {
     pub id: Id,
     pub vertex: Id,
@@ -65,24 +76,349 @@ pub struct Halfedge {
     pub alive: bool,
 }
 
+/// The vertices of a mesh as parallel arrays, the vertex with id N is at
+/// index N - 1 of every array.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vertices<S = f32> {
+    pub position: Vec<Point3<S>>,
+    pub source: Vec<i32>,
+
+    /// The outgoing halfedges. This is at the time of writing used as a set
+    /// but is implemented using a SmallVec. Finding or implementing something
+    /// like a "SmallSet" could provide a better API.
+    pub halfedges: Vec<SmallVec<[u32; VERTEX_HALFEDGE_INLINE_COUNT]>>,
+
+    pub alive: Vec<bool>,
+}
+
+impl<S> Default for Vertices<S> {
+    fn default() -> Self {
+        Vertices {
+            position: Vec::new(),
+            source: Vec::new(),
+            halfedges: Vec::new(),
+            alive: Vec::new(),
+        }
+    }
+}
+
+impl<S: Copy> Vertices<S> {
+    pub fn len(&self) -> usize {
+        self.alive.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alive.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.position.reserve(additional);
+        self.source.reserve(additional);
+        self.halfedges.reserve(additional);
+        self.alive.reserve(additional);
+    }
+
+    /// Copies the vertex out of the arrays, dead or alive.
+    pub fn get(&self, id: Id) -> Vertex<S> {
+        let index = id - 1;
+        Vertex {
+            id: id,
+            position: self.position[index],
+            halfedges: self.halfedges[index].iter().map(|&halfedge_id| halfedge_id as Id).collect(),
+            alive: self.alive[index],
+            source: self.source[index],
+        }
+    }
+
+    /// Writes the vertex back to the index given by its id.
+    pub fn set(&mut self, vertex: Vertex<S>) {
+        let index = vertex.id - 1;
+        self.position[index] = vertex.position;
+        self.source[index] = vertex.source;
+        self.halfedges[index] = vertex.halfedges.iter().map(|&halfedge_id| halfedge_id as u32).collect();
+        self.alive[index] = vertex.alive;
+    }
+
+    pub fn push(&mut self, vertex: Vertex<S>) {
+        self.position.push(vertex.position);
+        self.source.push(vertex.source);
+        self.halfedges.push(vertex.halfedges.iter().map(|&halfedge_id| halfedge_id as u32).collect());
+        self.alive.push(vertex.alive);
+    }
+
+    pub fn pop(&mut self) -> Option<Vertex<S>> {
+        if self.is_empty() {
+            return None;
+        }
+        let vertex = self.get(self.len());
+        self.position.pop();
+        self.source.pop();
+        self.halfedges.pop();
+        self.alive.pop();
+        Some(vertex)
+    }
+}
+
+/// The faces of a mesh as parallel arrays, indexed like Vertices.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Faces {
+    pub halfedge: Vec<u32>,
+    pub alive: Vec<bool>,
+}
+
+impl Faces {
+    pub fn len(&self) -> usize {
+        self.alive.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alive.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.halfedge.reserve(additional);
+        self.alive.reserve(additional);
+    }
+
+    /// Copies the face out of the arrays, dead or alive.
+    pub fn get(&self, id: Id) -> Face {
+        Face {
+            id: id,
+            halfedge: self.halfedge[id - 1] as Id,
+            alive: self.alive[id - 1],
+        }
+    }
+
+    /// Writes the face back to the index given by its id.
+    pub fn set(&mut self, face: Face) {
+        self.halfedge[face.id - 1] = face.halfedge as u32;
+        self.alive[face.id - 1] = face.alive;
+    }
+
+    pub fn push(&mut self, face: Face) {
+        self.halfedge.push(face.halfedge as u32);
+        self.alive.push(face.alive);
+    }
+
+    pub fn pop(&mut self) -> Option<Face> {
+        if self.is_empty() {
+            return None;
+        }
+        let face = self.get(self.len());
+        self.halfedge.pop();
+        self.alive.pop();
+        Some(face)
+    }
+}
+
+/// The halfedges of a mesh as parallel arrays, indexed like Vertices. The
+/// links hold ids, 0 when not set.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Halfedges {
+    pub vertex: Vec<u32>,
+    pub face: Vec<u32>,
+    pub prev: Vec<u32>,
+    pub next: Vec<u32>,
+    pub opposite: Vec<u32>,
+    pub alive: Vec<bool>,
+}
+
+impl Halfedges {
+    pub fn len(&self) -> usize {
+        self.alive.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alive.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.vertex.reserve(additional);
+        self.face.reserve(additional);
+        self.prev.reserve(additional);
+        self.next.reserve(additional);
+        self.opposite.reserve(additional);
+        self.alive.reserve(additional);
+    }
+
+    /// Copies the halfedge out of the arrays, dead or alive.
+    pub fn get(&self, id: Id) -> Halfedge {
+        let index = id - 1;
+        Halfedge {
+            id: id,
+            vertex: self.vertex[index] as Id,
+            face: self.face[index] as Id,
+            prev: self.prev[index] as Id,
+            next: self.next[index] as Id,
+            opposite: self.opposite[index] as Id,
+            alive: self.alive[index],
+        }
+    }
+
+    /// Writes the halfedge back to the index given by its id.
+    pub fn set(&mut self, halfedge: Halfedge) {
+        let index = halfedge.id - 1;
+        self.vertex[index] = halfedge.vertex as u32;
+        self.face[index] = halfedge.face as u32;
+        self.prev[index] = halfedge.prev as u32;
+        self.next[index] = halfedge.next as u32;
+        self.opposite[index] = halfedge.opposite as u32;
+        self.alive[index] = halfedge.alive;
+    }
+
+    pub fn push(&mut self, halfedge: Halfedge) {
+        self.vertex.push(halfedge.vertex as u32);
+        self.face.push(halfedge.face as u32);
+        self.prev.push(halfedge.prev as u32);
+        self.next.push(halfedge.next as u32);
+        self.opposite.push(halfedge.opposite as u32);
+        self.alive.push(halfedge.alive);
+    }
+
+    pub fn pop(&mut self) -> Option<Halfedge> {
+        if self.is_empty() {
+            return None;
+        }
+        let halfedge = self.get(self.len());
+        self.vertex.pop();
+        self.face.pop();
+        self.prev.pop();
+        self.next.pop();
+        self.opposite.pop();
+        self.alive.pop();
+        Some(halfedge)
+    }
+}
+
+/// Mutable access to one vertex, returned by Mesh::vertex_mut. It holds a
+/// copy of the vertex, which is written back to the arrays (and recorded in
+/// the journal) when the guard is dropped, if it was changed.
+pub struct VertexMut<'a, S: 'a + BaseFloat = f32> {
+    mesh: &'a mut Mesh<S>,
+    id: Id,
+    vertex: Vertex<S>,
+}
+
+impl<'a, S: BaseFloat> Deref for VertexMut<'a, S> {
+    type Target = Vertex<S>;
+
+    fn deref(&self) -> &Vertex<S> {
+        &self.vertex
+    }
+}
+
+impl<'a, S: BaseFloat> DerefMut for VertexMut<'a, S> {
+    fn deref_mut(&mut self) -> &mut Vertex<S> {
+        &mut self.vertex
+    }
+}
+
+impl<'a, S: BaseFloat> Drop for VertexMut<'a, S> {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.vertex.id = id;
+        if self.mesh.vertices.get(id) == self.vertex {
+            return;
+        }
+        journal::record_vertex(self.mesh, id);
+        self.mesh.vertices.set(self.vertex.clone());
+    }
+}
+
+/// Mutable access to one face, see VertexMut.
+pub struct FaceMut<'a, S: 'a + BaseFloat = f32> {
+    mesh: &'a mut Mesh<S>,
+    id: Id,
+    face: Face,
+}
+
+impl<'a, S: BaseFloat> Deref for FaceMut<'a, S> {
+    type Target = Face;
+
+    fn deref(&self) -> &Face {
+        &self.face
+    }
+}
+
+impl<'a, S: BaseFloat> DerefMut for FaceMut<'a, S> {
+    fn deref_mut(&mut self) -> &mut Face {
+        &mut self.face
+    }
+}
+
+impl<'a, S: BaseFloat> Drop for FaceMut<'a, S> {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.face.id = id;
+        if self.mesh.faces.get(id) == self.face {
+            return;
+        }
+        journal::record_face(self.mesh, id);
+        self.mesh.faces.set(self.face);
+    }
+}
+
+/// Mutable access to one halfedge, see VertexMut. A new start vertex is set
+/// with Mesh::set_halfedge_start_vertex_id, so the outgoing halfedges of the
+/// vertices stay in sync.
+pub struct HalfedgeMut<'a, S: 'a + BaseFloat = f32> {
+    mesh: &'a mut Mesh<S>,
+    id: Id,
+    halfedge: Halfedge,
+}
+
+impl<'a, S: BaseFloat> Deref for HalfedgeMut<'a, S> {
+    type Target = Halfedge;
+
+    fn deref(&self) -> &Halfedge {
+        &self.halfedge
+    }
+}
+
+impl<'a, S: BaseFloat> DerefMut for HalfedgeMut<'a, S> {
+    fn deref_mut(&mut self) -> &mut Halfedge {
+        &mut self.halfedge
+    }
+}
+
+impl<'a, S: BaseFloat> Drop for HalfedgeMut<'a, S> {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.halfedge.id = id;
+        self.mesh.set_halfedge_start_vertex_id(id, self.halfedge.vertex);
+        if self.mesh.halfedges.get(id) == self.halfedge {
+            return;
+        }
+        journal::record_halfedge(self.mesh, id);
+        self.mesh.halfedges.set(self.halfedge);
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct Point3Key {
-    x: u32,
-    y: u32,
-    z: u32,
+    x: i64,
+    y: i64,
+    z: i64,
 }
 
 impl Point3Key {
-    pub fn new(point: Point3<f32>) -> Self {
+    /// Rounds the point to a grid of 0.001, i64 keeps the grid exact for
+    /// negative and far from origin coordinates.
+    pub fn new<S: BaseFloat>(point: Point3<S>) -> Self {
+        let key = |value: S| (value * scalar(1000.0)).round().to_f64().unwrap() as i64;
         Point3Key {
-            x: (point.x * 1000.0).round() as u32,
-            y: (point.y * 1000.0).round() as u32,
-            z: (point.z * 1000.0).round() as u32,
+            x: key(point.x),
+            y: key(point.y),
+            z: key(point.z),
         }
     }
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EdgeEndpoints {
     pub low: Id,
     pub high: Id,
@@ -106,89 +442,156 @@ impl EdgeEndpoints {
 
 pub type FacePair = EdgeEndpoints;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsetMode {
+    /// Every face is inset on its own.
+    Individual,
+
+    /// Connected faces are inset together, only the border of the region gets a ring.
+    Region,
+}
+
+/// Halfedge mesh stored as dense u32 index arrays, see Vertices, Halfedges
+/// and Faces. Ids start at 1, 0 means none. Removed elements stay in the
+/// arrays with alive set to false.
+///
+/// Since there are no vertex, face or halfedge structs stored anymore,
+/// vertex(), face() and halfedge() return copies instead of references.
+/// vertex_mut(), face_mut() and halfedge_mut() return guards which write
+/// the changes back when dropped, the setters are cheaper for single fields.
 #[derive(Debug)]
-pub struct Mesh {
-    pub vertices: Vec<Vertex>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mesh<S = f32> {
+    pub vertices: Vertices<S>,
     pub vertex_count: usize,
-    pub faces: Vec<Face>,
+    pub faces: Faces,
     pub face_count: usize,
-    pub halfedges: Vec<Halfedge>,
+    pub halfedges: Halfedges,
     pub halfedge_count: usize,
-    pub edges: FnvHashMap<EdgeEndpoints, Id>
+    pub selections: HashMap<String, Selection>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub journal: Option<Journal<S>>,
 }
 
 impl Mesh {
+    /// Creates an empty f32 mesh, use Mesh::default() for other scalar
+    /// types.
     pub fn new() -> Self {
+        Mesh::default()
+    }
+}
+
+impl<S> Default for Mesh<S> {
+    fn default() -> Self {
         Mesh {
-            vertices: Vec::new(),
+            vertices: Vertices::default(),
             vertex_count: 0,
-            faces: Vec::new(),
+            faces: Faces::default(),
             face_count: 0,
-            halfedges: Vec::new(),
+            halfedges: Halfedges::default(),
             halfedge_count: 0,
-            edges: FnvHashMap::default()
+            selections: HashMap::new(),
+            journal: None,
         }
     }
+}
 
-    pub fn vertex(&self, id: Id) -> Option<&Vertex> {
-        if 0 == id {
+impl<S: BaseFloat> Mesh<S> {
+    pub fn is_vertex_alive(&self, id: Id) -> bool {
+        0 != id && self.vertices.alive[id - 1]
+    }
+
+    pub fn is_halfedge_alive(&self, id: Id) -> bool {
+        0 != id && self.halfedges.alive[id - 1]
+    }
+
+    pub fn is_face_alive(&self, id: Id) -> bool {
+        0 != id && self.faces.alive[id - 1]
+    }
+
+    pub fn vertex(&self, id: Id) -> Option<Vertex<S>> {
+        if !self.is_vertex_alive(id) {
             return None;
         }
-        {
-            let vertex = &self.vertices[id - 1];
-            if !vertex.alive {
-                return None;
-            }
+        Some(self.vertices.get(id))
+    }
+
+    pub fn vertex_mut(&mut self, id: Id) -> Option<VertexMut<'_, S>> {
+        let vertex = self.vertex(id)?;
+        Some(VertexMut {
+            mesh: self,
+            id: id,
+            vertex: vertex,
+        })
+    }
+
+    pub fn vertex_position(&self, id: Id) -> Option<Point3<S>> {
+        if !self.is_vertex_alive(id) {
+            return None;
         }
-        Some(&self.vertices[id - 1])
+        Some(self.vertices.position[id - 1])
     }
 
-    pub fn vertex_mut(&mut self, id: Id) -> Option<&mut Vertex> {
-        if 0 == id {
+    pub fn vertex_source(&self, id: Id) -> Option<i32> {
+        if !self.is_vertex_alive(id) {
             return None;
         }
-        {
-            let vertex = &self.vertices[id - 1];
-            if !vertex.alive {
-                return None;
-            }
+        Some(self.vertices.source[id - 1])
+    }
+
+    pub fn set_vertex_position(&mut self, id: Id, position: Point3<S>) {
+        if !self.is_vertex_alive(id) {
+            return;
         }
-        Some(&mut self.vertices[id - 1])
+        journal::record_vertex(self, id);
+        self.vertices.position[id - 1] = position;
+    }
+
+    pub fn set_vertex_source(&mut self, id: Id, source: i32) {
+        if !self.is_vertex_alive(id) {
+            return;
+        }
+        journal::record_vertex(self, id);
+        self.vertices.source[id - 1] = source;
     }
 
     pub fn peek_same_halfedge(&self, any_paired_id: Id) -> Id {
-        let halfedge = self.halfedge(any_paired_id).unwrap();
-        if halfedge.opposite > 0 && halfedge.opposite < any_paired_id {
-            return halfedge.opposite;
+        match self.halfedge_opposite_id(any_paired_id) {
+            Some(opposite_id) if opposite_id < any_paired_id => opposite_id,
+            _ => any_paired_id,
         }
-        any_paired_id
     }
 
-    pub fn edge_center(&self, id: Id) -> Point3<f32> {
-        let halfedge = self.halfedge(id).unwrap();
-        let next = self.halfedge(halfedge.next).unwrap();
-        Point3::midpoint(self.vertex(halfedge.vertex).unwrap().position,
-            self.vertex(next.vertex).unwrap().position)
+    pub fn edge_center(&self, id: Id) -> Point3<S> {
+        let next_id = self.halfedge_next_id(id).unwrap();
+        Point3::midpoint(self.halfedge_start_vertex_position(id).unwrap(),
+            self.halfedge_start_vertex_position(next_id).unwrap())
     }
 
-    pub fn face_center(&self, id: Id) -> Point3<f32> {
-        let face = self.face(id).unwrap();
-        let mut points = SmallVec::<[Point3<f32>; 4]>::new();
-        for halfedge_id in FaceHalfedgeIterator::new(self, face.halfedge) {
-            let halfedge = self.halfedge(halfedge_id).unwrap();
-            let vertex = self.vertex(halfedge.vertex).unwrap();
-            points.push(vertex.position);
+    pub fn face_center(&self, id: Id) -> Point3<S> {
+        let mut points = SmallVec::<[Point3<S>; 4]>::new();
+        for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(id).unwrap()) {
+            points.push(self.halfedge_start_vertex_position(halfedge_id).unwrap());
         }
         Point3::centroid(&points)
     }
 
-    pub fn face_norm(&self, id: Id) -> Vector3<f32> {
-        let face = self.face(id).unwrap();
+    pub fn face_area(&self, id: Id) -> S {
+        let mut points = SmallVec::<[Point3<S>; 4]>::new();
+        for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(id).unwrap()) {
+            points.push(self.halfedge_start_vertex_position(halfedge_id).unwrap());
+        }
+        let mut total = Vector3::zero();
+        for i in 1..points.len().saturating_sub(1) {
+            total += (points[i] - points[0]).cross(points[i + 1] - points[0]);
+        }
+        total.magnitude() * scalar(0.5)
+    }
+
+    pub fn face_norm(&self, id: Id) -> Vector3<S> {
         let mut points = Vec::new();
-        for halfedge_id in FaceHalfedgeIterator::new(self, face.halfedge) {
-            let halfedge = self.halfedge(halfedge_id).unwrap();
-            let vertex = self.vertex(halfedge.vertex).unwrap();
-            points.push(vertex.position);
+        for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(id).unwrap()) {
+            points.push(self.halfedge_start_vertex_position(halfedge_id).unwrap());
         }
         if points.len() < 3 {
             return Vector3::zero();
@@ -203,58 +606,71 @@ impl Mesh {
         total.normalize()
     }
 
-    pub fn face(&self, id: Id) -> Option<&Face> {
-        if 0 == id {
+    pub fn face(&self, id: Id) -> Option<Face> {
+        if !self.is_face_alive(id) {
             return None;
         }
-        {
-            let face = &self.faces[id - 1];
-            if !face.alive {
-                return None;
-            }
-        }
-        Some(&self.faces[id - 1])
+        Some(self.faces.get(id))
+    }
+
+    pub fn face_mut(&mut self, id: Id) -> Option<FaceMut<'_, S>> {
+        let face = self.face(id)?;
+        Some(FaceMut {
+            mesh: self,
+            id: id,
+            face: face,
+        })
     }
 
     pub fn face_adj_id(&self, id: Id) -> Option<Id> {
-        self.face(id)
-            .and_then(|f: &Face| self.halfedge(f.halfedge))
-            .and_then(|h: &Halfedge| self.halfedge(h.opposite))
-            .and_then(|o: &Halfedge| if 0 != o.face { Some(o.face) } else { None })
+        self.face_first_halfedge_id(id)
+            .and_then(|halfedge_id| self.halfedge_opposite_face_id(halfedge_id))
     }
 
-    pub fn face_adj(&self, id: Id) -> Option<&Face> {
+    pub fn face_adj(&self, id: Id) -> Option<Face> {
         self.face_adj_id(id).and_then(|id: Id| self.face(id))
     }
 
+    /// Reads a link from one of the halfedge arrays, None when the halfedge
+    /// is dead or the link is not set.
+    fn halfedge_link(&self, id: Id, links: &[u32]) -> Option<Id> {
+        if !self.is_halfedge_alive(id) {
+            return None;
+        }
+        match links[id - 1] {
+            0 => None,
+            link => Some(link as Id),
+        }
+    }
+
     pub fn halfedge_next_id(&self, id: Id) -> Option<Id> {
-        self.halfedge(id)
-            .and_then(|h: &Halfedge| if 0 != h.next { Some(h.next) } else { None })
+        self.halfedge_link(id, &self.halfedges.next)
     }
 
     pub fn halfedge_prev_id(&self, id: Id) -> Option<Id> {
-        self.halfedge(id)
-            .and_then(|h: &Halfedge| if 0 != h.prev { Some(h.prev) } else { None })
+        self.halfedge_link(id, &self.halfedges.prev)
     }
 
     pub fn halfedge_opposite_id(&self, id: Id) -> Option<Id> {
-        self.halfedge(id)
-            .and_then(|h: &Halfedge| if 0 != h.opposite { Some(h.opposite) } else { None })
+        self.halfedge_link(id, &self.halfedges.opposite)
     }
 
     pub fn face_first_halfedge_id(&self, id: Id) -> Option<Id> {
-        self.face(id)
-            .and_then(|f: &Face| if 0 != f.halfedge { Some(f.halfedge) } else { None })
+        if !self.is_face_alive(id) {
+            return None;
+        }
+        match self.faces.halfedge[id - 1] {
+            0 => None,
+            halfedge_id => Some(halfedge_id as Id),
+        }
     }
 
     pub fn halfedge_start_vertex_id(&self, id: Id) -> Option<Id> {
-        self.halfedge(id)
-            .and_then(|h: &Halfedge| if 0 != h.vertex { Some(h.vertex) } else { None })
+        self.halfedge_link(id, &self.halfedges.vertex)
     }
 
     pub fn halfedge_face_id(&self, id: Id) -> Option<Id> {
-        self.halfedge(id)
-            .and_then(|h: &Halfedge| if 0 != h.face { Some(h.face) } else { None })
+        self.halfedge_link(id, &self.halfedges.face)
     }
 
     pub fn halfedge_opposite_face_id(&self, id: Id) -> Option<Id> {
@@ -265,50 +681,122 @@ impl Mesh {
         self.halfedge_face_id(opposite_id.unwrap())
     }
 
-    pub fn halfedge_direct(&self, id: Id) -> Vector3<f32> {
-        let begin_pos = self.halfedge_start_vertex(id).unwrap().position;
-        let end_pos = self.halfedge_start_vertex(self.halfedge_next_id(id).unwrap()).unwrap().position;
+    pub fn halfedge_direct(&self, id: Id) -> Vector3<S> {
+        let begin_pos = self.halfedge_start_vertex_position(id).unwrap();
+        let end_pos = self.halfedge_start_vertex_position(self.halfedge_next_id(id).unwrap()).unwrap();
         end_pos - begin_pos
     }
 
+    /// Moves the start of the halfedge to another vertex, the outgoing
+    /// halfedges of both vertices are updated.
     pub fn set_halfedge_start_vertex_id(&mut self, halfedge_id: Id, vertex_id: Id) {
-        self.halfedge_mut(halfedge_id).unwrap().vertex = vertex_id;
+        if !self.is_halfedge_alive(halfedge_id) {
+            return;
+        }
+        let old_vertex_id = self.halfedges.vertex[halfedge_id - 1] as Id;
+        if old_vertex_id == vertex_id {
+            return;
+        }
+        if 0 != old_vertex_id {
+            journal::record_vertex(self, old_vertex_id);
+            self.vertices.halfedges[old_vertex_id - 1].retain(|id| *id as Id != halfedge_id);
+        }
+        if 0 != vertex_id {
+            journal::record_vertex(self, vertex_id);
+            let halfedges = &mut self.vertices.halfedges[vertex_id - 1];
+            if !halfedges.contains(&(halfedge_id as u32)) {
+                halfedges.push(halfedge_id as u32);
+            }
+        }
+        journal::record_halfedge(self, halfedge_id);
+        self.halfedges.vertex[halfedge_id - 1] = vertex_id as u32;
+    }
+
+    pub fn halfedge_start_vertex(&self, id: Id) -> Option<Vertex<S>> {
+        let vertex_id = self.halfedge_start_vertex_id(id)?;
+        self.vertex(vertex_id)
     }
 
-    pub fn halfedge_start_vertex_mut(&mut self, id: Id) -> Option<&mut Vertex> {
+    pub fn halfedge_start_vertex_mut(&mut self, id: Id) -> Option<VertexMut<'_, S>> {
         let vertex_id = self.halfedge_start_vertex_id(id)?;
         self.vertex_mut(vertex_id)
     }
 
-    pub fn halfedge_start_vertex(&self, id: Id) -> Option<&Vertex> {
+    pub fn halfedge_start_vertex_position(&self, id: Id) -> Option<Point3<S>> {
         let vertex_id = self.halfedge_start_vertex_id(id)?;
-        self.vertex(vertex_id)
+        self.vertex_position(vertex_id)
+    }
+
+    /// Records the halfedge in the journal and hands its index to the update,
+    /// nothing is done for dead halfedges.
+    fn update_halfedge<F: FnOnce(&mut Halfedges, usize)>(&mut self, id: Id, update: F) {
+        if !self.is_halfedge_alive(id) {
+            return;
+        }
+        journal::record_halfedge(self, id);
+        update(&mut self.halfedges, id - 1);
     }
 
     pub fn set_halfedge_opposite_id(&mut self, halfedge_id: Id, opposite_id: Id) {
-        let halfedge = self.halfedge_mut(halfedge_id);
-        if halfedge.is_none() {
+        self.update_halfedge(halfedge_id, |halfedges, index| halfedges.opposite[index] = opposite_id as u32);
+    }
+
+    pub fn set_halfedge_next_id(&mut self, halfedge_id: Id, next_id: Id) {
+        self.update_halfedge(halfedge_id, |halfedges, index| halfedges.next[index] = next_id as u32);
+    }
+
+    pub fn set_halfedge_prev_id(&mut self, halfedge_id: Id, prev_id: Id) {
+        self.update_halfedge(halfedge_id, |halfedges, index| halfedges.prev[index] = prev_id as u32);
+    }
+
+    pub fn set_halfedge_face_id(&mut self, halfedge_id: Id, face_id: Id) {
+        self.update_halfedge(halfedge_id, |halfedges, index| halfedges.face[index] = face_id as u32);
+    }
+
+    pub fn set_face_first_halfedge_id(&mut self, face_id: Id, halfedge_id: Id) {
+        if !self.is_face_alive(face_id) {
             return;
         }
-        halfedge.unwrap().opposite = opposite_id;
+        journal::record_face(self, face_id);
+        self.faces.halfedge[face_id - 1] = halfedge_id as u32;
     }
 
-    fn remove_halfedges_from_edges(&mut self, halfedges: &Vec<Id>) {
-        for &halfedge_id in halfedges {
-            let opposite = self.halfedge_opposite_id(halfedge_id);
-            let next_halfedge_id = self.halfedge_next_id(halfedge_id).unwrap();
-            let endpoints = EdgeEndpoints::new(self.halfedge_start_vertex_id(halfedge_id).unwrap(), 
-                self.halfedge_start_vertex_id(next_halfedge_id).unwrap());
-            if let Some(&id) = self.edges.get(&endpoints) {
-                if id == halfedge_id {
-                    if !opposite.is_none() {
-                        *self.edges.get_mut(&endpoints).unwrap() = opposite.unwrap();
-                    } else {
-                        self.edges.remove(&endpoints);
-                    }
+    /// Finds a linked halfedge running between the two vertices, in either
+    /// direction, by scanning the outgoing halfedges of both vertices instead
+    /// of looking the edge up in a map. Halfedges which are not paired yet are
+    /// preferred, then the lowest id.
+    pub fn edge_halfedge_id(&self, first_vertex_id: Id, second_vertex_id: Id) -> Option<Id> {
+        self.find_edge_halfedge(first_vertex_id, second_vertex_id, 0)
+    }
+
+    fn find_edge_halfedge(&self, first_vertex_id: Id, second_vertex_id: Id, except_id: Id) -> Option<Id> {
+        let mut found : Option<(bool, Id)> = None;
+        for &(from_id, to_id) in [(first_vertex_id, second_vertex_id), (second_vertex_id, first_vertex_id)].iter() {
+            if !self.is_vertex_alive(from_id) {
+                continue;
+            }
+            for &halfedge_id in self.vertices.halfedges[from_id - 1].iter() {
+                let halfedge_id = halfedge_id as Id;
+                let index = halfedge_id - 1;
+                let next_id = self.halfedges.next[index] as Id;
+                if halfedge_id == except_id || !self.halfedges.alive[index] || 0 == next_id ||
+                        self.halfedges.vertex[index] as Id != from_id ||
+                        self.halfedges.vertex[next_id - 1] as Id != to_id {
+                    continue;
+                }
+                let candidate = (0 != self.halfedges.opposite[index], halfedge_id);
+                match found {
+                    Some(best) if best <= candidate => {},
+                    _ => found = Some(candidate),
                 }
             }
         }
+        found.map(|(_, halfedge_id)| halfedge_id)
+    }
+
+    /// Number of undirected edges, counted from the topology.
+    pub fn edge_count(&self) -> usize {
+        EdgeIterator::new(self).count()
     }
 
     pub fn halfedge_start_vertex_alt_halfedge_id(&self, halfedge_id: Id) -> Option<Id> {
@@ -326,142 +814,120 @@ impl Mesh {
         None
     }
 
+    /// Takes the halfedge out of the outgoing halfedges of its start vertex,
+    /// the vertex dies when it has none left.
+    fn detach_halfedge_from_vertex(&mut self, halfedge_id: Id) {
+        let vertex_id = match self.halfedge_start_vertex_id(halfedge_id) {
+            Some(vertex_id) if self.is_vertex_alive(vertex_id) => vertex_id,
+            _ => return,
+        };
+        journal::record_vertex(self, vertex_id);
+        let index = vertex_id - 1;
+        if let Some(position) = self.vertices.halfedges[index].iter().position(|&id| id as Id == halfedge_id) {
+            self.vertices.halfedges[index].remove(position);
+        }
+        if self.vertices.halfedges[index].is_empty() {
+            self.vertices.alive[index] = false;
+            self.vertex_count -= 1;
+        }
+    }
+
     pub fn remove_face(&mut self, id: Id) {
         let halfedge_collection = FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(id).unwrap()).into_vec();
-        self.remove_halfedges_from_edges(&halfedge_collection);
         for &halfedge_id in halfedge_collection.iter() {
-            let vertex_count_need_reduce = {
-                let vertex = self.halfedge_start_vertex_mut(halfedge_id).unwrap();
-                for i in 0..vertex.halfedges.len() {
-                    if halfedge_id == vertex.halfedges[i] {
-                        vertex.halfedges.remove(i);
-                        break;
-                    }
-                }
-                if vertex.halfedges.is_empty() {
-                    vertex.alive = false;
-                    true
-                } else {
-                    false
-                }
-            };
-            if vertex_count_need_reduce {
-                self.vertex_count -= 1;
-            }
+            self.detach_halfedge_from_vertex(halfedge_id);
         }
         for &halfedge_id in halfedge_collection.iter() {
             let opposite = self.halfedge_opposite_id(halfedge_id);
             if !opposite.is_none() {
                 self.set_halfedge_opposite_id(opposite.unwrap(), 0);
             }
-            self.halfedge_mut(halfedge_id).unwrap().alive = false;
+            self.update_halfedge(halfedge_id, |halfedges, index| halfedges.alive[index] = false);
             self.halfedge_count -= 1;
         }
-        self.face_mut(id).unwrap().alive = false;
+        journal::record_face(self, id);
+        self.faces.alive[id - 1] = false;
         self.face_count -= 1;
     }
 
-    pub fn face_mut(&mut self, id: Id) -> Option<&mut Face> {
-        if 0 == id {
-            return None;
-        }
-        {
-            let face = &self.faces[id - 1];
-            if !face.alive {
-                return None;
-            }
+    /// Marks a vertex left without faces by remove_face as alive again, so
+    /// new faces can reuse it.
+    pub fn revive_vertex(&mut self, id: Id) {
+        if 0 == id || self.vertices.alive[id - 1] {
+            return;
         }
-        Some(&mut self.faces[id - 1])
+        journal::record_vertex(self, id);
+        self.vertices.alive[id - 1] = true;
+        self.vertex_count += 1;
     }
 
-    pub fn halfedge(&self, id: Id) -> Option<&Halfedge> {
-        if 0 == id {
+    pub fn halfedge(&self, id: Id) -> Option<Halfedge> {
+        if !self.is_halfedge_alive(id) {
             return None;
         }
-        {
-            let halfedge = &self.halfedges[id - 1];
-            if !halfedge.alive {
-                return None;
-            }
-        }
-        Some(&self.halfedges[id - 1])
+        Some(self.halfedges.get(id))
     }
 
-    pub fn halfedge_mut(&mut self, id: Id) -> Option<&mut Halfedge> {
-        if 0 == id {
-            return None;
-        }
-        {
-            let halfedge = &self.halfedges[id - 1];
-            if !halfedge.alive {
-                return None;
-            }
-        }
-        Some(&mut self.halfedges[id - 1])
+    pub fn halfedge_mut(&mut self, id: Id) -> Option<HalfedgeMut<'_, S>> {
+        let halfedge = self.halfedge(id)?;
+        Some(HalfedgeMut {
+            mesh: self,
+            id: id,
+            halfedge: halfedge,
+        })
     }
 
-    pub fn add_vertex(&mut self, position: Point3<f32>) -> usize {
+    pub fn add_vertex(&mut self, position: Point3<S>) -> usize {
         let new_id = self.vertices.len() + 1;
-        self.vertices.push(Vertex {
-            id: new_id,
-            halfedges: SmallVec::<[Id; VERTEX_HALFEDGE_INLINE_COUNT]>::new(),
-            prev: 0,
-            next: 0,
-            position : position,
-            alive: true,
-            source: -1,
-        });
+        journal::record_added(self, Change::PopVertex, new_id);
+        self.vertices.position.push(position);
+        self.vertices.source.push(-1);
+        self.vertices.halfedges.push(SmallVec::new());
+        self.vertices.alive.push(true);
         self.vertex_count += 1;
         new_id
     }
 
     pub fn add_halfedge(&mut self) -> Id {
         let new_id = self.halfedges.len() + 1;
-        self.halfedges.push(Halfedge {
-            id: new_id,
-            vertex: 0,
-            face: 0,
-            prev: 0,
-            next: 0,
-            opposite: 0,
-            alive: true,
-        });
+        journal::record_added(self, Change::PopHalfedge, new_id);
+        self.halfedges.vertex.push(0);
+        self.halfedges.face.push(0);
+        self.halfedges.prev.push(0);
+        self.halfedges.next.push(0);
+        self.halfedges.opposite.push(0);
+        self.halfedges.alive.push(true);
         self.halfedge_count += 1;
         new_id
     }
 
     pub fn pair_halfedges(&mut self, first: Id, second: Id) {
-        self.halfedge_mut(first).unwrap().opposite = second;
-        self.halfedge_mut(second).unwrap().opposite = first;
+        self.set_halfedge_opposite_id(first, second);
+        self.set_halfedge_opposite_id(second, first);
     }
 
     pub fn unpair_halfedges(&mut self, first: Id, second: Id) {
-        self.halfedge_mut(first).unwrap().opposite = 0;
-        self.halfedge_mut(second).unwrap().opposite = 0;
+        self.set_halfedge_opposite_id(first, 0);
+        self.set_halfedge_opposite_id(second, 0);
     }
 
+    /// Links first to second and pairs first with a halfedge already linked
+    /// along the same edge, if there is one.
     pub fn link_halfedges(&mut self, first: Id, second: Id) {
-        self.halfedge_mut(first).unwrap().next = second;
-        self.halfedge_mut(second).unwrap().prev = first;
-        let endpoints = EdgeEndpoints::new(self.halfedge(first).unwrap().vertex,
-            self.halfedge(second).unwrap().vertex);
-        match self.edges.get(&endpoints) {
-            Some(&halfedge) => self.pair_halfedges(first, halfedge),
-            _ => {
-                self.edges.insert(endpoints, first);
-            }
-        };
+        self.set_halfedge_next_id(first, second);
+        self.set_halfedge_prev_id(second, first);
+        let from_id = self.halfedges.vertex[first - 1] as Id;
+        let to_id = self.halfedges.vertex[second - 1] as Id;
+        if let Some(halfedge) = self.find_edge_halfedge(from_id, to_id, first) {
+            self.pair_halfedges(first, halfedge);
+        }
     }
 
     pub fn add_face(&mut self) -> Id {
         let new_id = self.faces.len() + 1;
-        self.faces.push(Face {
-            id: new_id,
-            halfedge: 0,
-            prev: 0,
-            next: 0,
-            alive: true,
-        });
+        journal::record_added(self, Change::PopFace, new_id);
+        self.faces.halfedge.push(0);
+        self.faces.alive.push(true);
         self.face_count += 1;
         new_id
     }
@@ -494,13 +960,13 @@ impl Mesh {
     }
 
     pub fn add_vertices(&mut self, added_vertices : Vec<Id>) -> Id {
-        assert!(added_vertices.len() < 1000);
+        assert!(added_vertices.len() < MAX_FACE_VERTEX_COUNT);
         if added_vertices.is_empty() {
             return 0;
         }
         let mut added_halfedges : Vec<(Id, Id)> = Vec::new();
         for i in 0..added_vertices.len() {
-            if self.vertex(added_vertices[i]).is_none() {
+            if !self.is_vertex_alive(added_vertices[i]) {
                 return 0;
             }
         }
@@ -510,7 +976,7 @@ impl Mesh {
         self.add_halfedges_and_vertices(&added_halfedges)
     }
 
-    pub fn add_positions(&mut self, added_positions : Vec<Point3<f32>>) -> Id {
+    pub fn add_positions(&mut self, added_positions : Vec<Point3<S>>) -> Id {
         if added_positions.is_empty() {
             return 0;
         }
@@ -528,17 +994,10 @@ impl Mesh {
         }
         let added_face_id = self.add_face();
         for &(added_halfedge_id, added_vertex_id) in added_halfedges.iter() {
-            {
-                let vert = self.vertex_mut(added_vertex_id).unwrap();
-                let halfedges = &mut vert.halfedges;
-                if !halfedges.contains(&added_halfedge_id) {
-                    halfedges.push(added_halfedge_id);
-                }
-            }
-            self.halfedge_mut(added_halfedge_id).unwrap().face = added_face_id;
-            self.halfedge_mut(added_halfedge_id).unwrap().vertex = added_vertex_id;
+            self.set_halfedge_face_id(added_halfedge_id, added_face_id);
+            self.set_halfedge_start_vertex_id(added_halfedge_id, added_vertex_id);
         }
-        self.face_mut(added_face_id).unwrap().halfedge = added_halfedges[0].0;
+        self.set_face_first_halfedge_id(added_face_id, added_halfedges[0].0);
         for i in 0..added_halfedges.len() {
             let first = added_halfedges[i].0;
             let second = added_halfedges[(i + 1) % added_halfedges.len()].0;
@@ -547,7 +1006,7 @@ impl Mesh {
         added_face_id
     }
 
-    pub fn extrude_halfedges(&mut self, halfedges: &Vec<Id>, normal: Vector3<f32>, amount: f32) {
+    pub fn extrude_halfedges(&mut self, halfedges: &Vec<Id>, normal: Vector3<S>, amount: S) {
         let mut downside_halfedges: Vec<Id> = Vec::new();
         let mut downside_vertices: Vec<Id> = Vec::new();
         let direct = normal * amount;
@@ -555,39 +1014,16 @@ impl Mesh {
         for &halfedge_id in halfedges {
             let opposite = self.halfedge_opposite_id(halfedge_id);
             if opposite.is_none() {
-                let old_position = {
-                    let mut vertex = self.halfedge_start_vertex_mut(halfedge_id).unwrap();
-                    let position = vertex.position;
-                    vertex.position += direct;
-                    position
-                };
+                let vertex_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                let old_position = self.vertex_position(vertex_id).unwrap();
+                self.set_vertex_position(vertex_id, old_position + direct);
                 downside_vertices.push(self.add_vertex(old_position));
                 downside_halfedges.push(self.add_halfedge());
                 need_fill_downside = true;
             } else {
                 let old_vertex_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
-                let copy_position = self.halfedge_start_vertex(halfedge_id).unwrap().position;
+                let copy_position = self.vertex_position(old_vertex_id).unwrap();
                 let copy_vertex = self.add_vertex(copy_position);
-                {
-                    let vertex_count_need_reduce = {
-                        let vertex = self.halfedge_start_vertex_mut(old_vertex_id).unwrap();
-                        for i in 0..vertex.halfedges.len() {
-                            if vertex.halfedges[i] == halfedge_id {
-                                vertex.halfedges.remove(i);
-                                break;
-                            }
-                        }
-                        if vertex.halfedges.is_empty() {
-                            vertex.alive = false;
-                            true
-                        } else {
-                            false
-                        }
-                    };
-                    if vertex_count_need_reduce {
-                        self.vertex_count -= 1;
-                    }
-                }
                 self.set_halfedge_start_vertex_id(halfedge_id, copy_vertex);
                 self.unpair_halfedges(halfedge_id, opposite.unwrap());
                 downside_vertices.push(old_vertex_id);
@@ -619,7 +1055,7 @@ impl Mesh {
         }
     }
 
-    pub fn extrude_face(&mut self, face_id: Id, normal: Vector3<f32>, amount: f32) -> &mut Self {
+    pub fn extrude_face(&mut self, face_id: Id, normal: Vector3<S>, amount: S) -> &mut Self {
         let mut new_halfedges : Vec<Id> = Vec::new();
         for halfedge_id in FaceHalfedgeIterator::new(self, face_id) {
             new_halfedges.push(halfedge_id);
@@ -628,13 +1064,110 @@ impl Mesh {
         self
     }
 
-    pub fn add_plane(&mut self, width: f32, depth: f32) -> Id {
-        let x = width / 2.0;
-        let y = depth / 2.0;
-        let points = vec![Point3 {x: -x, y: -y, z: 0.0},
-            Point3 {x: x, y: -y, z: 0.0},
-            Point3 {x: x, y: y, z: 0.0},
-            Point3 {x: -x, y: y, z: 0.0}];
+    /// Insets the faces, each face gets a smaller copy which is surrounded by
+    /// a ring of quads bridging it to the original border. Thickness is the
+    /// inward distance of the border, depth moves the inset faces along their
+    /// normals. Returns the inset faces.
+    pub fn inset_faces(&mut self, face_ids: &[Id], thickness: S, depth: S, mode: InsetMode) -> Vec<Id> {
+        match mode {
+            InsetMode::Individual => {
+                let mut inset_faces = Vec::new();
+                for &face_id in face_ids {
+                    inset_faces.extend(self.inset_region(&[face_id], thickness, depth));
+                }
+                inset_faces
+            },
+            InsetMode::Region => self.inset_region(face_ids, thickness, depth),
+        }
+    }
+
+    fn inset_region(&mut self, face_ids: &[Id], thickness: S, depth: S) -> Vec<Id> {
+        let region : HashSet<Id> = face_ids.iter().cloned().filter(|&face_id| self.face(face_id).is_some()).collect();
+        let mut polygons : Vec<Vec<Id>> = Vec::new();
+        let mut border : Vec<(Id, Id)> = Vec::new();
+        let mut vertex_norm_map : HashMap<Id, Vector3<S>> = HashMap::new();
+        let mut incoming_inward_map : HashMap<Id, Vector3<S>> = HashMap::new();
+        let mut outgoing_inward_map : HashMap<Id, Vector3<S>> = HashMap::new();
+        let mut visited : HashSet<Id> = HashSet::new();
+        for &face_id in face_ids {
+            if !region.contains(&face_id) || !visited.insert(face_id) {
+                continue;
+            }
+            let face_normal = self.face_norm(face_id);
+            let mut vertices = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                let from_vert_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                vertices.push(from_vert_id);
+                *vertex_norm_map.entry(from_vert_id).or_insert(Vector3::zero()) += face_normal;
+                let is_border = match self.halfedge_opposite_face_id(halfedge_id) {
+                    Some(opposite_face_id) => !region.contains(&opposite_face_id),
+                    None => true,
+                };
+                if is_border {
+                    let to_vert_id = self.halfedge_start_vertex_id(self.halfedge_next_id(halfedge_id).unwrap()).unwrap();
+                    let inward = face_normal.cross(self.halfedge_direct(halfedge_id));
+                    let inward = if inward.magnitude2() > S::zero() { inward.normalize() } else { inward };
+                    outgoing_inward_map.insert(from_vert_id, inward);
+                    incoming_inward_map.insert(to_vert_id, inward);
+                    border.push((from_vert_id, to_vert_id));
+                }
+            }
+            polygons.push(vertices);
+        }
+        let mut inset_positions : HashMap<Id, Point3<S>> = HashMap::new();
+        for (&vert_id, &vertex_normal) in vertex_norm_map.iter() {
+            let mut position = self.vertex_position(vert_id).unwrap();
+            if vertex_normal.magnitude2() > S::zero() {
+                position += vertex_normal.normalize() * depth;
+            }
+            let incoming = incoming_inward_map.get(&vert_id).cloned();
+            let outgoing = outgoing_inward_map.get(&vert_id).cloned();
+            if incoming.is_some() || outgoing.is_some() {
+                let first = incoming.or(outgoing).unwrap();
+                let second = outgoing.or(incoming).unwrap();
+                let bisector = first + second;
+                let offset = if bisector.magnitude2() > scalar(SMALL_NUM) {
+                    let bisector = bisector.normalize();
+                    // Keep the distance to both border edges equal to the thickness
+                    bisector * (thickness / bisector.dot(first).max(scalar(0.2)))
+                } else {
+                    first * thickness
+                };
+                position += offset;
+            }
+            inset_positions.insert(vert_id, position);
+        }
+        for &face_id in region.iter() {
+            self.remove_face(face_id);
+        }
+        let mut inset_vert_map : HashMap<Id, Id> = HashMap::new();
+        for (&vert_id, &position) in inset_positions.iter() {
+            let source = self.vertices.source[vert_id - 1];
+            let inset_vert_id = self.add_vertex(position);
+            self.set_vertex_source(inset_vert_id, source);
+            inset_vert_map.insert(vert_id, inset_vert_id);
+        }
+        for &(from_vert_id, to_vert_id) in border.iter() {
+            self.revive_vertex(from_vert_id);
+            self.revive_vertex(to_vert_id);
+        }
+        let mut inset_faces = Vec::new();
+        for vertices in polygons {
+            inset_faces.push(self.add_vertices(vertices.iter().map(|vert_id| inset_vert_map[vert_id]).collect()));
+        }
+        for (from_vert_id, to_vert_id) in border {
+            self.add_vertices(vec![from_vert_id, to_vert_id, inset_vert_map[&to_vert_id], inset_vert_map[&from_vert_id]]);
+        }
+        inset_faces
+    }
+
+    pub fn add_plane(&mut self, width: S, depth: S) -> Id {
+        let x = width / scalar(2.0);
+        let y = depth / scalar(2.0);
+        let points = vec![Point3 {x: -x, y: -y, z: S::zero()},
+            Point3 {x: x, y: -y, z: S::zero()},
+            Point3 {x: x, y: y, z: S::zero()},
+            Point3 {x: -x, y: y, z: S::zero()}];
         let mut added_halfedges : Vec<(Id, Id)> = Vec::new();
         for i in 0..points.len() {
             added_halfedges.push((self.add_halfedge(), self.add_vertex(points[i])));
@@ -642,88 +1175,120 @@ impl Mesh {
         self.add_halfedges_and_vertices(&added_halfedges)
     }
 
-    pub fn transform(&mut self, mat: &Matrix4<f32>) -> &mut Self {
-        for vertex in self.vertices.iter_mut() {
-            vertex.position = mat.transform_point(vertex.position);
+    pub fn transform(&mut self, mat: &Matrix4<S>) -> &mut Self {
+        if self.journal.is_some() {
+            for id in 1..self.vertices.len() + 1 {
+                journal::record_vertex(self, id);
+            }
+        }
+        for position in self.vertices.position.iter_mut() {
+            *position = mat.transform_point(*position);
         }
         self
     }
 
-    pub fn translate(&mut self, x: f32, y: f32, z: f32) -> &mut Self {
+    pub fn translate(&mut self, x: S, y: S, z: S) -> &mut Self {
         let mat = Matrix4::from_translation(Vector3::new(x, y, z));
         self.transform(&mat)
     }
 
-    pub fn scale(&mut self, value: f32) -> &mut Self {
+    pub fn scale(&mut self, value: S) -> &mut Self {
         let mat = Matrix4::from_scale(value);
         self.transform(&mat)
     }
 
+    /// Copies the mesh with the positions converted to another scalar type,
+    /// ids, topology and selections are kept. The journal is not copied.
+    pub fn cast<T: BaseFloat>(&self) -> Mesh<T> {
+        Mesh {
+            vertices: Vertices {
+                position: self.vertices.position.iter().map(|position| position.cast().unwrap()).collect(),
+                source: self.vertices.source.clone(),
+                halfedges: self.vertices.halfedges.clone(),
+                alive: self.vertices.alive.clone(),
+            },
+            vertex_count: self.vertex_count,
+            faces: self.faces.clone(),
+            face_count: self.face_count,
+            halfedges: self.halfedges.clone(),
+            halfedge_count: self.halfedge_count,
+            selections: self.selections.clone(),
+            journal: None,
+        }
+    }
+
     pub fn weld(&self) -> Self {
-        let mut new_mesh = Mesh::new();
+        let mut new_mesh = Mesh::default();
         let mut vertices_set : HashMap<Point3Key, Id> = HashMap::new();
+        let mut new_vert_map : HashMap<Id, Id> = HashMap::new();
+        let mut new_face_map : HashMap<Id, Id> = HashMap::new();
         for face_id in FaceIterator::new(&self) {
             let face = self.face(face_id).unwrap();
             let mut key_set : HashSet<Point3Key> = HashSet::new();
-            let mut positions : Vec<(Point3<f32>, i32)> = Vec::new();
+            let mut positions : Vec<(Point3<S>, i32, Id)> = Vec::new();
             for halfedge_id in FaceHalfedgeIterator::new(&self, face.halfedge) {
-                let vertex = self.halfedge_start_vertex(halfedge_id).unwrap();
-                let key = Point3Key::new(vertex.position);
+                let vertex_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                let position = self.vertex_position(vertex_id).unwrap();
+                let key = Point3Key::new(position);
                 if key_set.contains(&key) {
                     continue;
                 }
                 key_set.insert(key);
-                positions.push((vertex.position, vertex.source));
+                positions.push((position, self.vertex_source(vertex_id).unwrap(), vertex_id));
             }
             if positions.len() < 3 {
                 continue;
             }
             let mut added_vertices : Vec<Id> = Vec::new();
-            for &(pos, source) in positions.iter() {
+            for &(pos, source, old_vert_id) in positions.iter() {
                 let key = Point3Key::new(pos);
                 let new_vert_id = *vertices_set.entry(key).or_insert_with(|| {
                     let new_added_vert_id = new_mesh.add_vertex(pos);
-                    new_mesh.vertex_mut(new_added_vert_id).unwrap().source = source;
+                    new_mesh.set_vertex_source(new_added_vert_id, source);
                     new_added_vert_id
                 });
+                new_vert_map.insert(old_vert_id, new_vert_id);
                 added_vertices.push(new_vert_id);
             }
-            new_mesh.add_vertices(added_vertices);
+            new_face_map.insert(face_id, new_mesh.add_vertices(added_vertices));
         }
+        remap_selections(self, &mut new_mesh, &new_vert_map, &new_face_map);
         new_mesh
     }
 
-    pub fn add_mesh(&mut self, other: &Mesh) {
+    pub fn add_mesh(&mut self, other: &Mesh<S>) {
         let mut vertices_set : HashMap<Id, Id> = HashMap::new();
+        let mut new_face_map : HashMap<Id, Id> = HashMap::new();
         for face_id in FaceIterator::new(&other) {
             let face = other.face(face_id).unwrap();
             let mut added_halfedges : Vec<(Id, Id)> = Vec::new();
             for halfedge_id in FaceHalfedgeIterator::new(&other, face.halfedge) {
-                let vertex = other.halfedge_start_vertex(halfedge_id).unwrap();
-                let key = vertex.id;
+                let key = other.halfedge_start_vertex_id(halfedge_id).unwrap();
                 if let Some(&new_vertex_id) = vertices_set.get(&key) {
                     added_halfedges.push((self.add_halfedge(), new_vertex_id));
                 } else {
-                    let new_vertex_id = self.add_vertex(vertex.position);
-                    self.vertex_mut(new_vertex_id).unwrap().source = vertex.source;
+                    let new_vertex_id = self.add_vertex(other.vertex_position(key).unwrap());
+                    self.set_vertex_source(new_vertex_id, other.vertex_source(key).unwrap());
                     vertices_set.insert(key, new_vertex_id);
                     added_halfedges.push((self.add_halfedge(), new_vertex_id));
                 }
             }
-            self.add_halfedges_and_vertices(&added_halfedges);
+            new_face_map.insert(face_id, self.add_halfedges_and_vertices(&added_halfedges));
         }
+        remap_selections(other, self, &vertices_set, &new_face_map);
     }
 
-    pub fn flip_mesh(&self) -> Mesh {
-        let mut new_mesh = Mesh::new();
+    pub fn flip_mesh(&self) -> Self {
+        let mut new_mesh = Mesh::default();
         let mut new_vert_map = HashMap::new();
+        let mut new_face_map = HashMap::new();
         for face_id in FaceIterator::new(self) {
             let mut verts = Vec::new();
             for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
-                let old_vert = self.halfedge_start_vertex(halfedge_id).unwrap();
-                let new_vert_id = new_vert_map.entry(old_vert.id).or_insert_with(|| {
-                    let new_added_vert_id = new_mesh.add_vertex(old_vert.position);
-                    new_mesh.vertex_mut(new_added_vert_id).unwrap().source = old_vert.source;
+                let old_vert_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                let new_vert_id = new_vert_map.entry(old_vert_id).or_insert_with(|| {
+                    let new_added_vert_id = new_mesh.add_vertex(self.vertex_position(old_vert_id).unwrap());
+                    new_mesh.set_vertex_source(new_added_vert_id, self.vertex_source(old_vert_id).unwrap());
                     new_added_vert_id
                 });
                 verts.push(*new_vert_id);
@@ -732,18 +1297,19 @@ impl Mesh {
             for new_vert_id in verts.iter().rev() {
                 added_halfedges.push((new_mesh.add_halfedge(), *new_vert_id));
             }
-            new_mesh.add_halfedges_and_vertices(&added_halfedges);
+            new_face_map.insert(face_id, new_mesh.add_halfedges_and_vertices(&added_halfedges));
         }
+        remap_selections(self, &mut new_mesh, &new_vert_map, &new_face_map);
         new_mesh
     }
 
-    pub fn split_mesh_by_other(&self, other: &Mesh) -> (Mesh, Mesh) {
-        let mut inner_mesh = Mesh::new();
-        let mut outter_mesh = Mesh::new();
+    pub fn split_mesh_by_other(&self, other: &Mesh<S>) -> (Self, Self) {
+        let mut inner_mesh = Mesh::default();
+        let mut outter_mesh = Mesh::default();
         inner_mesh.add_mesh(self);
         for face_id in FaceIterator::new(other) {
             let norm = other.face_norm(face_id);
-            let point = other.halfedge_start_vertex(other.face_first_halfedge_id(face_id).unwrap()).unwrap().position;
+            let point = other.halfedge_start_vertex_position(other.face_first_halfedge_id(face_id).unwrap()).unwrap();
             let (sub_front, sub_back) = inner_mesh.split_mesh_by_plane(point, norm, false);
             inner_mesh = sub_back;
             outter_mesh.add_mesh(&sub_front);
@@ -751,40 +1317,40 @@ impl Mesh {
         (outter_mesh, inner_mesh)
     }
 
-    pub fn union_convex_mesh(&self, other: &Mesh) -> Mesh {
+    pub fn union_convex_mesh(&self, other: &Mesh<S>) -> Self {
         let (other_outter, _) = other.split_mesh_by_other(self);
         let (my_outter, _) = self.split_mesh_by_other(other);
         let mesh = other_outter + my_outter;
         mesh.weld().fix_tjunction().combine_adj_faces()
     }
 
-    pub fn diff_convex_mesh(&self, other: &Mesh) -> Mesh {
+    pub fn diff_convex_mesh(&self, other: &Mesh<S>) -> Self {
         let (_, other_inner) =  other.split_mesh_by_other(self);
         let (my_outter, _) = self.split_mesh_by_other(other);
         let mesh = other_inner.flip_mesh() + my_outter;
         mesh.weld().fix_tjunction().combine_adj_faces()
     }
 
-    pub fn intersect_convex_mesh(&self, other: &Mesh) -> Mesh {
+    pub fn intersect_convex_mesh(&self, other: &Mesh<S>) -> Self {
         let (_, other_inner) =  other.split_mesh_by_other(self);
         let (_, my_inner) = self.split_mesh_by_other(other);
         let mesh = other_inner + my_inner;
         mesh.weld().fix_tjunction().combine_adj_faces()
     }
 
-    pub fn split_mesh_by_plane(&self, pt_on_plane: Point3<f32>, norm: Vector3<f32>, fill_cut: bool) -> (Mesh, Mesh) { 
+    pub fn split_mesh_by_plane(&self, pt_on_plane: Point3<S>, norm: Vector3<S>, fill_cut: bool) -> (Self, Self) { 
         let mut vert_side_map : HashMap<Id, PointSide> = HashMap::new();
         for face_id in FaceIterator::new(self) {
             for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
-                let vert = self.halfedge_start_vertex(halfedge_id).unwrap();
-                vert_side_map.entry(vert.id).or_insert(point_side_on_plane(vert.position, pt_on_plane, norm));
+                let vert_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                vert_side_map.entry(vert_id).or_insert(point_side_on_plane(self.vertex_position(vert_id).unwrap(), pt_on_plane, norm));
             }
         }
-        let mut front_mesh = Mesh::new();
-        let mut back_mesh = Mesh::new();
+        let mut front_mesh = Mesh::default();
+        let mut back_mesh = Mesh::default();
         let mut front_vert_map : HashMap<Id, Id> = HashMap::new();
         let mut back_vert_map : HashMap<Id, Id> = HashMap::new();
-        let mut intersect_map : HashMap<EdgeEndpoints, SegmentPlaneIntersect> = HashMap::new();
+        let mut intersect_map : HashMap<EdgeEndpoints, SegmentPlaneIntersect<S>> = HashMap::new();
         let mut front_intersect_map : HashMap<EdgeEndpoints, Id> = HashMap::new();
         let mut back_intersect_map : HashMap<EdgeEndpoints, Id> = HashMap::new();
         let mut front_cut_map : HashMap<Id, Id> = HashMap::new();
@@ -806,7 +1372,9 @@ impl Mesh {
                 let edge = EdgeEndpoints::new(from_vert_id, to_vert_id);
                 if from_is_front {
                     let new_vert_id = *front_vert_map.entry(from_vert_id).or_insert_with(|| {
-                        front_mesh.add_vertex(self.vertex(from_vert_id).unwrap().position)
+                        let new_vert_id = front_mesh.add_vertex(self.vertex_position(from_vert_id).unwrap());
+                        front_mesh.set_vertex_source(new_vert_id, self.vertex_source(from_vert_id).unwrap());
+                        new_vert_id
                     });
                     if front_new_vert_set.insert(new_vert_id) {
                         front_new_verts.push(new_vert_id);
@@ -814,7 +1382,9 @@ impl Mesh {
                 }
                 if from_is_back {
                     let new_vert_id = *back_vert_map.entry(from_vert_id).or_insert_with(|| {
-                        back_mesh.add_vertex(self.vertex(from_vert_id).unwrap().position)
+                        let new_vert_id = back_mesh.add_vertex(self.vertex_position(from_vert_id).unwrap());
+                        back_mesh.set_vertex_source(new_vert_id, self.vertex_source(from_vert_id).unwrap());
+                        new_vert_id
                     });
                     if back_new_vert_set.insert(new_vert_id) {
                         back_new_verts.push(new_vert_id);
@@ -822,8 +1392,8 @@ impl Mesh {
                 }
                 if (from_is_front && to_is_back) || (from_is_back && to_is_front) {
                     let intersect = intersect_map.entry(edge.clone()).or_insert_with(|| {
-                        let p0 = self.vertex(from_vert_id).unwrap().position;
-                        let p1 = self.vertex(to_vert_id).unwrap().position;
+                        let p0 = self.vertex_position(from_vert_id).unwrap();
+                        let p1 = self.vertex_position(to_vert_id).unwrap();
                         intersect_of_segment_and_plane(p0, p1, pt_on_plane, norm)
                     });
                     if let SegmentPlaneIntersect::Intersection(intersect_pt) = *intersect {
@@ -859,7 +1429,9 @@ impl Mesh {
                 }
                 if to_is_front {
                     let new_vert_id = *front_vert_map.entry(to_vert_id).or_insert_with(|| {
-                        front_mesh.add_vertex(self.vertex(to_vert_id).unwrap().position)
+                        let new_vert_id = front_mesh.add_vertex(self.vertex_position(to_vert_id).unwrap());
+                        front_mesh.set_vertex_source(new_vert_id, self.vertex_source(to_vert_id).unwrap());
+                        new_vert_id
                     });
                     if front_new_vert_set.insert(new_vert_id) {
                         front_new_verts.push(new_vert_id);
@@ -867,7 +1439,9 @@ impl Mesh {
                 }
                 if to_is_back {
                     let new_vert_id = *back_vert_map.entry(to_vert_id).or_insert_with(|| {
-                        back_mesh.add_vertex(self.vertex(to_vert_id).unwrap().position)
+                        let new_vert_id = back_mesh.add_vertex(self.vertex_position(to_vert_id).unwrap());
+                        back_mesh.set_vertex_source(new_vert_id, self.vertex_source(to_vert_id).unwrap());
+                        new_vert_id
                     });
                     if back_new_vert_set.insert(new_vert_id) {
                         back_new_verts.push(new_vert_id);
@@ -904,11 +1478,8 @@ impl Mesh {
             (halfedge.face, halfedge.next)
         };
         let new_halfedge_id = self.add_halfedge();
-        {
-            let new_halfedge = self.halfedge_mut(new_halfedge_id).unwrap();
-            new_halfedge.vertex = vertex_id;
-            new_halfedge.face = face_id;
-        }
+        self.set_halfedge_start_vertex_id(new_halfedge_id, vertex_id);
+        self.set_halfedge_face_id(new_halfedge_id, face_id);
         self.link_halfedges(new_halfedge_id, next_halfedge_id);
         self.link_halfedges(halfedge_id, new_halfedge_id);
         new_halfedge_id
@@ -934,22 +1505,18 @@ impl Mesh {
                 if !self.halfedge_opposite_id(long_id).is_none() {
                     continue;
                 }
-                let long_begin = self.halfedge_start_vertex(long_id).unwrap().position;
-                let long_end = self.halfedge_start_vertex(self.halfedge_next_id(long_id).unwrap()).unwrap().position;
+                let long_begin = self.halfedge_start_vertex_position(long_id).unwrap();
+                let long_end = self.halfedge_start_vertex_position(self.halfedge_next_id(long_id).unwrap()).unwrap();
                 
-                let (short_begin_pos, short_begin_vert_id) = {
-                    let vert = self.halfedge_start_vertex(short_id).unwrap();
-                    (vert.position, vert.id)
-                };
+                let short_begin_vert_id = self.halfedge_start_vertex_id(short_id).unwrap();
+                let short_begin_pos = self.vertex_position(short_begin_vert_id).unwrap();
                 if is_point_on_segment(short_begin_pos, long_begin, long_end) {
                     may_broken_halfedges.push(self.split_halfedge(long_id, short_begin_vert_id));
                     continue 'outer;
                 }
 
-                let (short_end_pos, short_end_vert_id) = {
-                    let vert = self.halfedge_start_vertex(self.halfedge_next_id(short_id).unwrap()).unwrap();
-                    (vert.position, vert.id)
-                };
+                let short_end_vert_id = self.halfedge_start_vertex_id(self.halfedge_next_id(short_id).unwrap()).unwrap();
+                let short_end_pos = self.vertex_position(short_end_vert_id).unwrap();
                 if is_point_on_segment(short_end_pos, long_begin, long_end) {
                     may_broken_halfedges.push(self.split_halfedge(long_id, short_end_vert_id));
                     continue 'outer;
@@ -962,9 +1529,9 @@ impl Mesh {
 
     pub fn remove_extra_vertices(&self) -> Self {
         let from_mesh = self;
-        let mut to_mesh = Mesh::new();
+        let mut to_mesh = Mesh::default();
         let mut extra_vertices : HashSet<Id> = HashSet::new();
-        for (_, &halfedge_id) in from_mesh.edges.iter() {
+        for halfedge_id in EdgeIterator::new(from_mesh) {
             let vert_id = from_mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
             let prev_id = from_mesh.halfedge_prev_id(halfedge_id).unwrap();
             let prev_opposite_id = from_mesh.halfedge_opposite_id(prev_id);
@@ -978,6 +1545,7 @@ impl Mesh {
             }
         }
         let mut new_vert_map : HashMap<Id, Id> = HashMap::new();
+        let mut new_face_map : HashMap<Id, Id> = HashMap::new();
         for face_id in FaceIterator::new(from_mesh) {
             let mut added_vertices : Vec<Id> = Vec::new();
             for halfedge_id in FaceHalfedgeIterator::new(from_mesh, from_mesh.face_first_halfedge_id(face_id).unwrap()) {
@@ -986,25 +1554,25 @@ impl Mesh {
                     continue;
                 }
                 let new_vert_id = *new_vert_map.entry(vert_id).or_insert_with(|| {
-                    let new_added_vertex_id = to_mesh.add_vertex(from_mesh.vertex(vert_id).unwrap().position);
-                    to_mesh.vertex_mut(new_added_vertex_id).unwrap().source = from_mesh.vertex(vert_id).unwrap().source;
+                    let new_added_vertex_id = to_mesh.add_vertex(from_mesh.vertex_position(vert_id).unwrap());
+                    to_mesh.set_vertex_source(new_added_vertex_id, from_mesh.vertex_source(vert_id).unwrap());
                     new_added_vertex_id
                 });
                 added_vertices.push(new_vert_id);
             }
-            to_mesh.add_vertices(added_vertices);
+            new_face_map.insert(face_id, to_mesh.add_vertices(added_vertices));
         }
+        remap_selections(from_mesh, &mut to_mesh, &new_vert_map, &new_face_map);
         to_mesh
     }
 
     pub fn combine_coplanar_faces(&self) -> Self {
         let from_mesh = self;
-        let mut to_mesh = Mesh::new();
-        let mut coplanar_edges = HashSet::new();
+        let mut to_mesh = Mesh::default();
         let mut coplanar_faces = HashSet::new();
         let mut coplanar_halfedge_link : HashMap<Id, Id> = HashMap::new();
-        let mut face_norm_map : HashMap<Id, Vector3<f32>> = HashMap::new();
-        for (edge, &halfedge_id) in from_mesh.edges.iter() {
+        let mut face_norm_map : HashMap<Id, Vector3<S>> = HashMap::new();
+        for halfedge_id in EdgeIterator::new(from_mesh) {
             let face_id = from_mesh.halfedge_face_id(halfedge_id).unwrap();
             let face_norm = *face_norm_map.entry(face_id).or_insert_with(|| from_mesh.face_norm(face_id));
             if let Some(opposite_id) = from_mesh.halfedge_opposite_id(halfedge_id) {
@@ -1026,31 +1594,31 @@ impl Mesh {
                     if dir2.is_zero() {
                         dir2 = face_norm;
                     }
-                    if dir1.dot(dir2) < 0.0 {
+                    if dir1.dot(dir2) < S::zero() {
                         continue;
                     }
                     coplanar_halfedge_link.insert(halfedge_id, opposite_id);
                     coplanar_halfedge_link.insert(opposite_id, halfedge_id);
-                    coplanar_edges.insert(edge);
                     coplanar_faces.insert(face_id);
                     coplanar_faces.insert(opposite_face_id);
                 }
             }
         }
         let mut new_vert_map : HashMap<Id, Id> = HashMap::new();
+        let mut new_face_map : HashMap<Id, Id> = HashMap::new();
         for face_id in FaceIterator::new(from_mesh) {
             if !coplanar_faces.contains(&face_id) {
                 let mut added_vertices : Vec<Id> = Vec::new();
                 for halfedge_id in FaceHalfedgeIterator::new(from_mesh, from_mesh.face_first_halfedge_id(face_id).unwrap()) {
                     let vert_id = from_mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
                     let new_vert_id = *new_vert_map.entry(vert_id).or_insert_with(|| {
-                        let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex(vert_id).unwrap().position);
-                        to_mesh.vertex_mut(new_added_vert_id).unwrap().source = from_mesh.vertex(vert_id).unwrap().source;
+                        let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex_position(vert_id).unwrap());
+                        to_mesh.set_vertex_source(new_added_vert_id, from_mesh.vertex_source(vert_id).unwrap());
                         new_added_vert_id
                     });
                     added_vertices.push(new_vert_id);
                 }
-                to_mesh.add_vertices(added_vertices);
+                new_face_map.insert(face_id, to_mesh.add_vertices(added_vertices));
             }
         }
         let mut used_halfedges : HashSet<Id> = HashSet::new();
@@ -1085,29 +1653,34 @@ impl Mesh {
                             used_halfedges.insert(loop_halfedge_id);
                             let vert_id = from_mesh.halfedge_start_vertex_id(loop_halfedge_id).unwrap();
                             let new_vert_id = *new_vert_map.entry(vert_id).or_insert_with(|| {
-                                let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex(vert_id).unwrap().position);
-                                to_mesh.vertex_mut(new_added_vert_id).unwrap().source = from_mesh.vertex(vert_id).unwrap().source;
+                                let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex_position(vert_id).unwrap());
+                                to_mesh.set_vertex_source(new_added_vert_id, from_mesh.vertex_source(vert_id).unwrap());
                                 new_added_vert_id
                             });
                             added_vertices.push(new_vert_id);
                         }
-                        to_mesh.add_vertices(added_vertices);
+                        let new_face_id = to_mesh.add_vertices(added_vertices);
+                        for &loop_halfedge_id in loop_halfedges.iter() {
+                            new_face_map.insert(from_mesh.halfedge_face_id(loop_halfedge_id).unwrap(), new_face_id);
+                        }
                     }
                 }
             }
         }
+        remap_selections(from_mesh, &mut to_mesh, &new_vert_map, &new_face_map);
         to_mesh.remove_extra_vertices().weld()
     }
 
     pub fn combine_adj_faces_round(&self) -> (bool, Self) {
         let from_mesh = self;
-        let mut to_mesh = Mesh::new();
+        let mut to_mesh = Mesh::default();
         let mut ignore_faces = HashSet::new();
         let mut new_vert_map : HashMap<Id, Id> = HashMap::new();
         let mut ignore_vert_ids : HashSet<Id> = HashSet::new();
         let mut pending_old_verts : Vec<Vec<Id>> = Vec::new();
+        let mut pending_old_faces : Vec<Vec<Id>> = Vec::new();
         let mut face_pair_map : HashSet<FacePair> = HashSet::new();
-        for (_, &halfedge_id) in from_mesh.edges.iter() {
+        for halfedge_id in EdgeIterator::new(from_mesh) {
             let face_id = from_mesh.halfedge_face_id(halfedge_id).unwrap();
             if ignore_faces.contains(&face_id) {
                 continue;
@@ -1156,6 +1729,7 @@ impl Mesh {
                     loop_id = from_mesh.halfedge_next_id(loop_id).unwrap();
                 }
                 pending_old_verts.push(old_vertices);
+                pending_old_faces.push(vec![face_id, opposite_face_id]);
             }
         }
         for face_id in FaceIterator::new(from_mesh) {
@@ -1167,28 +1741,34 @@ impl Mesh {
                 old_vertices.push(from_mesh.halfedge_start_vertex_id(halfedge_id).unwrap());
             }
             pending_old_verts.push(old_vertices);
+            pending_old_faces.push(vec![face_id]);
         }
-        for verts in pending_old_verts.iter() {
+        let mut new_face_map : HashMap<Id, Id> = HashMap::new();
+        for (verts, old_faces) in pending_old_verts.iter().zip(pending_old_faces.iter()) {
             let mut added_vertices = Vec::new();
             for old_vert_id in verts.iter() {
                 if !ignore_vert_ids.contains(old_vert_id) {
                     let new_vert_id = new_vert_map.entry(*old_vert_id).or_insert_with(|| {
-                        let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex(*old_vert_id).unwrap().position);
-                        to_mesh.vertex_mut(new_added_vert_id).unwrap().source = from_mesh.vertex(*old_vert_id).unwrap().source;
+                        let new_added_vert_id = to_mesh.add_vertex(from_mesh.vertex_position(*old_vert_id).unwrap());
+                        to_mesh.set_vertex_source(new_added_vert_id, from_mesh.vertex_source(*old_vert_id).unwrap());
                         new_added_vert_id
                     });
                     added_vertices.push(*new_vert_id);
                 }
             }
-            to_mesh.add_vertices(added_vertices);
+            let new_face_id = to_mesh.add_vertices(added_vertices);
+            for &old_face_id in old_faces.iter() {
+                new_face_map.insert(old_face_id, new_face_id);
+            }
         }
+        remap_selections(from_mesh, &mut to_mesh, &new_vert_map, &new_face_map);
         (!ignore_faces.is_empty(), to_mesh)
     }
 
     pub fn combine_adj_faces(&self) -> Self {
         let mut from_mesh = self.clone();
         let mut combined = true;
-        let mut to_mesh = Mesh::new();
+        let mut to_mesh = Mesh::default();
         while combined {
             let (sub_combined, sub_to_mesh) = from_mesh.combine_adj_faces_round();
             combined = sub_combined;
@@ -1199,34 +1779,34 @@ impl Mesh {
     }
 
     pub fn trim(&self, normalize: bool) -> Self {
-        let mut to_mesh = Mesh::new();
+        let mut to_mesh = Mesh::default();
         to_mesh.add_mesh(self);
-        let mut x_low = f32::MAX;
-        let mut x_high = f32::MIN;
-        let mut y_low = f32::MAX;
-        let mut y_high = f32::MIN;
-        let mut z_low = f32::MAX;
-        let mut z_high = f32::MIN;
-        for vert in self.vertices.iter() {
-            if vert.position.x < x_low {
-                x_low = vert.position.x;
-            } else if vert.position.x > x_high {
-                x_high = vert.position.x;
-            }
-            if vert.position.y < y_low {
-                y_low = vert.position.y;
-            } else if vert.position.y > y_high {
-                y_high = vert.position.y;
-            }
-            if vert.position.z < z_low {
-                z_low = vert.position.z;
-            } else if vert.position.z > z_high {
-                z_high = vert.position.z;
-            }
-        }
-        let x_middle = (x_high + x_low) / 2.0;
-        let y_middle = (y_high + y_low) / 2.0;
-        let z_middle = (z_high + z_low) / 2.0;
+        let mut x_low = S::max_value();
+        let mut x_high = S::min_value();
+        let mut y_low = S::max_value();
+        let mut y_high = S::min_value();
+        let mut z_low = S::max_value();
+        let mut z_high = S::min_value();
+        for position in self.vertices.position.iter() {
+            if position.x < x_low {
+                x_low = position.x;
+            } else if position.x > x_high {
+                x_high = position.x;
+            }
+            if position.y < y_low {
+                y_low = position.y;
+            } else if position.y > y_high {
+                y_high = position.y;
+            }
+            if position.z < z_low {
+                z_low = position.z;
+            } else if position.z > z_high {
+                z_high = position.z;
+            }
+        }
+        let x_middle = (x_high + x_low) / scalar(2.0);
+        let y_middle = (y_high + y_low) / scalar(2.0);
+        let z_middle = (z_high + z_low) / scalar(2.0);
         if normalize {
             let x_size = x_high - x_low;
             let y_size = y_high - y_low;
@@ -1238,16 +1818,16 @@ impl Mesh {
             if z_size > long_size {
                 long_size = z_size;
             }
-            for vert in to_mesh.vertices.iter_mut() {
-                vert.position.x = (vert.position.x - x_middle) / long_size;
-                vert.position.y = (vert.position.y - y_middle) / long_size;
-                vert.position.z = (vert.position.z - z_middle) / long_size;
+            for position in to_mesh.vertices.position.iter_mut() {
+                position.x = (position.x - x_middle) / long_size;
+                position.y = (position.y - y_middle) / long_size;
+                position.z = (position.z - z_middle) / long_size;
             }
         } else {
-            for vert in to_mesh.vertices.iter_mut() {
-                vert.position.x -= x_middle;
-                vert.position.y -= y_middle;
-                vert.position.z -= z_middle;
+            for position in to_mesh.vertices.position.iter_mut() {
+                position.x -= x_middle;
+                position.y -= y_middle;
+                position.z -= z_middle;
             }
         }
         to_mesh
@@ -1288,6 +1868,41 @@ impl Mesh {
         verts.len() as isize + faces.len() as isize - edges.len() as isize == 2
     }
 
+    /// Collects loops of halfedges without opposites, each loop runs in the
+    /// direction of its faces.
+    pub fn boundary_loops(&self) -> Vec<Vec<Id>> {
+        let mut loops = Vec::new();
+        let mut visited : HashSet<Id> = HashSet::new();
+        for face_id in FaceIterator::new(self) {
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                if self.halfedge_opposite_id(halfedge_id).is_some() || visited.contains(&halfedge_id) {
+                    continue;
+                }
+                let boundary = BoundaryLoopIterator::new(self, halfedge_id).into_vec();
+                visited.extend(boundary.iter().cloned());
+                let closed = boundary.last()
+                    .and_then(|&last_id| self.next_boundary_halfedge(last_id)) == Some(halfedge_id);
+                if closed {
+                    loops.push(boundary);
+                }
+            }
+        }
+        loops
+    }
+
+    /// The boundary halfedge following the given one, found by rotating
+    /// around the end vertex of the given halfedge.
+    pub fn next_boundary_halfedge(&self, halfedge_id: Id) -> Option<Id> {
+        let mut next_id = self.halfedge_next_id(halfedge_id)?;
+        for _ in 0..self.halfedges.len() {
+            match self.halfedge_opposite_id(next_id) {
+                Some(opposite_id) => next_id = self.halfedge_next_id(opposite_id)?,
+                None => return Some(next_id),
+            }
+        }
+        None
+    }
+
     pub fn broken_face_set(&self) -> HashSet<Id> {
         let mut broken_face_set = HashSet::new();
         let mut endpoints : HashMap<EdgeEndpoints, Vec<Id>> = HashMap::new();
@@ -1322,26 +1937,87 @@ impl Mesh {
         broken_face_set
     }
 
-    pub fn mirror_in_x(&self, center_x: f32) -> Self {
-        let mut new_mesh = Mesh::new();
+    pub fn mirror_in_x(&self, center_x: S) -> Self {
+        let mut new_mesh = Mesh::default();
+        new_mesh.add_mesh(self);
+        for position in new_mesh.vertices.position.iter_mut() {
+            position.x = center_x - position.x;
+        }
+        new_mesh.flip_mesh()
+    }
+
+    pub fn mirror_in_z(&self, center_z: S) -> Self {
+        let mut new_mesh = Mesh::default();
         new_mesh.add_mesh(self);
-        for vert in new_mesh.vertices.iter_mut() {
-            vert.position.x = center_x - vert.position.x;
+        for position in new_mesh.vertices.position.iter_mut() {
+            position.z = center_z - position.z;
         }
         new_mesh.flip_mesh()
     }
 
-    pub fn mirror_in_z(&self, center_z: f32) -> Self {
-        let mut new_mesh = Mesh::new();
+    /// Reflects the mesh across the plane, the faces are reversed so the
+    /// normals still point outward.
+    pub fn mirror(&self, plane_point: Point3<S>, plane_normal: Vector3<S>) -> Self {
+        let norm = plane_normal.normalize();
+        let mut new_mesh = Mesh::default();
         new_mesh.add_mesh(self);
-        for vert in new_mesh.vertices.iter_mut() {
-            vert.position.z = center_z - vert.position.z;
+        for position in new_mesh.vertices.position.iter_mut() {
+            let distance = (*position - plane_point).dot(norm);
+            *position -= norm * (scalar::<S>(2.0) * distance);
         }
         new_mesh.flip_mesh()
     }
 
+    /// Keeps the part of the mesh in front of the plane and joins it with its
+    /// reflection. Vertices closer to the plane than SYMMETRIZE_SEAM_DISTANCE
+    /// are moved onto the plane and shared by both halves.
+    pub fn symmetrize(&self, plane_point: Point3<S>, plane_normal: Vector3<S>) -> Self {
+        let norm = plane_normal.normalize();
+        let (front_mesh, _) = self.split_mesh_by_plane(plane_point, norm, false);
+        let mut new_mesh = Mesh::default();
+        new_mesh.add_mesh(&front_mesh);
+        let mut seam_vertices : HashSet<Id> = HashSet::new();
+        for (index, position) in new_mesh.vertices.position.iter_mut().enumerate() {
+            let distance = (*position - plane_point).dot(norm);
+            if distance.abs() <= scalar(SYMMETRIZE_SEAM_DISTANCE) {
+                *position -= norm * distance;
+                seam_vertices.insert(index + 1);
+            }
+        }
+        let mut mirror_vert_map : HashMap<Id, Id> = HashMap::new();
+        for face_id in FaceIterator::new(&new_mesh).into_vec() {
+            let mut verts = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(&new_mesh, new_mesh.face_first_halfedge_id(face_id).unwrap()) {
+                verts.push(new_mesh.halfedge_start_vertex_id(halfedge_id).unwrap());
+            }
+            let mut added_halfedges : Vec<(Id, Id)> = Vec::new();
+            for &vert_id in verts.iter().rev() {
+                let mirror_vert_id = if seam_vertices.contains(&vert_id) {
+                    vert_id
+                } else {
+                    match mirror_vert_map.get(&vert_id) {
+                        Some(&mirror_vert_id) => mirror_vert_id,
+                        None => {
+                            let vert_position = new_mesh.vertex_position(vert_id).unwrap();
+                            let source = new_mesh.vertex_source(vert_id).unwrap();
+                            let distance = (vert_position - plane_point).dot(norm);
+                            let position = vert_position - norm * (scalar::<S>(2.0) * distance);
+                            let mirror_vert_id = new_mesh.add_vertex(position);
+                            new_mesh.set_vertex_source(mirror_vert_id, source);
+                            mirror_vert_map.insert(vert_id, mirror_vert_id);
+                            mirror_vert_id
+                        }
+                    }
+                };
+                added_halfedges.push((new_mesh.add_halfedge(), mirror_vert_id));
+            }
+            new_mesh.add_halfedges_and_vertices(&added_halfedges);
+        }
+        new_mesh
+    }
+
     pub fn fix_hole(&self) -> Self {
-        let mut new_mesh = Mesh::new();
+        let mut new_mesh = Mesh::default();
         let mut border_map : HashMap<Id, Id> = HashMap::new();
         new_mesh.add_mesh(self);
         {
@@ -1364,77 +2040,290 @@ impl Mesh {
         new_mesh
     }
 
-    pub fn smooth(&mut self, factor: f32, limit_vertices: Option<&HashSet<usize>>) {
-        let mut neighbor_position_sum_map : HashMap<Id, (Point3<f32>, usize)> = HashMap::new();
-        let mut face_norm_map : HashMap<Id, Vector3<f32>> = HashMap::new();
+    /// Collects the weighted neighbors of every vertex which is allowed to move.
+    /// With cotangent weighting, an edge is weighted by the cotangent of the
+    /// corner opposite to it in each adjacent face; for polygons the corner
+    /// following the edge is used. Negative cotangents are clamped to zero.
+    fn smooth_neighbor_weights(&self, cotangent: bool, limit_vertices: Option<&HashSet<usize>>) -> HashMap<Id, Vec<(Id, S)>> {
+        let mut neighbor_weights_map : HashMap<Id, Vec<(Id, S)>> = HashMap::new();
+        let is_movable = |vert_id: Id| limit_vertices.is_none() || limit_vertices.unwrap().contains(&vert_id);
         for face_id in FaceIterator::new(self) {
             for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
-                let from_vert = self.halfedge_start_vertex(halfedge_id);
-                if from_vert.is_some() {
-                    let next_halfedge_id = self.halfedge_next_id(halfedge_id);
-                    if next_halfedge_id.is_some() {
-                        let to_vert = self.halfedge_start_vertex(next_halfedge_id.unwrap());
-                        if to_vert.is_some() {
-                            if limit_vertices.is_none() || limit_vertices.unwrap().contains(&to_vert.unwrap().id) {
-                                let item = &mut neighbor_position_sum_map.entry(to_vert.unwrap().id).or_insert((Point3 {x:0.0, y:0.0, z:0.0}, 0));
-                                item.0 += from_vert.unwrap().position.to_vec();
-                                item.1 += 1;
-                                face_norm_map.entry(face_id).or_insert_with(|| self.face_norm(face_id));
-                            }
-                        }
+                let next_halfedge_id = self.halfedge_next_id(halfedge_id);
+                if next_halfedge_id.is_none() {
+                    continue;
+                }
+                let from_id = self.halfedge_start_vertex_id(halfedge_id).unwrap_or(0);
+                let to_id = self.halfedge_start_vertex_id(next_halfedge_id.unwrap()).unwrap_or(0);
+                let from_position = self.vertex_position(from_id);
+                let to_position = self.vertex_position(to_id);
+                if from_position.is_none() || to_position.is_none() {
+                    continue;
+                }
+                if !cotangent {
+                    if is_movable(to_id) {
+                        neighbor_weights_map.entry(to_id).or_default().push((from_id, S::one()));
                     }
+                    continue;
+                }
+                let opposite_position = self.halfedge_next_id(next_halfedge_id.unwrap())
+                    .and_then(|id| self.halfedge_start_vertex_position(id));
+                if opposite_position.is_none() {
+                    continue;
+                }
+                let opposite_position = opposite_position.unwrap();
+                let a = from_position.unwrap() - opposite_position;
+                let b = to_position.unwrap() - opposite_position;
+                let sin = a.cross(b).magnitude();
+                let weight = if sin > scalar(SMALL_NUM) {
+                    (a.dot(b) / sin).max(S::zero())
+                } else {
+                    S::zero()
+                };
+                if is_movable(to_id) {
+                    neighbor_weights_map.entry(to_id).or_default().push((from_id, weight));
+                }
+                if is_movable(from_id) {
+                    neighbor_weights_map.entry(from_id).or_default().push((to_id, weight));
                 }
             }
         }
-        let self_factor = 1.0 - factor;
-        let mut old_position_map: HashMap<Id, Point3<f32>> = HashMap::new();
-        for vert in self.vertices.iter_mut() {
-            match neighbor_position_sum_map.get(&vert.id) {
-                Some(&sum_and_count) => {
-                    if sum_and_count.1 > 0 {
-                        let old_position = vert.position;
-                        old_position_map.entry(vert.id).or_insert(old_position);
-                        vert.position = (sum_and_count.0 / sum_and_count.1 as f32) * factor + old_position.to_vec() * self_factor;
-                    }
-                },
-                _ => {}
+        neighbor_weights_map
+    }
+
+    /// Weighted average of the neighbor positions, falls back to the plain
+    /// average when all the weights vanish.
+    fn smooth_neighbor_average(&self, neighbors: &[(Id, S)]) -> Option<Point3<S>> {
+        if neighbors.is_empty() {
+            return None;
+        }
+        let mut position_sum = Vector3::zero();
+        let mut weight_sum = S::zero();
+        for &(neighbor_id, weight) in neighbors {
+            position_sum += self.vertex_position(neighbor_id).unwrap().to_vec() * weight;
+            weight_sum += weight;
+        }
+        if weight_sum > scalar(SMALL_NUM) {
+            return Some(Point3::from_vec(position_sum / weight_sum));
+        }
+        let mut position_sum = Vector3::zero();
+        for &(neighbor_id, _) in neighbors {
+            position_sum += self.vertex_position(neighbor_id).unwrap().to_vec();
+        }
+        Some(Point3::from_vec(position_sum / S::from(neighbors.len()).unwrap()))
+    }
+
+    /// Normals of the faces around the vertices which are going to move.
+    fn smooth_face_norms(&self, neighbor_weights_map: &HashMap<Id, Vec<(Id, S)>>) -> HashMap<Id, Vector3<S>> {
+        let mut face_norm_map : HashMap<Id, Vector3<S>> = HashMap::new();
+        for face_id in FaceIterator::new(self) {
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                let vert_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                if neighbor_weights_map.contains_key(&vert_id) {
+                    face_norm_map.insert(face_id, self.face_norm(face_id));
+                    break;
+                }
             }
         }
-        let mut change_back_pairs : Vec<(Id, Point3<f32>)> = Vec::new();
-        for (face_id, face_normal) in face_norm_map {
-            if face_normal.dot(self.face_norm(face_id)) <= 0.0 {
+        face_norm_map
+    }
+
+    /// Moves the vertices of flipped faces back to where they were before smoothing.
+    fn revert_flipped_faces(&mut self, face_norm_map: &HashMap<Id, Vector3<S>>, old_position_map: &HashMap<Id, Point3<S>>) {
+        let mut change_back_pairs : Vec<(Id, Point3<S>)> = Vec::new();
+        for (&face_id, face_normal) in face_norm_map {
+            if face_normal.dot(self.face_norm(face_id)) <= S::zero() {
                 for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
-                    let from_vert = self.halfedge_start_vertex(halfedge_id);
-                    if from_vert.is_some() {
-                        match old_position_map.get(&from_vert.unwrap().id) {
-                            Some(&old_position) => {
-                                change_back_pairs.push((from_vert.unwrap().id, old_position));
-                            },
-                            _ => {}
+                    if let Some(from_vert_id) = self.halfedge_start_vertex_id(halfedge_id) {
+                        if let Some(&old_position) = old_position_map.get(&from_vert_id) {
+                            change_back_pairs.push((from_vert_id, old_position));
                         }
                     }
                 }
             }
         }
         for (vert_id, old_position) in change_back_pairs {
-            self.vertex_mut(vert_id).unwrap().position = old_position;
+            self.set_vertex_position(vert_id, old_position);
+        }
+    }
+
+    fn current_positions(&self, neighbor_weights_map: &HashMap<Id, Vec<(Id, S)>>) -> HashMap<Id, Point3<S>> {
+        neighbor_weights_map.keys().map(|&vert_id| (vert_id, self.vertex_position(vert_id).unwrap())).collect()
+    }
+
+    /// One Laplacian step, moves every vertex by factor towards the weighted
+    /// average of its neighbors. A negative factor moves it away.
+    fn laplacian_step(&mut self, factor: S, cotangent: bool, limit_vertices: Option<&HashSet<usize>>) {
+        let neighbor_weights_map = self.smooth_neighbor_weights(cotangent, limit_vertices);
+        let mut new_positions : Vec<(Id, Point3<S>)> = Vec::new();
+        for (&vert_id, neighbors) in neighbor_weights_map.iter() {
+            if let Some(average) = self.smooth_neighbor_average(neighbors) {
+                let old_position = self.vertex_position(vert_id).unwrap();
+                new_positions.push((vert_id, old_position + (average - old_position) * factor));
+            }
+        }
+        for (vert_id, position) in new_positions {
+            self.set_vertex_position(vert_id, position);
+        }
+    }
+
+    pub fn smooth(&mut self, factor: S, limit_vertices: Option<&HashSet<usize>>) {
+        let neighbor_weights_map = self.smooth_neighbor_weights(false, limit_vertices);
+        let face_norm_map = self.smooth_face_norms(&neighbor_weights_map);
+        let old_position_map = self.current_positions(&neighbor_weights_map);
+        self.laplacian_step(factor, false, limit_vertices);
+        self.revert_flipped_faces(&face_norm_map, &old_position_map);
+    }
+
+    /// Same as smooth, but neighbors are weighted by cotangents, so the
+    /// vertices slide less along the surface on irregular meshes.
+    pub fn smooth_cotangent(&mut self, factor: S, limit_vertices: Option<&HashSet<usize>>) {
+        let neighbor_weights_map = self.smooth_neighbor_weights(true, limit_vertices);
+        let face_norm_map = self.smooth_face_norms(&neighbor_weights_map);
+        let old_position_map = self.current_positions(&neighbor_weights_map);
+        self.laplacian_step(factor, true, limit_vertices);
+        self.revert_flipped_faces(&face_norm_map, &old_position_map);
+    }
+
+    /// Taubin smoothing, every iteration shrinks by lambda and inflates by mu
+    /// (negative, with a slightly larger magnitude than lambda, e.g. 0.5 and
+    /// -0.53), which keeps the volume.
+    pub fn smooth_taubin(&mut self, lambda: S, mu: S, iterations: usize, limit_vertices: Option<&HashSet<usize>>) {
+        let neighbor_weights_map = self.smooth_neighbor_weights(false, limit_vertices);
+        let face_norm_map = self.smooth_face_norms(&neighbor_weights_map);
+        let old_position_map = self.current_positions(&neighbor_weights_map);
+        for _ in 0..iterations {
+            self.laplacian_step(lambda, false, limit_vertices);
+            self.laplacian_step(mu, false, limit_vertices);
+        }
+        self.revert_flipped_faces(&face_norm_map, &old_position_map);
+    }
+
+    /// HC smoothing as described in Improved Laplacian Smoothing of Noisy
+    /// Surface Meshes, Vollmer et al. 1999. Alpha pulls the vertices towards
+    /// their original positions, beta weights the correction of a vertex
+    /// against the corrections of its neighbors (typically 0.0 and 0.5).
+    pub fn smooth_hc(&mut self, alpha: S, beta: S, iterations: usize, limit_vertices: Option<&HashSet<usize>>) {
+        let neighbor_weights_map = self.smooth_neighbor_weights(false, limit_vertices);
+        let face_norm_map = self.smooth_face_norms(&neighbor_weights_map);
+        let old_position_map = self.current_positions(&neighbor_weights_map);
+        for _ in 0..iterations {
+            let previous_position_map = self.current_positions(&neighbor_weights_map);
+            let mut difference_map : HashMap<Id, Vector3<S>> = HashMap::new();
+            let mut average_map : HashMap<Id, Point3<S>> = HashMap::new();
+            for (&vert_id, neighbors) in neighbor_weights_map.iter() {
+                if let Some(average) = self.smooth_neighbor_average(neighbors) {
+                    let original = old_position_map[&vert_id].to_vec();
+                    let previous = previous_position_map[&vert_id].to_vec();
+                    difference_map.insert(vert_id, average.to_vec() - (original * alpha + previous * (S::one() - alpha)));
+                    average_map.insert(vert_id, average);
+                }
+            }
+            // All the averages are taken from the previous positions, so none
+            // of them is moved before the others are computed.
+            for (&vert_id, &average) in average_map.iter() {
+                self.set_vertex_position(vert_id, average);
+            }
+            for (&vert_id, neighbors) in neighbor_weights_map.iter() {
+                let difference = match difference_map.get(&vert_id) {
+                    Some(&difference) => difference,
+                    None => continue,
+                };
+                let mut neighbor_difference_sum = Vector3::zero();
+                for &(neighbor_id, _) in neighbors {
+                    if let Some(&neighbor_difference) = difference_map.get(&neighbor_id) {
+                        neighbor_difference_sum += neighbor_difference;
+                    }
+                }
+                let neighbor_difference = neighbor_difference_sum / S::from(neighbors.len()).unwrap();
+                let position = self.vertex_position(vert_id).unwrap();
+                self.set_vertex_position(vert_id, position - (difference * beta + neighbor_difference * (S::one() - beta)));
+            }
+        }
+        self.revert_flipped_faces(&face_norm_map, &old_position_map);
+    }
+
+    /// Feature preserving denoising, see Bilateral Normal Filtering for Mesh
+    /// Denoising, Zheng et al. 2011. Face normals are filtered by weighting the
+    /// normals of the faces sharing a vertex with a gaussian of the center
+    /// distance (spatial_sigma, in the order of the average edge length) and of
+    /// the normal difference (normal_sigma, typically 0.2 to 0.6), then the
+    /// vertices are moved to fit the filtered normals.
+    pub fn denoise(&mut self, spatial_sigma: S, normal_sigma: S, iterations: usize) {
+        let face_ids = FaceIterator::new(self).into_vec();
+        let mut vertex_faces_map : HashMap<Id, Vec<Id>> = HashMap::new();
+        for &face_id in face_ids.iter() {
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                vertex_faces_map.entry(self.halfedge_start_vertex_id(halfedge_id).unwrap()).or_default().push(face_id);
+            }
+        }
+        let mut face_neighbors_map : HashMap<Id, Vec<Id>> = HashMap::new();
+        for &face_id in face_ids.iter() {
+            let mut neighbors = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                for &other_face_id in vertex_faces_map[&self.halfedge_start_vertex_id(halfedge_id).unwrap()].iter() {
+                    if other_face_id != face_id && !neighbors.contains(&other_face_id) {
+                        neighbors.push(other_face_id);
+                    }
+                }
+            }
+            face_neighbors_map.insert(face_id, neighbors);
+        }
+        let face_norm_map : HashMap<Id, Vector3<S>> = face_ids.iter().map(|&face_id| (face_id, self.face_norm(face_id))).collect();
+        let old_position_map : HashMap<Id, Point3<S>> = vertex_faces_map.keys().map(|&vert_id| (vert_id, self.vertex_position(vert_id).unwrap())).collect();
+        let face_center_map : HashMap<Id, Point3<S>> = face_ids.iter().map(|&face_id| (face_id, self.face_center(face_id))).collect();
+        let face_area_map : HashMap<Id, S> = face_ids.iter().map(|&face_id| (face_id, self.face_area(face_id))).collect();
+        let spatial_factor = S::one() / (scalar::<S>(2.0) * spatial_sigma * spatial_sigma).max(scalar(SMALL_NUM));
+        let normal_factor = S::one() / (scalar::<S>(2.0) * normal_sigma * normal_sigma).max(scalar(SMALL_NUM));
+        let mut filtered_norm_map = face_norm_map.clone();
+        for _ in 0..iterations {
+            let mut next_norm_map : HashMap<Id, Vector3<S>> = HashMap::new();
+            for &face_id in face_ids.iter() {
+                let center = face_center_map[&face_id];
+                let normal = filtered_norm_map[&face_id];
+                let mut sum = normal * face_area_map[&face_id];
+                for &other_face_id in face_neighbors_map[&face_id].iter() {
+                    let other_normal = filtered_norm_map[&other_face_id];
+                    let spatial_weight = (-center.distance2(face_center_map[&other_face_id]) * spatial_factor).exp();
+                    let normal_weight = (-(normal - other_normal).magnitude2() * normal_factor).exp();
+                    sum += other_normal * (face_area_map[&other_face_id] * spatial_weight * normal_weight);
+                }
+                next_norm_map.insert(face_id, if sum.magnitude2() > S::zero() { sum.normalize() } else { normal });
+            }
+            filtered_norm_map = next_norm_map;
+        }
+        for _ in 0..DENOISE_VERTEX_ITERATIONS {
+            let mut new_positions : Vec<(Id, Point3<S>)> = Vec::new();
+            for (&vert_id, faces) in vertex_faces_map.iter() {
+                let position = self.vertex_position(vert_id).unwrap();
+                let mut offset = Vector3::zero();
+                for &face_id in faces.iter() {
+                    let normal = filtered_norm_map[&face_id];
+                    offset += normal * normal.dot(self.face_center(face_id) - position);
+                }
+                new_positions.push((vert_id, position + offset / S::from(faces.len()).unwrap()));
+            }
+            for (vert_id, position) in new_positions {
+                self.set_vertex_position(vert_id, position);
+            }
         }
+        self.revert_flipped_faces(&face_norm_map, &old_position_map);
     }
 }
 
-impl Add for Mesh {
-    type Output = Mesh;
+impl<S: BaseFloat> Add for Mesh<S> {
+    type Output = Mesh<S>;
 
-    fn add(self, other: Mesh) -> Mesh {
-        let mut new_mesh = Mesh::new();
+    fn add(self, other: Mesh<S>) -> Mesh<S> {
+        let mut new_mesh = Mesh::default();
         new_mesh.add_mesh(&self);
         new_mesh.add_mesh(&other);
         new_mesh
     }
 }
 
-impl AddAssign for Mesh {
-    fn add_assign(&mut self, other: Mesh) {
+impl<S: BaseFloat> AddAssign for Mesh<S> {
+    fn add_assign(&mut self, other: Mesh<S>) {
         self.add_mesh(&other);
     }
 }
@@ -1447,9 +2336,9 @@ pub trait Import {
     fn import(&mut self, filename: &str) -> io::Result<()>;
 }
 
-impl Clone for Mesh {
+impl<S: BaseFloat> Clone for Mesh<S> {
     fn clone(&self) -> Self {
-        let mut mesh = Mesh::new();
+        let mut mesh = Mesh::default();
         mesh.add_mesh(self);
         mesh
     }
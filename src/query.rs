@@ -0,0 +1,203 @@
+use bvh::Aabb;
+use bvh::Bvh;
+use cgmath::Point3;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use mesh::Id;
+use mesh::Mesh;
+use std::f32;
+use util::*;
+
+/// Nodes farther away than this factor times their radius are treated as a
+/// single dipole when accumulating the winding number.
+const WINDING_NUMBER_ACCURACY: f32 = 2.0;
+
+struct QueryTriangle {
+    face: Id,
+    corners: [Point3<f32>; 3],
+}
+
+/// Per BVH node data for the fast winding number approximation.
+struct WindingNode {
+    /// Sum of the area weighted normals, the length is the area.
+    area_normal: Vector3<f32>,
+
+    /// Area weighted center of the triangles in the node.
+    center: Point3<f32>,
+
+    /// Distance from the center to the farthest triangle corner.
+    radius: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClosestPoint {
+    pub position: Point3<f32>,
+    pub face: Id,
+    pub distance: f32,
+}
+
+/// Spatial queries against a snapshot of a mesh. Faces are fan triangulated,
+/// so the query should be rebuilt after the mesh has been edited.
+pub struct MeshQuery {
+    triangles: Vec<QueryTriangle>,
+    triangle_bounds: Vec<Aabb>,
+    bvh: Bvh,
+    winding_nodes: Vec<WindingNode>,
+}
+
+impl MeshQuery {
+    pub fn new(mesh: &Mesh) -> Self {
+        let mut triangles = Vec::new();
+        for face_id in FaceIterator::new(mesh) {
+            let mut points = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+                points.push(mesh.halfedge_start_vertex_position(halfedge_id).unwrap());
+            }
+            for i in 1..points.len().saturating_sub(1) {
+                triangles.push(QueryTriangle {
+                    face: face_id,
+                    corners: [points[0], points[i], points[i + 1]],
+                });
+            }
+        }
+        let triangle_bounds : Vec<Aabb> = triangles.iter().map(|t| Aabb::from_points(&t.corners)).collect();
+        let bvh = Bvh::new(&triangle_bounds);
+        let mut query = MeshQuery {
+            triangles: triangles,
+            triangle_bounds: triangle_bounds,
+            bvh: bvh,
+            winding_nodes: Vec::new(),
+        };
+        query.build_winding_nodes();
+        query
+    }
+
+    fn build_winding_nodes(&mut self) {
+        let mut winding_nodes : Vec<Option<WindingNode>> = Vec::new();
+        for _ in 0..self.bvh.nodes.len() {
+            winding_nodes.push(None);
+        }
+        // Children always have a larger index than their parent
+        for node_index in (0..self.bvh.nodes.len()).rev() {
+            let node = &self.bvh.nodes[node_index];
+            let winding_node = if node.is_leaf() {
+                let mut area_normal = Vector3::zero();
+                let mut weighted_center = Vector3::zero();
+                let mut total_area = 0.0;
+                let mut corners = Vec::new();
+                for &item in self.bvh.items[node.first..node.first + node.count].iter() {
+                    let [a, b, c] = self.triangles[item].corners;
+                    let triangle_area_normal = (b - a).cross(c - a) * 0.5;
+                    let area = triangle_area_normal.magnitude();
+                    area_normal += triangle_area_normal;
+                    weighted_center += Point3::centroid(&[a, b, c]).to_vec() * area;
+                    total_area += area;
+                    corners.extend_from_slice(&[a, b, c]);
+                }
+                let center = if total_area > 0.0 {
+                    Point3::from_vec(weighted_center / total_area)
+                } else {
+                    Point3::centroid(&corners)
+                };
+                let radius = corners.iter().fold(0.0, |r: f32, &p| r.max(p.distance(center)));
+                WindingNode {area_normal, center, radius}
+            } else {
+                let left = winding_nodes[node.left].as_ref().unwrap();
+                let right = winding_nodes[node.right].as_ref().unwrap();
+                let left_weight = left.area_normal.magnitude();
+                let right_weight = right.area_normal.magnitude();
+                let center = if left_weight + right_weight > 0.0 {
+                    Point3::from_vec((left.center.to_vec() * left_weight + right.center.to_vec() * right_weight) / (left_weight + right_weight))
+                } else {
+                    Point3::midpoint(left.center, right.center)
+                };
+                let radius = (left.radius + left.center.distance(center)).max(right.radius + right.center.distance(center));
+                WindingNode {
+                    area_normal: left.area_normal + right.area_normal,
+                    center: center,
+                    radius: radius,
+                }
+            };
+            winding_nodes[node_index] = Some(winding_node);
+        }
+        self.winding_nodes = winding_nodes.into_iter().map(|n| n.unwrap()).collect();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    pub fn closest_point(&self, point: Point3<f32>) -> Option<ClosestPoint> {
+        let triangles = &self.triangles;
+        let (item, distance2) = self.bvh.nearest(point, |item| {
+            let [a, b, c] = triangles[item].corners;
+            closest_point_on_triangle(point, a, b, c).distance2(point)
+        })?;
+        let [a, b, c] = triangles[item].corners;
+        Some(ClosestPoint {
+            position: closest_point_on_triangle(point, a, b, c),
+            face: triangles[item].face,
+            distance: distance2.sqrt(),
+        })
+    }
+
+    /// Generalized winding number, roughly 1 inside and 0 outside of a closed
+    /// and outward facing mesh, and still a usable estimation when the mesh has
+    /// small holes.
+    // Far away clusters are approximated by dipoles as described in
+    // Fast Winding Numbers for Soups and Clouds, Barill et al. 2018
+    pub fn winding_number(&self, point: Point3<f32>) -> f32 {
+        if self.bvh.nodes.is_empty() {
+            return 0.0;
+        }
+        let mut solid_angle = 0.0;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.bvh.nodes[node_index];
+            let winding_node = &self.winding_nodes[node_index];
+            let ray = winding_node.center - point;
+            let distance = ray.magnitude();
+            if distance > winding_node.radius * WINDING_NUMBER_ACCURACY {
+                solid_angle += winding_node.area_normal.dot(ray) / (distance * distance * distance);
+                continue;
+            }
+            if node.is_leaf() {
+                for &item in self.bvh.items[node.first..node.first + node.count].iter() {
+                    let [a, b, c] = self.triangles[item].corners;
+                    solid_angle += triangle_solid_angle(point, a, b, c);
+                }
+            } else {
+                stack.push(node.left);
+                stack.push(node.right);
+            }
+        }
+        solid_angle / (4.0 * f32::consts::PI)
+    }
+
+    pub fn is_inside(&self, point: Point3<f32>) -> bool {
+        self.winding_number(point) > 0.5
+    }
+
+    /// Distance to the surface, negative inside.
+    pub fn signed_distance(&self, point: Point3<f32>) -> Option<f32> {
+        let closest = self.closest_point(point)?;
+        if self.is_inside(point) {
+            Some(-closest.distance)
+        } else {
+            Some(closest.distance)
+        }
+    }
+
+    /// Faces with at least one triangle overlapping the box, useful for
+    /// collecting candidates before an exact test.
+    pub fn faces_in_bounds(&self, bounds: &Aabb) -> Vec<Id> {
+        let mut items = Vec::new();
+        self.bvh.query_aabb(&self.triangle_bounds, bounds, &mut items);
+        let mut faces : Vec<Id> = items.iter().map(|&item| self.triangles[item].face).collect();
+        faces.sort();
+        faces.dedup();
+        faces
+    }
+}
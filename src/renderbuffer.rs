@@ -0,0 +1,240 @@
+use cgmath::BaseFloat;
+use cgmath::Deg;
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::prelude::*;
+use fnv::FnvHashMap;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use util::scalar;
+use vertexcache::optimize_overdraw;
+use vertexcache::optimize_vertex_cache;
+use vertexcache::optimize_vertex_fetch;
+
+/// Number of floats taken by each attribute in an interleaved vertex.
+const POSITION_SIZE: usize = 3;
+const NORMAL_SIZE: usize = 3;
+const UV_SIZE: usize = 2;
+const COLOR_SIZE: usize = 4;
+const SOURCE_SIZE: usize = 1;
+
+/// Vertex cache misses optimize may add in exchange for less overdraw.
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+/// The attributes of each interleaved vertex. Position and normal always
+/// come first, followed by the enabled ones of uv, color and source in that
+/// order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VertexLayout {
+    pub uv: bool,
+    pub color: bool,
+    pub source: bool,
+}
+
+impl VertexLayout {
+    /// Number of floats per vertex.
+    pub fn stride(&self) -> usize {
+        let mut stride = POSITION_SIZE + NORMAL_SIZE;
+        if self.uv {
+            stride += UV_SIZE;
+        }
+        if self.color {
+            stride += COLOR_SIZE;
+        }
+        if self.source {
+            stride += SOURCE_SIZE;
+        }
+        stride
+    }
+
+    pub fn uv_offset(&self) -> Option<usize> {
+        if self.uv {
+            Some(POSITION_SIZE + NORMAL_SIZE)
+        } else {
+            None
+        }
+    }
+
+    pub fn color_offset(&self) -> Option<usize> {
+        if self.color {
+            Some(POSITION_SIZE + NORMAL_SIZE + if self.uv { UV_SIZE } else { 0 })
+        } else {
+            None
+        }
+    }
+
+    pub fn source_offset(&self) -> Option<usize> {
+        if self.source {
+            Some(self.stride() - SOURCE_SIZE)
+        } else {
+            None
+        }
+    }
+}
+
+/// Interleaved vertices and triangle list indices, ready to be uploaded to
+/// the GPU as they are.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RenderBuffer {
+    pub layout: VertexLayout,
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+impl RenderBuffer {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.stride()
+    }
+
+    /// The indices narrowed to u16, or None when there are too many vertices
+    /// to address them all.
+    pub fn indices_u16(&self) -> Option<Vec<u16>> {
+        if self.vertex_count() > u16::MAX as usize + 1 {
+            return None;
+        }
+        Some(self.indices.iter().map(|&index| index as u16).collect())
+    }
+
+    /// Reorders the triangles for the vertex cache and then for less
+    /// overdraw, then the vertices for linear fetching. The rendered result
+    /// stays the same.
+    pub fn optimize(&mut self) {
+        let vertex_count = self.vertex_count();
+        self.indices = optimize_vertex_cache(&self.indices, vertex_count);
+        self.indices = optimize_overdraw(&self.indices, &self.vertices, self.layout.stride(), OVERDRAW_THRESHOLD);
+        self.vertices = optimize_vertex_fetch(&self.vertices, self.layout.stride(), &mut self.indices);
+    }
+}
+
+/// Builds a render buffer from a mesh. Every face corner becomes a vertex and
+/// corners with exactly the same attributes share it, so a mesh vertex is
+/// split wherever the normals, uvs or colors of its corners differ. Faces
+/// are split into triangle fans, triangulate concave faces beforehand.
+///
+/// Corner attributes are keyed by the id of the halfedge starting at the
+/// corner. Corners without uv get (0, 0) and corners without color are
+/// opaque white.
+pub struct RenderBufferBuilder<'a, S: 'a = f32> {
+    mesh: &'a Mesh<S>,
+    crease_angle: f32,
+    uvs: Option<&'a HashMap<Id, Vector2<f32>>>,
+    colors: Option<&'a HashMap<Id, Vector4<f32>>>,
+    source: bool,
+}
+
+impl<'a, S: BaseFloat> RenderBufferBuilder<'a, S> {
+    pub fn new(mesh: &'a Mesh<S>) -> Self {
+        RenderBufferBuilder {
+            mesh: mesh,
+            crease_angle: 180.0,
+            uvs: None,
+            colors: None,
+            source: false,
+        }
+    }
+
+    /// Faces meeting at more than this angle, in degrees, get their own
+    /// normals at the shared vertices. 0 gives flat shading, the default 180
+    /// smooths across every edge.
+    pub fn crease_angle(mut self, degrees: f32) -> Self {
+        self.crease_angle = degrees;
+        self
+    }
+
+    pub fn uvs(mut self, uvs: &'a HashMap<Id, Vector2<f32>>) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn colors(mut self, colors: &'a HashMap<Id, Vector4<f32>>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Appends the source of the mesh vertex, stored as a float.
+    pub fn source(mut self, enable: bool) -> Self {
+        self.source = enable;
+        self
+    }
+
+    /// Normal at the corner of face_id on vert_id, averaged over the faces
+    /// around the vertex that are within the crease angle of face_id.
+    fn corner_norm(&self, face_norms: &[Option<Vector3<S>>], min_cos: S, face_id: Id, vert_id: Id) -> Vector3<S> {
+        let face_norm = face_norms[face_id].unwrap();
+        let mut total = Vector3::zero();
+        for &halfedge_id in self.mesh.vertices.halfedges[vert_id - 1].iter() {
+            let other_face_id = match self.mesh.halfedge_face_id(halfedge_id as Id) {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(other_norm) = face_norms[other_face_id] {
+                if other_face_id == face_id || face_norm.dot(other_norm) >= min_cos {
+                    total += other_norm;
+                }
+            }
+        }
+        if total.magnitude2() > S::zero() {
+            total.normalize()
+        } else {
+            face_norm
+        }
+    }
+
+    pub fn build(&self) -> RenderBuffer {
+        let layout = VertexLayout {
+            uv: self.uvs.is_some(),
+            color: self.colors.is_some(),
+            source: self.source,
+        };
+        let mut face_norms = vec![None; self.mesh.faces.len() + 1];
+        for face_id in FaceIterator::new(self.mesh) {
+            face_norms[face_id] = Some(self.mesh.face_norm(face_id));
+        }
+        let min_cos = Deg(scalar::<S>(self.crease_angle)).cos();
+        let mut buffer = RenderBuffer {
+            layout: layout,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+        let mut vertex_map : FnvHashMap<Vec<u32>, u32> = FnvHashMap::default();
+        let mut attributes = Vec::with_capacity(layout.stride());
+        let mut corners = Vec::new();
+        for face_id in FaceIterator::new(self.mesh) {
+            corners.clear();
+            for halfedge_id in FaceHalfedgeIterator::new(self.mesh, self.mesh.face_first_halfedge_id(face_id).unwrap()) {
+                let vert_id = self.mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
+                let position = self.mesh.vertex_position(vert_id).unwrap().cast::<f32>().unwrap();
+                let normal = self.corner_norm(&face_norms, min_cos, face_id, vert_id).cast::<f32>().unwrap();
+                attributes.clear();
+                attributes.extend_from_slice(&[position.x, position.y, position.z, normal.x, normal.y, normal.z]);
+                if let Some(uvs) = self.uvs {
+                    let uv = uvs.get(&halfedge_id).cloned().unwrap_or_else(Vector2::zero);
+                    attributes.extend_from_slice(&[uv.x, uv.y]);
+                }
+                if let Some(colors) = self.colors {
+                    let color = colors.get(&halfedge_id).cloned().unwrap_or_else(|| Vector4::new(1.0, 1.0, 1.0, 1.0));
+                    attributes.extend_from_slice(&[color.x, color.y, color.z, color.w]);
+                }
+                if self.source {
+                    attributes.push(self.mesh.vertex_source(vert_id).unwrap() as f32);
+                }
+                let key : Vec<u32> = attributes.iter().map(|value| value.to_bits()).collect();
+                let vertices = &mut buffer.vertices;
+                let index = *vertex_map.entry(key).or_insert_with(|| {
+                    let index = (vertices.len() / layout.stride()) as u32;
+                    vertices.extend_from_slice(&attributes);
+                    index
+                });
+                corners.push(index);
+            }
+            for i in 1..corners.len().saturating_sub(1) {
+                buffer.indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+            }
+        }
+        buffer
+    }
+}
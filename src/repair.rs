@@ -0,0 +1,431 @@
+use cgmath::Point3;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::f32;
+
+#[derive(Debug, Clone, Default)]
+pub struct OrientReport {
+    /// Faces of the source mesh which have been reversed.
+    pub flipped_faces: Vec<Id>,
+
+    /// Number of edge connected components.
+    pub component_count: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NonManifoldReport {
+    /// Edges of the source mesh, as vertex pairs, shared by more than two faces.
+    pub non_manifold_edges: Vec<(Id, Id)>,
+
+    /// Vertices of the source mesh where separate fans of faces meet without
+    /// a non manifold edge between them.
+    pub bowtie_vertices: Vec<Id>,
+
+    /// Every source vertex which has been duplicated, with the number of added copies.
+    pub split_vertices: Vec<(Id, usize)>,
+
+    /// Faces of the source mesh which have been moved to a duplicated vertex.
+    pub touched_faces: Vec<Id>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DegenerateReport {
+    /// Faces of the source mesh removed because they had less than three
+    /// distinct vertices left after collapsing repeated ones.
+    pub repeated_vertex_faces: Vec<Id>,
+
+    /// Faces of the source mesh kept after collapsing their repeated vertices.
+    pub repaired_faces: Vec<Id>,
+
+    /// Faces of the source mesh removed because of a tiny area or angle.
+    pub degenerate_faces: Vec<Id>,
+
+    /// Faces of the source mesh removed because an earlier face uses the same vertices.
+    pub duplicate_faces: Vec<Id>,
+
+    /// Number of edges collapsed while removing degenerate faces.
+    pub collapsed_edges: usize,
+}
+
+pub trait Repair {
+    fn orient_faces(&self) -> (Mesh, OrientReport);
+    fn fix_non_manifold(&self) -> (Mesh, NonManifoldReport);
+    fn remove_degenerate_faces(&self, area_tolerance: f32, angle_tolerance: f32) -> (Mesh, DegenerateReport);
+}
+
+struct RepairPolygon {
+    face: Id,
+    vertices: Vec<Id>,
+}
+
+fn collect_polygons(mesh: &Mesh) -> Vec<RepairPolygon> {
+    let mut polygons = Vec::new();
+    for face_id in FaceIterator::new(mesh) {
+        let mut vertices = Vec::new();
+        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            vertices.push(mesh.halfedge_start_vertex_id(halfedge_id).unwrap());
+        }
+        polygons.push(RepairPolygon {
+            face: face_id,
+            vertices: vertices,
+        });
+    }
+    polygons
+}
+
+/// Rebuilds the mesh from the polygons, the faces keep their order, so the
+/// face with index i in polygons gets the id i + 1. Vertex ids beyond the
+/// vertices of the source mesh refer to copies of the vertices listed in
+/// duplicated_vertices.
+fn build_mesh(mesh: &Mesh, polygons: &[RepairPolygon], duplicated_vertices: &[Id]) -> Mesh {
+    let mut new_mesh = Mesh::new();
+    let mut new_vert_map = HashMap::new();
+    for polygon in polygons {
+        let mut added_halfedges : Vec<(Id, Id)> = Vec::new();
+        for &old_vert_id in polygon.vertices.iter() {
+            let new_vert_id = *new_vert_map.entry(old_vert_id).or_insert_with(|| {
+                let source_vert_id = if old_vert_id > mesh.vertices.len() {
+                    duplicated_vertices[old_vert_id - mesh.vertices.len() - 1]
+                } else {
+                    old_vert_id
+                };
+                let new_added_vert_id = new_mesh.add_vertex(mesh.vertex_position(source_vert_id).unwrap());
+                new_mesh.set_vertex_source(new_added_vert_id, mesh.vertex_source(source_vert_id).unwrap());
+                new_added_vert_id
+            });
+            added_halfedges.push((new_mesh.add_halfedge(), new_vert_id));
+        }
+        new_mesh.add_halfedges_and_vertices(&added_halfedges);
+    }
+    new_mesh
+}
+
+/// Maps each undirected edge to the polygons using it, together with whether
+/// the polygon walks the edge from the smaller to the larger vertex id.
+fn collect_edge_uses(polygons: &[RepairPolygon]) -> HashMap<(Id, Id), Vec<(usize, bool)>> {
+    let mut edge_uses : HashMap<(Id, Id), Vec<(usize, bool)>> = HashMap::new();
+    for (index, polygon) in polygons.iter().enumerate() {
+        for i in 0..polygon.vertices.len() {
+            let from = polygon.vertices[i];
+            let to = polygon.vertices[(i + 1) % polygon.vertices.len()];
+            if from == to {
+                continue;
+            }
+            let key = if from < to { (from, to) } else { (to, from) };
+            edge_uses.entry(key).or_default().push((index, from < to));
+        }
+    }
+    edge_uses
+}
+
+/// Six times the signed volume enclosed by the polygons, measured from the
+/// centroid of their vertices so open components give a stable sign.
+fn component_signed_volume(mesh: &Mesh, polygons: &[RepairPolygon], component: &[usize], flipped: &[bool]) -> f32 {
+    let mut points = Vec::new();
+    for &index in component {
+        for &vert_id in polygons[index].vertices.iter() {
+            points.push(mesh.vertex_position(vert_id).unwrap());
+        }
+    }
+    if points.is_empty() {
+        return 0.0;
+    }
+    let center = Point3::centroid(&points);
+    let mut volume = 0.0;
+    for &index in component {
+        let vertices = &polygons[index].vertices;
+        let mut polygon_volume = 0.0;
+        let first = mesh.vertex_position(vertices[0]).unwrap() - center;
+        for i in 1..vertices.len().saturating_sub(1) {
+            let second = mesh.vertex_position(vertices[i]).unwrap() - center;
+            let third = mesh.vertex_position(vertices[i + 1]).unwrap() - center;
+            polygon_volume += first.dot(second.cross(third));
+        }
+        if flipped[index] {
+            polygon_volume = -polygon_volume;
+        }
+        volume += polygon_volume;
+    }
+    volume
+}
+
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+/// Removes repeated consecutive vertices, including the wrap around.
+fn collapse_repeated_vertices(vertices: &mut Vec<Id>) {
+    vertices.dedup();
+    while vertices.len() > 1 && vertices.first() == vertices.last() {
+        vertices.pop();
+    }
+}
+
+fn has_repeated_vertices(vertices: &[Id]) -> bool {
+    (0..vertices.len()).any(|i| vertices[i + 1..].contains(&vertices[i]))
+}
+
+/// Area and smallest corner angle of the polygon.
+fn polygon_area_and_min_angle(mesh: &Mesh, vertices: &[Id]) -> (f32, f32) {
+    let points : Vec<Point3<f32>> = vertices.iter().map(|&vert_id| mesh.vertex_position(vert_id).unwrap()).collect();
+    let mut area_normal = Vector3::zero();
+    for i in 1..points.len().saturating_sub(1) {
+        area_normal += (points[i] - points[0]).cross(points[i + 1] - points[0]);
+    }
+    let mut min_angle = f32::consts::PI;
+    for i in 0..points.len() {
+        let to_prev = points[(i + points.len() - 1) % points.len()] - points[i];
+        let to_next = points[(i + 1) % points.len()] - points[i];
+        let angle = if to_prev.magnitude2() > 0.0 && to_next.magnitude2() > 0.0 {
+            to_prev.angle(to_next).0
+        } else {
+            0.0
+        };
+        min_angle = min_angle.min(angle);
+    }
+    (area_normal.magnitude() * 0.5, min_angle)
+}
+
+impl Repair for Mesh {
+    /// Makes the winding of the faces consistent across every edge connected
+    /// component, then reverses the components which enclose a negative volume
+    /// so the normals point outward. Winding is only propagated over edges
+    /// shared by exactly two faces, and the faces of a non orientable component
+    /// keep whatever winding the traversal reached them with.
+    fn orient_faces(&self) -> (Mesh, OrientReport) {
+        let mut polygons = collect_polygons(self);
+        let edge_uses = collect_edge_uses(&polygons);
+        let mut polygon_edges : Vec<Vec<(Id, Id)>> = vec![Vec::new(); polygons.len()];
+        for (&key, uses) in edge_uses.iter() {
+            if uses.len() == 2 {
+                polygon_edges[uses[0].0].push(key);
+                polygon_edges[uses[1].0].push(key);
+            }
+        }
+        let mut flipped = vec![false; polygons.len()];
+        let mut visited = vec![false; polygons.len()];
+        let mut report = OrientReport::default();
+        for seed in 0..polygons.len() {
+            if visited[seed] {
+                continue;
+            }
+            visited[seed] = true;
+            let mut component = vec![seed];
+            let mut queue = VecDeque::new();
+            queue.push_back(seed);
+            while let Some(index) = queue.pop_front() {
+                for key in polygon_edges[index].iter() {
+                    let uses = &edge_uses[key];
+                    let (&(_, forward), &(other, other_forward)) = if uses[0].0 == index {
+                        (&uses[0], &uses[1])
+                    } else {
+                        (&uses[1], &uses[0])
+                    };
+                    if visited[other] {
+                        continue;
+                    }
+                    visited[other] = true;
+                    // Consistent neighbors walk the shared edge in opposite directions
+                    flipped[other] = other_forward == (forward != flipped[index]);
+                    component.push(other);
+                    queue.push_back(other);
+                }
+            }
+            if component_signed_volume(self, &polygons, &component, &flipped) < 0.0 {
+                for &index in component.iter() {
+                    flipped[index] = !flipped[index];
+                }
+            }
+            report.component_count += 1;
+        }
+        for (index, polygon) in polygons.iter_mut().enumerate() {
+            if flipped[index] {
+                polygon.vertices.reverse();
+                report.flipped_faces.push(polygon.face);
+            }
+        }
+        (build_mesh(self, &polygons, &[]), report)
+    }
+
+    /// Splits the mesh at non manifold edges and bowtie vertices. The faces
+    /// around a vertex are grouped into fans connected by edges shared by
+    /// exactly two faces, and every fan except the first gets its own copy of
+    /// the vertex, so the faces around a non manifold edge only stay connected
+    /// through their other edges.
+    fn fix_non_manifold(&self) -> (Mesh, NonManifoldReport) {
+        let mut polygons = collect_polygons(self);
+        let edge_uses = collect_edge_uses(&polygons);
+        let mut report = NonManifoldReport::default();
+        let mut non_manifold_vertices : HashSet<Id> = HashSet::new();
+        for (&key, uses) in edge_uses.iter() {
+            if uses.len() > 2 {
+                report.non_manifold_edges.push(key);
+                non_manifold_vertices.insert(key.0);
+                non_manifold_vertices.insert(key.1);
+            }
+        }
+        report.non_manifold_edges.sort();
+        let mut vertex_corners : HashMap<Id, Vec<(usize, usize)>> = HashMap::new();
+        for (index, polygon) in polygons.iter().enumerate() {
+            for (corner, &vert_id) in polygon.vertices.iter().enumerate() {
+                vertex_corners.entry(vert_id).or_default().push((index, corner));
+            }
+        }
+        let mut vert_ids : Vec<Id> = vertex_corners.keys().cloned().collect();
+        vert_ids.sort();
+        let mut duplicated_vertices : Vec<Id> = Vec::new();
+        let mut corner_changes : Vec<(usize, usize, Id)> = Vec::new();
+        for &vert_id in vert_ids.iter() {
+            let corners = &vertex_corners[&vert_id];
+            let mut parents : Vec<usize> = (0..corners.len()).collect();
+            for (i, &(index, corner)) in corners.iter().enumerate() {
+                let vertices = &polygons[index].vertices;
+                let neighbors = [vertices[(corner + vertices.len() - 1) % vertices.len()],
+                    vertices[(corner + 1) % vertices.len()]];
+                for &neighbor in neighbors.iter() {
+                    let key = if neighbor < vert_id { (neighbor, vert_id) } else { (vert_id, neighbor) };
+                    let uses = match edge_uses.get(&key) {
+                        Some(uses) => uses,
+                        None => continue,
+                    };
+                    if uses.len() != 2 {
+                        continue;
+                    }
+                    let other = if uses[0].0 == index { uses[1].0 } else { uses[0].0 };
+                    if let Some(j) = corners.iter().position(|&(other_index, _)| other_index == other) {
+                        let first_root = find_root(&mut parents, i);
+                        let second_root = find_root(&mut parents, j);
+                        parents[first_root] = second_root;
+                    }
+                }
+            }
+            let mut fan_roots : Vec<usize> = Vec::new();
+            for i in 0..corners.len() {
+                let root = find_root(&mut parents, i);
+                if !fan_roots.contains(&root) {
+                    fan_roots.push(root);
+                }
+            }
+            if fan_roots.len() < 2 {
+                continue;
+            }
+            if !non_manifold_vertices.contains(&vert_id) {
+                report.bowtie_vertices.push(vert_id);
+            }
+            report.split_vertices.push((vert_id, fan_roots.len() - 1));
+            for &root in fan_roots.iter().skip(1) {
+                duplicated_vertices.push(vert_id);
+                let new_vert_id = self.vertices.len() + duplicated_vertices.len();
+                for (i, &(index, corner)) in corners.iter().enumerate() {
+                    if find_root(&mut parents, i) == root {
+                        corner_changes.push((index, corner, new_vert_id));
+                    }
+                }
+            }
+        }
+        for (index, corner, new_vert_id) in corner_changes {
+            polygons[index].vertices[corner] = new_vert_id;
+            if !report.touched_faces.contains(&polygons[index].face) {
+                report.touched_faces.push(polygons[index].face);
+            }
+        }
+        report.touched_faces.sort();
+        (build_mesh(self, &polygons, &duplicated_vertices), report)
+    }
+
+    /// Removes faces whose area is not larger than area_tolerance or which
+    /// have a corner sharper than angle_tolerance (in radians), collapsing the
+    /// shortest edge of each such face into its midpoint so the neighbors
+    /// stay connected. Repeated vertices are collapsed first, and of the faces
+    /// using the same set of vertices only the first one is kept.
+    fn remove_degenerate_faces(&self, area_tolerance: f32, angle_tolerance: f32) -> (Mesh, DegenerateReport) {
+        let mut polygons = collect_polygons(self);
+        let mut report = DegenerateReport::default();
+        let mut working = Mesh::new();
+        for (&position, &source) in self.vertices.position.iter().zip(self.vertices.source.iter()) {
+            let vert_id = working.add_vertex(position);
+            working.set_vertex_source(vert_id, source);
+        }
+        let mut parents : Vec<usize> = (0..self.vertices.len() + 1).collect();
+        let mut removed = vec![false; polygons.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..polygons.len() {
+                if removed[index] {
+                    continue;
+                }
+                let mut vertices : Vec<Id> = polygons[index].vertices.iter().map(|&vert_id| find_root(&mut parents, vert_id)).collect();
+                collapse_repeated_vertices(&mut vertices);
+                if vertices.len() < 3 || has_repeated_vertices(&vertices) {
+                    removed[index] = true;
+                    report.repeated_vertex_faces.push(polygons[index].face);
+                    continue;
+                }
+                polygons[index].vertices = vertices;
+                let (area, min_angle) = polygon_area_and_min_angle(&working, &polygons[index].vertices);
+                if area > area_tolerance && min_angle >= angle_tolerance {
+                    continue;
+                }
+                let vertices = &polygons[index].vertices;
+                let mut shortest = (vertices[0], vertices[1]);
+                let mut shortest_length2 = f32::MAX;
+                for i in 0..vertices.len() {
+                    let from = vertices[i];
+                    let to = vertices[(i + 1) % vertices.len()];
+                    let length2 = working.vertex_position(from).unwrap().distance2(working.vertex_position(to).unwrap());
+                    if length2 < shortest_length2 {
+                        shortest_length2 = length2;
+                        shortest = (from, to);
+                    }
+                }
+                let midpoint = Point3::midpoint(working.vertex_position(shortest.0).unwrap(),
+                    working.vertex_position(shortest.1).unwrap());
+                working.set_vertex_position(shortest.0, midpoint);
+                parents[shortest.1] = shortest.0;
+                removed[index] = true;
+                report.degenerate_faces.push(polygons[index].face);
+                report.collapsed_edges += 1;
+                changed = true;
+            }
+        }
+        let mut face_keys : HashSet<Vec<Id>> = HashSet::new();
+        for index in 0..polygons.len() {
+            if removed[index] {
+                continue;
+            }
+            let mut key = polygons[index].vertices.clone();
+            key.sort();
+            if !face_keys.insert(key) {
+                removed[index] = true;
+                report.duplicate_faces.push(polygons[index].face);
+            } else if polygons[index].vertices.len() != FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(polygons[index].face).unwrap()).count() {
+                report.repaired_faces.push(polygons[index].face);
+            }
+        }
+        let kept_polygons : Vec<RepairPolygon> = polygons.into_iter().enumerate()
+            .filter(|&(index, _)| !removed[index])
+            .map(|(_, polygon)| polygon)
+            .collect();
+        report.repeated_vertex_faces.sort();
+        report.degenerate_faces.sort();
+        (build_mesh(&working, &kept_polygons, &[]), report)
+    }
+}
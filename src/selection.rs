@@ -0,0 +1,234 @@
+use cgmath::BaseFloat;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use iterator::VertexFaceIterator;
+use iterator::VertexNeighborIterator;
+use mesh::EdgeEndpoints;
+use mesh::Id;
+use mesh::Mesh;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Selected vertices, edges and faces of a mesh. Edges are stored by their
+/// endpoints, so both halfedges of an edge are the same selected edge.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Selection {
+    pub vertices: HashSet<Id>,
+    pub edges: HashSet<EdgeEndpoints>,
+    pub faces: HashSet<Id>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty() && self.edges.is_empty() && self.faces.is_empty()
+    }
+}
+
+/// Edits the named selection sets stored on a mesh. Operations on a name
+/// without a selection set work on an empty one.
+pub trait Select {
+    fn selection(&self, name: &str) -> Option<&Selection>;
+    /// Gets the selection set, creating an empty one first when missing.
+    fn selection_mut(&mut self, name: &str) -> &mut Selection;
+    fn remove_selection(&mut self, name: &str) -> Option<Selection>;
+    /// Selects every vertex, edge and face.
+    fn select_all(&mut self, name: &str);
+    /// Adds the elements touching a vertex of the selected ones.
+    fn grow_selection(&mut self, name: &str);
+    /// Removes the elements touching a vertex of unselected ones.
+    fn shrink_selection(&mut self, name: &str);
+    /// Extends the selection to the whole connected parts it touches.
+    fn select_linked(&mut self, name: &str);
+    /// Adds the faces whose normal is within the angle (in radians) of the
+    /// direction.
+    fn select_by_normal(&mut self, name: &str, direction: Vector3<f32>, max_angle: f32);
+    /// Replaces the selection by the border edges of the selected faces and
+    /// their vertices, the edges with no selected face on the other side.
+    fn select_boundary(&mut self, name: &str);
+}
+
+fn vertex_edges(mesh: &Mesh, vert_id: Id) -> Vec<EdgeEndpoints> {
+    VertexNeighborIterator::new(mesh, vert_id).map(|neighbor_id| EdgeEndpoints::new(vert_id, neighbor_id)).collect()
+}
+
+fn face_vertices(mesh: &Mesh, face_id: Id) -> Vec<Id> {
+    FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap())
+        .map(|halfedge_id| mesh.halfedge_start_vertex_id(halfedge_id).unwrap())
+        .collect()
+}
+
+fn selected_vertices(mesh: &Mesh, selection: &Selection) -> HashSet<Id> {
+    let mut vertices = selection.vertices.clone();
+    for edge in selection.edges.iter() {
+        vertices.insert(edge.low);
+        vertices.insert(edge.high);
+    }
+    for &face_id in selection.faces.iter() {
+        vertices.extend(face_vertices(mesh, face_id));
+    }
+    vertices
+}
+
+/// Copies the selection sets of the mesh onto the rebuilt one, through the
+/// maps from old to new vertex and face ids. Elements missing from the maps
+/// are dropped, edges are kept when both endpoints still share an edge.
+pub fn remap_selections<S: BaseFloat>(from_mesh: &Mesh<S>, to_mesh: &mut Mesh<S>, vertex_map: &HashMap<Id, Id>, face_map: &HashMap<Id, Id>) {
+    for (name, selection) in from_mesh.selections.iter() {
+        let mut remapped = Selection::new();
+        remapped.vertices.extend(selection.vertices.iter().filter_map(|vert_id| vertex_map.get(vert_id).cloned()));
+        for edge in selection.edges.iter() {
+            if let (Some(&low), Some(&high)) = (vertex_map.get(&edge.low), vertex_map.get(&edge.high)) {
+                let endpoints = EdgeEndpoints::new(low, high);
+                if to_mesh.edge_halfedge_id(low, high).is_some() {
+                    remapped.edges.insert(endpoints);
+                }
+            }
+        }
+        remapped.faces.extend(selection.faces.iter().filter_map(|face_id| face_map.get(face_id).cloned()));
+        let target = to_mesh.selections.entry(name.clone()).or_default();
+        target.vertices.extend(remapped.vertices);
+        target.edges.extend(remapped.edges);
+        target.faces.extend(remapped.faces);
+    }
+}
+
+impl Select for Mesh {
+    fn selection(&self, name: &str) -> Option<&Selection> {
+        self.selections.get(name)
+    }
+
+    fn selection_mut(&mut self, name: &str) -> &mut Selection {
+        self.selections.entry(name.to_string()).or_default()
+    }
+
+    fn remove_selection(&mut self, name: &str) -> Option<Selection> {
+        self.selections.remove(name)
+    }
+
+    fn select_all(&mut self, name: &str) {
+        let mut selection = Selection::new();
+        for face_id in FaceIterator::new(self) {
+            let vertices = face_vertices(self, face_id);
+            for i in 0..vertices.len() {
+                selection.vertices.insert(vertices[i]);
+                selection.edges.insert(EdgeEndpoints::new(vertices[i], vertices[(i + 1) % vertices.len()]));
+            }
+            selection.faces.insert(face_id);
+        }
+        self.selections.insert(name.to_string(), selection);
+    }
+
+    fn grow_selection(&mut self, name: &str) {
+        let mut selection = self.selections.remove(name).unwrap_or_default();
+        let face_touched = selected_vertices(self, &Selection { faces: selection.faces.clone(), ..Selection::new() });
+        for &vert_id in selection.vertices.clone().iter() {
+            selection.vertices.extend(VertexNeighborIterator::new(self, vert_id));
+        }
+        let edge_vertices : HashSet<Id> = selection.edges.iter().flat_map(|edge| vec![edge.low, edge.high]).collect();
+        for &vert_id in edge_vertices.iter() {
+            selection.edges.extend(vertex_edges(self, vert_id));
+        }
+        if !selection.faces.is_empty() {
+            for &vert_id in face_touched.iter() {
+                selection.faces.extend(VertexFaceIterator::new(self, vert_id));
+            }
+        }
+        self.selections.insert(name.to_string(), selection);
+    }
+
+    fn shrink_selection(&mut self, name: &str) {
+        let mut selection = self.selections.remove(name).unwrap_or_default();
+        let outer_vertices : HashSet<Id> = selection.vertices.iter().cloned()
+            .filter(|&vert_id| VertexNeighborIterator::new(self, vert_id).any(|neighbor_id| !selection.vertices.contains(&neighbor_id)))
+            .collect();
+        let outer_edge_vertices : HashSet<Id> = selection.edges.iter()
+            .flat_map(|edge| vec![edge.low, edge.high])
+            .filter(|&vert_id| vertex_edges(self, vert_id).iter().any(|edge| !selection.edges.contains(edge)))
+            .collect();
+        let outer_face_vertices : HashSet<Id> = selected_vertices(self, &Selection { faces: selection.faces.clone(), ..Selection::new() })
+            .into_iter()
+            .filter(|&vert_id| VertexFaceIterator::new(self, vert_id).any(|face_id| !selection.faces.contains(&face_id)))
+            .collect();
+        selection.vertices.retain(|vert_id| !outer_vertices.contains(vert_id));
+        selection.edges.retain(|edge| !outer_edge_vertices.contains(&edge.low) && !outer_edge_vertices.contains(&edge.high));
+        let faces : Vec<Id> = selection.faces.iter().cloned().collect();
+        for face_id in faces {
+            if face_vertices(self, face_id).iter().any(|vert_id| outer_face_vertices.contains(vert_id)) {
+                selection.faces.remove(&face_id);
+            }
+        }
+        self.selections.insert(name.to_string(), selection);
+    }
+
+    fn select_linked(&mut self, name: &str) {
+        let mut selection = self.selections.remove(name).unwrap_or_default();
+        let mut visited : HashSet<Id> = HashSet::new();
+        let mut pending : Vec<Id> = selected_vertices(self, &selection).into_iter().collect();
+        while let Some(vert_id) = pending.pop() {
+            if !visited.insert(vert_id) {
+                continue;
+            }
+            for neighbor_id in VertexNeighborIterator::new(self, vert_id) {
+                if !visited.contains(&neighbor_id) {
+                    pending.push(neighbor_id);
+                }
+            }
+        }
+        for &vert_id in visited.iter() {
+            if !selection.vertices.is_empty() {
+                selection.vertices.insert(vert_id);
+            }
+            if !selection.edges.is_empty() {
+                selection.edges.extend(vertex_edges(self, vert_id));
+            }
+        }
+        if !selection.faces.is_empty() {
+            for &vert_id in visited.iter() {
+                selection.faces.extend(VertexFaceIterator::new(self, vert_id));
+            }
+        }
+        self.selections.insert(name.to_string(), selection);
+    }
+
+    fn select_by_normal(&mut self, name: &str, direction: Vector3<f32>, max_angle: f32) {
+        if direction.magnitude2() == 0.0 {
+            return;
+        }
+        let direction = direction.normalize();
+        let min_cos = max_angle.cos();
+        let faces : Vec<Id> = FaceIterator::new(self)
+            .filter(|&face_id| self.face_norm(face_id).dot(direction) >= min_cos)
+            .collect();
+        self.selection_mut(name).faces.extend(faces);
+    }
+
+    fn select_boundary(&mut self, name: &str) {
+        let mut selection = self.selections.remove(name).unwrap_or_default();
+        selection.vertices.clear();
+        selection.edges.clear();
+        for &face_id in selection.faces.iter() {
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                let is_border = match self.halfedge_opposite_face_id(halfedge_id) {
+                    Some(opposite_face_id) => !selection.faces.contains(&opposite_face_id),
+                    None => true,
+                };
+                if is_border {
+                    let from_vert_id = self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                    let to_vert_id = self.halfedge_start_vertex_id(self.halfedge_next_id(halfedge_id).unwrap()).unwrap();
+                    selection.vertices.insert(from_vert_id);
+                    selection.vertices.insert(to_vert_id);
+                    selection.edges.insert(EdgeEndpoints::new(from_vert_id, to_vert_id));
+                }
+            }
+        }
+        selection.faces.clear();
+        self.selections.insert(name.to_string(), selection);
+    }
+}
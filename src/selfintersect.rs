@@ -0,0 +1,116 @@
+use bvh::Aabb;
+use bvh::Bvh;
+use cgmath::Point3;
+use cgmath::prelude::*;
+use iterator::FaceHalfedgeIterator;
+use iterator::FaceIterator;
+use mesh::Id;
+use mesh::Mesh;
+use util::*;
+
+/// Segments shorter than this are treated as touching, not intersecting.
+const MIN_SEGMENT_LENGTH: f32 = 0.00001;
+
+#[derive(Debug, Clone)]
+pub struct FaceIntersection {
+    pub first_face: Id,
+    pub second_face: Id,
+    pub segment: (Point3<f32>, Point3<f32>),
+}
+
+struct IntersectTriangle {
+    face: Id,
+    vertices: [Id; 3],
+    corners: [Point3<f32>; 3],
+}
+
+pub trait SelfIntersect {
+    fn self_intersections(&self) -> Vec<FaceIntersection>;
+}
+
+fn shared_vertex_count(first: &IntersectTriangle, second: &IntersectTriangle) -> usize {
+    first.vertices.iter().filter(|v| second.vertices.contains(v)).count()
+}
+
+fn intersect_of_triangles(first: &IntersectTriangle, second: &IntersectTriangle) -> Option<(Point3<f32>, Point3<f32>)> {
+    let mut points : Vec<Point3<f32>> = Vec::new();
+    for &(edges_of, against) in [(first, second), (second, first)].iter() {
+        let [a, b, c] = against.corners;
+        for i in 0..3 {
+            let p0 = edges_of.corners[i];
+            let p1 = edges_of.corners[(i + 1) % 3];
+            if let Some(point) = intersect_of_segment_and_triangle(p0, p1, a, b, c) {
+                points.push(point);
+            }
+        }
+    }
+    if points.len() < 2 {
+        return None;
+    }
+    let mut segment = (points[0], points[1]);
+    let mut longest = 0.0;
+    for i in 0..points.len() {
+        for j in i + 1..points.len() {
+            let length = points[i].distance(points[j]);
+            if length > longest {
+                longest = length;
+                segment = (points[i], points[j]);
+            }
+        }
+    }
+    if longest < MIN_SEGMENT_LENGTH {
+        return None;
+    }
+    Some(segment)
+}
+
+impl SelfIntersect for Mesh {
+    /// Finds pairs of faces which cut through each other. Polygons are fan
+    /// triangulated, so a pair of faces may be reported once for every pair of
+    /// their triangles that intersect. Faces sharing an edge are never tested
+    /// against each other, and coplanar overlaps are not reported.
+    fn self_intersections(&self) -> Vec<FaceIntersection> {
+        let mut triangles : Vec<IntersectTriangle> = Vec::new();
+        for face_id in FaceIterator::new(self) {
+            let mut vertices = Vec::new();
+            for halfedge_id in FaceHalfedgeIterator::new(self, self.face_first_halfedge_id(face_id).unwrap()) {
+                vertices.push(self.halfedge_start_vertex_id(halfedge_id).unwrap());
+            }
+            for i in 1..vertices.len().saturating_sub(1) {
+                let triangle_vertices = [vertices[0], vertices[i], vertices[i + 1]];
+                triangles.push(IntersectTriangle {
+                    face: face_id,
+                    vertices: triangle_vertices,
+                    corners: [self.vertex_position(triangle_vertices[0]).unwrap(),
+                        self.vertex_position(triangle_vertices[1]).unwrap(),
+                        self.vertex_position(triangle_vertices[2]).unwrap()],
+                });
+            }
+        }
+        let triangle_bounds : Vec<Aabb> = triangles.iter().map(|t| Aabb::from_points(&t.corners)).collect();
+        let bvh = Bvh::new(&triangle_bounds);
+        let mut intersections = Vec::new();
+        let mut candidates = Vec::new();
+        for (i, triangle) in triangles.iter().enumerate() {
+            candidates.clear();
+            bvh.query_aabb(&triangle_bounds, &triangle_bounds[i], &mut candidates);
+            for &j in candidates.iter() {
+                if j <= i {
+                    continue;
+                }
+                let other = &triangles[j];
+                if other.face == triangle.face || shared_vertex_count(triangle, other) >= 2 {
+                    continue;
+                }
+                if let Some(segment) = intersect_of_triangles(triangle, other) {
+                    intersections.push(FaceIntersection {
+                        first_face: triangle.face,
+                        second_face: other.face,
+                        segment: segment,
+                    });
+                }
+            }
+        }
+        intersections
+    }
+}
@@ -6,11 +6,13 @@ use subdivide::Subdivide;
 use wrap::GiftWrapper;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Bone {
     from: Point3<f32>,
     to: Point3<f32>,
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SkeletonMesh {
     bones: Vec<Bone>,
     mesh: Mesh,
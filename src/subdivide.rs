@@ -1,16 +1,22 @@
+use cgmath::BaseFloat;
 use cgmath::EuclideanSpace;
 use cgmath::Point3;
 use iterator::FaceHalfedgeIterator;
 use iterator::FaceIterator;
 use mesh::Id;
 use mesh::Mesh;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "rayon")]
+use smallvec::SmallVec;
+use util::scalar;
 
 /// Derives Clone to allow initializing a vec with the vec![value; length]
 /// macro.
 #[derive(Clone)]
-struct FaceData {
+struct FaceData<S> {
     /// The center point of the original face in the input mesh.
-    average_of_points: Point3<f32>,
+    average_of_points: Point3<S>,
 
     /// The new vertex in the output mesh.
     generated_vertex_id: Id,
@@ -19,8 +25,8 @@ struct FaceData {
 /// Derives Clone to allow initializing a vec with the vec![value; length]
 /// macro.
 #[derive(Clone)]
-struct EdgeData {
-    mid_point: Point3<f32>,
+struct EdgeData<S> {
+    mid_point: Point3<S>,
     generated_vertex_id: Id,
 }
 
@@ -39,12 +45,38 @@ impl VertexData {
     }
 }
 
-fn face_data_mut<'a>(
-    input: &Mesh,
+/// The new position of an edge point. Shared by the serial and the parallel
+/// path so both produce the same bits.
+fn edge_point<S: BaseFloat>(
+    f1_average: Point3<S>,
+    f2_average: Point3<S>,
+    start_position: Point3<S>,
+    stop_position: Point3<S>,
+) -> Point3<S> {
+    Point3::centroid(&[f1_average, f2_average, start_position, stop_position])
+}
+
+/// The new position of an original vertex from the centers of its faces and
+/// the mid points of its edges.
+fn vertex_point<S: BaseFloat>(
+    position: Point3<S>,
+    avg_of_faces: &[Point3<S>],
+    avg_of_edge_mids: &[Point3<S>],
+) -> Point3<S> {
+    let barycenter = Point3::centroid(avg_of_faces);
+    let average_of_edge = Point3::centroid(avg_of_edge_mids);
+    (((average_of_edge * scalar(2.0)) + barycenter.to_vec())
+        + (position.to_vec()
+            * S::from((avg_of_faces.len() as i32 - 3).abs()).unwrap()))
+        / S::from(avg_of_faces.len()).unwrap()
+}
+
+fn face_data_mut<'a, S: BaseFloat>(
+    input: &Mesh<S>,
     id: Id,
-    face_data_set: &'a mut Vec<Option<FaceData>>,
-    output: &mut Mesh,
-) -> &'a mut FaceData {
+    face_data_set: &'a mut Vec<Option<FaceData<S>>>,
+    output: &mut Mesh<S>,
+) -> &'a mut FaceData<S> {
     if face_data_set[id].is_some() {
         return face_data_set[id].as_mut().unwrap();
     }
@@ -56,13 +88,13 @@ fn face_data_mut<'a>(
     face_data_set[id].as_mut().unwrap()
 }
 
-fn edge_data_mut<'a>(
-    input: &Mesh,
+fn edge_data_mut<'a, S: BaseFloat>(
+    input: &Mesh<S>,
     id: Id,
-    face_data_set: &mut Vec<Option<FaceData>>,
-    edge_data_set: &'a mut Vec<Option<EdgeData>>,
-    output: &mut Mesh,
-) -> &'a mut EdgeData {
+    face_data_set: &mut Vec<Option<FaceData<S>>>,
+    edge_data_set: &'a mut Vec<Option<EdgeData<S>>>,
+    output: &mut Mesh<S>,
+) -> &'a mut EdgeData<S> {
     let id = input.peek_same_halfedge(id);
     if edge_data_set[id].is_some() {
         return edge_data_set[id].as_mut().unwrap();
@@ -79,23 +111,23 @@ fn edge_data_mut<'a>(
             halfedge.face,
             input.halfedge(halfedge.opposite).unwrap().face,
             input.halfedge(halfedge.next).unwrap().vertex,
-            input.vertex(halfedge.vertex).unwrap().position,
+            input.vertex_position(halfedge.vertex).unwrap(),
         )
     };
     let stop_vertex_position =
-        input.vertex(next_halfedge_vertex_id).unwrap().position;
+        input.vertex_position(next_halfedge_vertex_id).unwrap();
     let f1_data_average =
         face_data_mut(input, halfedge_face_id, face_data_set, output)
             .average_of_points;
     let f2_data_average =
         face_data_mut(input, opposite_face_id, face_data_set, output)
             .average_of_points;
-    let center = Point3::centroid(&[
+    let center = edge_point(
         f1_data_average,
         f2_data_average,
         start_vertex_position,
         stop_vertex_position,
-    ]);
+    );
     edge_data_set[id] = Some(EdgeData {
         mid_point,
         generated_vertex_id: output.add_vertex(center),
@@ -103,37 +135,108 @@ fn edge_data_mut<'a>(
     edge_data_set[id].as_mut().unwrap()
 }
 
+/// The input element an output vertex is generated from.
+#[cfg(feature = "rayon")]
+#[derive(Copy, Clone)]
+enum GeneratedPoint {
+    Face(Id),
+    Edge(Id),
+    Vertex(Id),
+}
+
+/// Assigns the output vertex ids in the same order generate() adds the
+/// vertices, without computing any positions.
+#[cfg(feature = "rayon")]
+struct PointOrder<'a, S: 'a> {
+    input: &'a Mesh<S>,
+    face_ids: Vec<Id>,
+    edge_ids: Vec<Id>,
+    vertex_ids: Vec<Id>,
+    points: Vec<GeneratedPoint>,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, S: BaseFloat> PointOrder<'a, S> {
+    fn face_id(&mut self, id: Id) -> Id {
+        if self.face_ids[id] == 0 {
+            self.points.push(GeneratedPoint::Face(id));
+            self.face_ids[id] = self.points.len();
+        }
+        self.face_ids[id]
+    }
+
+    fn edge_id(&mut self, id: Id) -> Id {
+        let input = self.input;
+        let id = input.peek_same_halfedge(id);
+        if self.edge_ids[id] == 0 {
+            let halfedge = input.halfedge(id).unwrap();
+            self.face_id(halfedge.face);
+            self.face_id(input.halfedge(halfedge.opposite).unwrap().face);
+            self.points.push(GeneratedPoint::Edge(id));
+            self.edge_ids[id] = self.points.len();
+        }
+        self.edge_ids[id]
+    }
+
+    fn vertex_id(&mut self, id: Id) -> Id {
+        let input = self.input;
+        if self.vertex_ids[id] == 0 {
+            for &halfedge_id in input.vertices.halfedges[id - 1].iter() {
+                let halfedge_id = halfedge_id as Id;
+                self.face_id(input.halfedge_face_id(halfedge_id).unwrap());
+                self.edge_id(halfedge_id);
+            }
+            self.points.push(GeneratedPoint::Vertex(id));
+            self.vertex_ids[id] = self.points.len();
+        }
+        self.vertex_ids[id]
+    }
+}
+
+/// One output quad, generated for the corner at the end of the input
+/// halfedge.
+#[cfg(feature = "rayon")]
+struct Corner {
+    halfedge: Id,
+    face_vertex: Id,
+    edge_vertex: Id,
+    corner_vertex: Id,
+    next_edge_vertex: Id,
+    /// The corner of the previous halfedge in the same input face.
+    prev: usize,
+}
+
 /// A context for subdivision, providing temporary memory buffers.
-pub struct CatmullClarkSubdivider<'a> {
+pub struct CatmullClarkSubdivider<'a, S: 'a = f32> {
     /// Maps HALFEDGE ID in the input mesh to EdgeData.
-    edge_data_set: Vec<Option<EdgeData>>,
+    edge_data_set: Vec<Option<EdgeData<S>>>,
 
     /// Maps FACE ID in the INPUT mesh to FaceData.
-    face_data_set: Vec<Option<FaceData>>,
+    face_data_set: Vec<Option<FaceData<S>>>,
 
     /// Destination mesh
-    output: Mesh,
+    output: Mesh<S>,
 
     /// Source mesh
-    input: &'a Mesh,
+    input: &'a Mesh<S>,
 
     // Temporary and reusable memory buffer for vertex_data_mut().
-    tmp_avg_of_faces: Vec<Point3<f32>>,
+    tmp_avg_of_faces: Vec<Point3<S>>,
 
     // Temporary and reusable memory buffer for vertex_data_mut().
-    tmp_avg_of_edge_mids: Vec<Point3<f32>>,
+    tmp_avg_of_edge_mids: Vec<Point3<S>>,
 
     /// Maps VERTEX ID in the INPUT mesh to VertexData.
     vertex_data_set: Vec<Option<VertexData>>,
 }
 
-impl<'a> CatmullClarkSubdivider<'a> {
+impl<'a, S: BaseFloat> CatmullClarkSubdivider<'a, S> {
     /// Constructs a CatmullClarkSubdivider.
     ///
     /// This function will preallocate as much memory as it can predict is
     /// necessary for the subdivision.
-    pub fn new(input: &'a Mesh) -> Self {
-        let mut output = Mesh::new();
+    pub fn new(input: &'a Mesh<S>) -> Self {
+        let mut output = Mesh::default();
 
         // Each halfedge produce 3 new
         let halfedge_prediction = input.halfedge_count * 4;
@@ -148,16 +251,6 @@ impl<'a> CatmullClarkSubdivider<'a> {
             input.face_count * 4, // Optimize for quads
         );
 
-        // Is this prediction true for all meshes? If false, this is probably
-        // still ok since the worst-case here is degraded performance and
-        // overallocation.
-        //
-        // Insertion in this collection is at the time of writing the single
-        // largest time sink that sticks out when profiling subdivision.
-        //
-        // TODO: Try to replace this with Vec or some other efficient lookup.
-        output.edges.reserve(halfedge_prediction / 2);
-
         // mesh.rs is using 1-based indexing so we need + 1 for the length of
         // each Vec below. It is also not enough to use input.halfedge_count,
         // the count represents "living" elements and may be less than the
@@ -181,7 +274,7 @@ impl<'a> CatmullClarkSubdivider<'a> {
         }
     }
 
-    pub fn generate(mut self) -> Mesh {
+    pub fn generate(mut self) -> Mesh<S> {
         for face_id in FaceIterator::new(self.input) {
             let face_vertex_id = face_data_mut(
                 &self.input,
@@ -207,7 +300,7 @@ impl<'a> CatmullClarkSubdivider<'a> {
                 let vertex_generated_id =
                     self.vertex_data_mut(vertex_id).generated_vertex_id;
                 let added_face_id = self.output.add_face();
-                let mut added_halfedges = [
+                let added_halfedges = [
                     (self.output.add_halfedge(), face_vertex_id),
                     (self.output.add_halfedge(), e1_vertex_id),
                     (self.output.add_halfedge(), vertex_generated_id),
@@ -216,23 +309,15 @@ impl<'a> CatmullClarkSubdivider<'a> {
                 for &(added_halfedge_id, added_vertex_id) in
                     added_halfedges.iter()
                 {
-                    {
-                        let vert = self.output
-                            .vertex_mut(added_vertex_id)
-                            .unwrap();
-                        if !vert.halfedges.contains(&added_halfedge_id) {
-                            vert.halfedges.push(added_halfedge_id);
-                        }
-                    }
-                    self.output.halfedge_mut(added_halfedge_id).unwrap().face =
-                        added_face_id;
                     self.output
-                        .halfedge_mut(added_halfedge_id)
-                        .unwrap()
-                        .vertex = added_vertex_id;
+                        .set_halfedge_face_id(added_halfedge_id, added_face_id);
+                    self.output.set_halfedge_start_vertex_id(
+                        added_halfedge_id,
+                        added_vertex_id,
+                    );
                 }
-                self.output.face_mut(added_face_id).unwrap().halfedge =
-                    added_halfedges[0].0;
+                self.output
+                    .set_face_first_halfedge_id(added_face_id, added_halfedges[0].0);
                 for i in 0..added_halfedges.len() {
                     let first = added_halfedges[i].0;
                     let second =
@@ -244,9 +329,172 @@ impl<'a> CatmullClarkSubdivider<'a> {
         self.output
     }
 
+    /// Same as generate(), with the face, edge and vertex points computed in
+    /// parallel and the output topology built in parallel from precomputed
+    /// id ranges: the corners of the input faces are numbered in face order
+    /// and corner k becomes face k + 1 with the halfedges 4k + 1 to 4k + 4.
+    /// The output is identical to the one of generate().
+    #[cfg(feature = "rayon")]
+    pub fn generate_parallel(self) -> Mesh<S>
+    where
+        S: Send + Sync,
+    {
+        let input = self.input;
+        let mut output = self.output;
+        let mut order = PointOrder {
+            input,
+            face_ids: vec![0; input.faces.len() + 1],
+            edge_ids: vec![0; input.halfedges.len() + 1],
+            vertex_ids: vec![0; input.vertices.len() + 1],
+            points: Vec::with_capacity(output.vertices.position.capacity()),
+        };
+        let mut corners: Vec<Corner> = Vec::with_capacity(input.halfedge_count);
+        let mut halfedge_corners = vec![0; input.halfedges.len() + 1];
+        for face_id in FaceIterator::new(input) {
+            let face_vertex = order.face_id(face_id);
+            let face_halfedge = input.face(face_id).unwrap().halfedge;
+            let first = corners.len();
+            let face_halfedge_id_vec =
+                FaceHalfedgeIterator::new(input, face_halfedge).into_vec();
+            let count = face_halfedge_id_vec.len();
+            for (i, halfedge_id) in face_halfedge_id_vec.into_iter().enumerate() {
+                let next_halfedge_id = input.halfedge(halfedge_id).unwrap().next;
+                let vertex_id = input.halfedge(next_halfedge_id).unwrap().vertex;
+                halfedge_corners[halfedge_id] = corners.len();
+                corners.push(Corner {
+                    halfedge: halfedge_id,
+                    face_vertex,
+                    edge_vertex: order.edge_id(halfedge_id),
+                    next_edge_vertex: order.edge_id(next_halfedge_id),
+                    corner_vertex: order.vertex_id(vertex_id),
+                    prev: first + (i + count - 1) % count,
+                });
+            }
+        }
+
+        let face_points: Vec<Option<Point3<S>>> = order
+            .face_ids
+            .par_iter()
+            .enumerate()
+            .map(|(id, &generated_id)| {
+                if generated_id == 0 {
+                    return None;
+                }
+                Some(input.face_center(id))
+            })
+            .collect();
+        let edge_points: Vec<Option<(Point3<S>, Point3<S>)>> = order
+            .edge_ids
+            .par_iter()
+            .enumerate()
+            .map(|(id, &generated_id)| {
+                if generated_id == 0 {
+                    return None;
+                }
+                let halfedge = input.halfedge(id).unwrap();
+                let opposite = input.halfedge(halfedge.opposite).unwrap();
+                let next = input.halfedge(halfedge.next).unwrap();
+                let center = edge_point(
+                    face_points[halfedge.face].unwrap(),
+                    face_points[opposite.face].unwrap(),
+                    input.vertex_position(halfedge.vertex).unwrap(),
+                    input.vertex_position(next.vertex).unwrap(),
+                );
+                Some((input.edge_center(id), center))
+            })
+            .collect();
+        let vertex_points: Vec<Option<Point3<S>>> = order
+            .vertex_ids
+            .par_iter()
+            .enumerate()
+            .map(|(id, &generated_id)| {
+                if generated_id == 0 {
+                    return None;
+                }
+                let halfedges = &input.vertices.halfedges[id - 1];
+                let mut avg_of_faces = Vec::with_capacity(halfedges.len());
+                let mut avg_of_edge_mids = Vec::with_capacity(halfedges.len());
+                for &halfedge_id in halfedges.iter() {
+                    let halfedge_id = halfedge_id as Id;
+                    let halfedge_face_id = input.halfedge_face_id(halfedge_id).unwrap();
+                    avg_of_faces.push(face_points[halfedge_face_id].unwrap());
+                    avg_of_edge_mids.push(
+                        edge_points[input.peek_same_halfedge(halfedge_id)].unwrap().0,
+                    );
+                }
+                let position = input.vertex_position(id).unwrap();
+                Some(vertex_point(position, &avg_of_faces, &avg_of_edge_mids))
+            })
+            .collect();
+
+        let vertex_count = order.points.len();
+        order
+            .points
+            .par_iter()
+            .map(|point| match *point {
+                GeneratedPoint::Face(id) => face_points[id].unwrap(),
+                GeneratedPoint::Edge(id) => edge_points[id].unwrap().1,
+                GeneratedPoint::Vertex(id) => vertex_points[id].unwrap(),
+            })
+            .collect_into_vec(&mut output.vertices.position);
+        output.vertices.source = vec![-1; vertex_count];
+        output.vertices.halfedges = vec![SmallVec::new(); vertex_count];
+        output.vertices.alive = vec![true; vertex_count];
+        (0..corners.len())
+            .into_par_iter()
+            .map(|k| (k * 4 + 1) as u32)
+            .collect_into_vec(&mut output.faces.halfedge);
+        output.faces.alive = vec![true; corners.len()];
+        let halfedge_count = corners.len() * 4;
+        (0..halfedge_count)
+            .into_par_iter()
+            .map(|i| {
+                let (k, slot) = (i / 4, i % 4);
+                let corner = &corners[k];
+                let halfedge = input.halfedge(corner.halfedge).unwrap();
+                // The quads are F -> E1 -> V -> E2, each side is shared with
+                // the quad of a neighboring corner
+                let (vertex, opposite) = match slot {
+                    0 => (corner.face_vertex, corner.prev * 4 + 4),
+                    1 => {
+                        let opposite_corner = halfedge_corners[halfedge.opposite];
+                        (corner.edge_vertex, corners[opposite_corner].prev * 4 + 3)
+                    },
+                    2 => {
+                        let next = input.halfedge(halfedge.next).unwrap();
+                        (corner.corner_vertex, halfedge_corners[next.opposite] * 4 + 2)
+                    },
+                    _ => (corner.next_edge_vertex, halfedge_corners[halfedge.next] * 4 + 1),
+                };
+                (vertex as u32, opposite as u32)
+            })
+            .unzip_into_vecs(&mut output.halfedges.vertex, &mut output.halfedges.opposite);
+        (0..halfedge_count)
+            .into_par_iter()
+            .map(|i| (i / 4 + 1) as u32)
+            .collect_into_vec(&mut output.halfedges.face);
+        (0..halfedge_count)
+            .into_par_iter()
+            .map(|i| (i / 4 * 4 + (i + 3) % 4 + 1) as u32)
+            .collect_into_vec(&mut output.halfedges.prev);
+        (0..halfedge_count)
+            .into_par_iter()
+            .map(|i| (i / 4 * 4 + (i + 1) % 4 + 1) as u32)
+            .collect_into_vec(&mut output.halfedges.next);
+        output.halfedges.alive = vec![true; halfedge_count];
+
+        for (index, &vertex) in output.halfedges.vertex.iter().enumerate() {
+            output.vertices.halfedges[vertex as usize - 1].push(index as u32 + 1);
+        }
+        output.vertex_count = output.vertices.len();
+        output.face_count = output.faces.len();
+        output.halfedge_count = output.halfedges.len();
+        output
+    }
+
     /// Helps to reduce the syntax noise when a Self is available. Splits Self
     /// into multiple mutable borrows.
-    fn edge_data_mut(&mut self, halfedge_id: Id) -> &EdgeData {
+    fn edge_data_mut(&mut self, halfedge_id: Id) -> &EdgeData<S> {
         edge_data_mut(
             &self.input,
             halfedge_id,
@@ -265,10 +513,10 @@ impl<'a> CatmullClarkSubdivider<'a> {
         }
         self.tmp_avg_of_faces.clear();
         self.tmp_avg_of_edge_mids.clear();
-        let vertex = self.input.vertex(vertex_id).unwrap();
-        for halfedge_id in vertex.halfedges.iter() {
-            let halfedge_face_id =
-                self.input.halfedge(*halfedge_id).unwrap().face;
+        let input = self.input;
+        for &halfedge_id in input.vertices.halfedges[vertex_id - 1].iter() {
+            let halfedge_id = halfedge_id as Id;
+            let halfedge_face_id = input.halfedge_face_id(halfedge_id).unwrap();
             self.tmp_avg_of_faces.push(
                 face_data_mut(
                     &self.input,
@@ -280,19 +528,18 @@ impl<'a> CatmullClarkSubdivider<'a> {
             self.tmp_avg_of_edge_mids.push(
                 edge_data_mut(
                     &self.input,
-                    *halfedge_id,
+                    halfedge_id,
                     &mut self.face_data_set,
                     &mut self.edge_data_set,
                     &mut self.output,
                 ).mid_point,
             );
         }
-        let barycenter = Point3::centroid(&self.tmp_avg_of_faces);
-        let average_of_edge = Point3::centroid(&self.tmp_avg_of_edge_mids);
-        let position = (((average_of_edge * 2.0) + barycenter.to_vec())
-            + (vertex.position.to_vec()
-                * ((self.tmp_avg_of_faces.len() as i32 - 3).abs() as f32)))
-            / (self.tmp_avg_of_faces.len() as f32);
+        let position = vertex_point(
+            input.vertex_position(vertex_id).unwrap(),
+            &self.tmp_avg_of_faces,
+            &self.tmp_avg_of_edge_mids,
+        );
         let mut data = VertexData::new();
         data.generated_vertex_id = self.output.add_vertex(position);
         self.vertex_data_set[vertex_id] = Some(data);
@@ -304,7 +551,7 @@ pub trait Subdivide {
     fn subdivide(&self) -> Self;
 }
 
-impl Subdivide for Mesh {
+impl<S: BaseFloat> Subdivide for Mesh<S> {
     fn subdivide(&self) -> Self {
         CatmullClarkSubdivider::new(self).generate()
     }
@@ -1,3 +1,4 @@
+use cgmath::BaseFloat;
 use iterator::FaceHalfedgeIterator;
 use iterator::FaceIterator;
 use mesh::Id;
@@ -44,11 +45,10 @@ struct TriangulationPrediction {
     vertex_count: usize,
     triangle_count: usize,
     halfedge_count: usize,
-    edge_count: usize,
 }
 
 impl TriangulationPrediction {
-    pub fn new(input: &Mesh) -> Self {
+    pub fn new<S: BaseFloat>(input: &Mesh<S>) -> Self {
         let mut pec = PolygonEdgeCounter::new();
         for face_id in FaceIterator::new(input) {
             let halfedge = input.face_first_halfedge_id(face_id).unwrap();
@@ -60,21 +60,19 @@ impl TriangulationPrediction {
         Self {
             triangle_count,
             halfedge_count,
-            edge_count: halfedge_count / 2,
             vertex_count: input.vertex_count,
         }
     }
 }
 
-impl Triangulate for Mesh {
+impl<S: BaseFloat> Triangulate for Mesh<S> {
     /// Triangulate without knowing stats about the input mesh.
     fn triangulate(&self) -> Self {
         let tp = TriangulationPrediction::new(self);
-        let mut tri_mesh = Mesh::new();
+        let mut tri_mesh = Mesh::default();
         tri_mesh.vertices.reserve(tp.vertex_count);
         tri_mesh.faces.reserve(tp.triangle_count);
         tri_mesh.halfedges.reserve(tp.halfedge_count);
-        tri_mesh.edges.reserve(tp.edge_count);
         let mut new_vertices: Vec<Option<Id>> =
             vec![None; self.vertices.len() + 1];
         let mut tri_faces = Vec::new();
@@ -87,12 +85,14 @@ impl Triangulate for Mesh {
             for halfedge_id in
                 FaceHalfedgeIterator::new(self, first_halfedge_id)
             {
-                let vertex = self.halfedge_start_vertex(halfedge_id).unwrap();
-                let new_vert = &mut new_vertices[vertex.id];
+                let vertex_id =
+                    self.halfedge_start_vertex_id(halfedge_id).unwrap();
+                let new_vert = &mut new_vertices[vertex_id];
                 if new_vert.is_none() {
-                    *new_vert = Some(tri_mesh.add_vertex(vertex.position));
+                    let position = self.vertex_position(vertex_id).unwrap();
+                    *new_vert = Some(tri_mesh.add_vertex(position));
                 }
-                vertices.push(vertex.id);
+                vertices.push(vertex_id);
             }
             if vertices.len() > 3 {
                 let direct = self.face_norm(face_id);
@@ -103,25 +103,26 @@ impl Triangulate for Mesh {
                         let enter = vertices[i];
                         let cone = vertices[i_next];
                         let leave = vertices[(i + 2) % vertices.len()];
-                        let cone_v = self.vertex(cone).unwrap();
-                        let enter_v = self.vertex(enter).unwrap();
-                        let leave_v = self.vertex(leave).unwrap();
+                        let cone_position = self.vertex_position(cone).unwrap();
+                        let enter_position =
+                            self.vertex_position(enter).unwrap();
+                        let leave_position =
+                            self.vertex_position(leave).unwrap();
                         let angle = angle360(
-                            cone_v.position - enter_v.position,
-                            leave_v.position - cone_v.position,
+                            cone_position - enter_position,
+                            leave_position - cone_position,
                             direct,
                         );
-                        if angle >= 1.0 && angle <= 179.0 {
+                        if angle >= S::one() && angle <= scalar(179.0) {
                             let mut is_ear = true;
                             for j in 0..(vertices.len() - 3) {
                                 let fourth =
                                     vertices[(i + 3 + j) % vertices.len()];
-                                let fourth_v = self.vertex(fourth).unwrap();
                                 if point_in_triangle(
-                                    enter_v.position,
-                                    cone_v.position,
-                                    leave_v.position,
-                                    fourth_v.position,
+                                    enter_position,
+                                    cone_position,
+                                    leave_position,
+                                    self.vertex_position(fourth).unwrap(),
                                 ) {
                                     is_ear = false;
                                     break;
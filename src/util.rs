@@ -1,3 +1,4 @@
+use cgmath::BaseFloat;
 use cgmath::Point3;
 use cgmath::Vector3;
 use cgmath::prelude::*;
@@ -25,48 +26,54 @@ Dot     Angle
 Source: http://chortle.ccsu.edu/vectorlessons/vch09/vch09_6.html
  */
 
-pub fn norm(p1: Point3<f32>, p2: Point3<f32>, p3: Point3<f32>) -> Vector3<f32> {
+/// Converts an f32 constant to the scalar type of the geometry. For f64 the
+/// value keeps the rounding of the f32 constant.
+pub fn scalar<S: BaseFloat>(value: f32) -> S {
+    S::from(value).unwrap()
+}
+
+pub fn norm<S: BaseFloat>(p1: Point3<S>, p2: Point3<S>, p3: Point3<S>) -> Vector3<S> {
     let side1 = p2 - p1;
     let side2 = p3 - p1;
     let perp = side1.cross(side2);
     perp.normalize()
 }
 
-pub fn almost_eq(v1: Vector3<f32>, v2: Vector3<f32>) -> bool {
-    (v1.x - v2.x).abs() <= 0.01 &&
-        (v1.y - v2.y).abs() <= 0.01 &&
-        (v1.z - v2.z).abs() <= 0.01
+pub fn almost_eq<S: BaseFloat>(v1: Vector3<S>, v2: Vector3<S>) -> bool {
+    (v1.x - v2.x).abs() <= scalar(0.01) &&
+        (v1.y - v2.y).abs() <= scalar(0.01) &&
+        (v1.z - v2.z).abs() <= scalar(0.01)
 }
 
 // Modifed from Reza Nourai's C# version: PointInTriangle
 // https://blogs.msdn.microsoft.com/rezanour/2011/08/07/barycentric-coordinates-and-point-in-triangle-tests/
-pub fn point_in_triangle(a: Point3<f32>, b: Point3<f32>, c: Point3<f32>, p: Point3<f32>) -> bool {
+pub fn point_in_triangle<S: BaseFloat>(a: Point3<S>, b: Point3<S>, c: Point3<S>, p: Point3<S>) -> bool {
     let u = b - a;
     let v = c - a;
     let w = p - a;
     let v_cross_w = v.cross(w);
     let v_cross_u = v.cross(u);
-    if v_cross_w.dot(v_cross_u) < 0.0 {
+    if v_cross_w.dot(v_cross_u) < S::zero() {
         return false;
     }
     let u_cross_w = u.cross(w);
     let u_cross_v = u.cross(v);
-    if u_cross_w.dot(u_cross_v) < 0.0 {
+    if u_cross_w.dot(u_cross_v) < S::zero() {
         return false;
     }
     let denom = u_cross_v.magnitude();
     let r = v_cross_w.magnitude() / denom;
     let t = u_cross_w.magnitude() / denom;
-    r + t <= 1.0
+    r + t <= S::one()
 }
 
 // Modified from Cyranose's answer
 // https://www.opengl.org/discussion_boards/showthread.php/159385-Deriving-angles-from-0-to-360-from-Dot-Product
-pub fn angle360(a: Vector3<f32>, b: Vector3<f32>, direct: Vector3<f32>) -> f32 {
+pub fn angle360<S: BaseFloat>(a: Vector3<S>, b: Vector3<S>, direct: Vector3<S>) -> S {
     let angle = Rad::acos(a.dot(b));
     let c = a.cross(b);
-    if c.dot(direct) < 0.0 {
-        180.0 + Deg::from(angle).0
+    if c.dot(direct) < S::zero() {
+        Deg::from(angle).0 + scalar(180.0)
     } else {
         Deg::from(angle).0
     }
@@ -80,12 +87,12 @@ pub enum PointSide {
     Coincident
 }
 
-pub fn point_side_on_plane(pt: Point3<f32>, pt_on_plane: Point3<f32>, norm: Vector3<f32>) -> PointSide {
+pub fn point_side_on_plane<S: BaseFloat>(pt: Point3<S>, pt_on_plane: Point3<S>, norm: Vector3<S>) -> PointSide {
     let line = pt - pt_on_plane;
     let dot = line.dot(norm);
-    if dot > 0.0 {
+    if dot > S::zero() {
         PointSide::Front
-    } else if dot < 0.0 {
+    } else if dot < S::zero() {
         PointSide::Back
     } else {
         PointSide::Coincident
@@ -94,38 +101,38 @@ pub fn point_side_on_plane(pt: Point3<f32>, pt_on_plane: Point3<f32>, norm: Vect
 
 #[derive(PartialEq)]
 #[derive(Debug)]
-pub enum SegmentPlaneIntersect {
+pub enum SegmentPlaneIntersect<S = f32> {
     NoIntersection,
     Parallel,
     LiesIn,
-    Intersection(Point3<f32>),
+    Intersection(Point3<S>),
 }
 
 pub const SMALL_NUM : f32 = 0.00000001;
 
 // Modfied from the C++ version intersect3D_SegmentPlane
 // http://geomalgorithms.com/a05-_intersect-1.html
-pub fn intersect_of_segment_and_plane(p0: Point3<f32>, p1: Point3<f32>, pt_on_plane: Point3<f32>, norm: Vector3<f32>) -> SegmentPlaneIntersect {
+pub fn intersect_of_segment_and_plane<S: BaseFloat>(p0: Point3<S>, p1: Point3<S>, pt_on_plane: Point3<S>, norm: Vector3<S>) -> SegmentPlaneIntersect<S> {
     let u = p1 - p0;
     let w = p0 - pt_on_plane;
     let d = norm.dot(u);
     let n = -norm.dot(w);
-    if d.abs() < SMALL_NUM {
-        if n == 0.0 {
+    if d.abs() < scalar(SMALL_NUM) {
+        if n == S::zero() {
             return SegmentPlaneIntersect::LiesIn;
         }
         return SegmentPlaneIntersect::Parallel;
     }
     let s_i = n / d;
-    if s_i < 0.0 || s_i > 1.0 || s_i.is_nan() || s_i.is_infinite() {
+    if s_i < S::zero() || s_i > S::one() || s_i.is_nan() || s_i.is_infinite() {
         return SegmentPlaneIntersect::NoIntersection;
     }
-    SegmentPlaneIntersect::Intersection(p0 + (s_i * u))
+    SegmentPlaneIntersect::Intersection(p0 + (u * s_i))
 }
 
 // Modified from intersectRayWithSquare
 // https://stackoverflow.com/questions/21114796/3d-ray-quad-intersection-test-in-java
-pub fn is_segment_and_quad_intersect(p0: Point3<f32>, p1: Point3<f32>, quad: &Vec<Point3<f32>>) -> bool {
+pub fn is_segment_and_quad_intersect<S: BaseFloat>(p0: Point3<S>, p1: Point3<S>, quad: &Vec<Point3<S>>) -> bool {
     let r1 = p0;
     let r2 = p1;
     let s1 = quad[0];
@@ -136,7 +143,7 @@ pub fn is_segment_and_quad_intersect(p0: Point3<f32>, p1: Point3<f32>, quad: &Ve
     let n = ds21.cross(ds31);
     let dr = r1 - r2;
     let ndotdr = n.dot(dr);
-    if ndotdr.abs() < SMALL_NUM {
+    if ndotdr.abs() < scalar(SMALL_NUM) {
         return false;
     }
     let t = -n.dot(r1 - s1) / ndotdr;
@@ -144,10 +151,10 @@ pub fn is_segment_and_quad_intersect(p0: Point3<f32>, p1: Point3<f32>, quad: &Ve
     let dms1 = m - s1;
     let u = dms1.dot(ds21);
     let v = dms1.dot(ds31);
-    u >= 0.0 && u <= ds21.dot(ds21) && v >= 0.0 && v <= ds31.dot(ds31)
+    u >= S::zero() && u <= ds21.dot(ds21) && v >= S::zero() && v <= ds31.dot(ds31)
 }
 
-pub fn is_two_quads_intersect(first_quad: &Vec<Point3<f32>>, second_quad: &Vec<Point3<f32>>) -> bool {
+pub fn is_two_quads_intersect<S: BaseFloat>(first_quad: &Vec<Point3<S>>, second_quad: &Vec<Point3<S>>) -> bool {
     for i in 0..second_quad.len() {
         if is_segment_and_quad_intersect(second_quad[i], second_quad[(i + 1) % second_quad.len()], first_quad) {
             return true;
@@ -161,11 +168,11 @@ pub fn is_two_quads_intersect(first_quad: &Vec<Point3<f32>>, second_quad: &Vec<P
     false
 }
 
-pub fn is_point_on_segment(point: Point3<f32>, seg_begin: Point3<f32>, seg_end: Point3<f32>) -> bool {
+pub fn is_point_on_segment<S: BaseFloat>(point: Point3<S>, seg_begin: Point3<S>, seg_end: Point3<S>) -> bool {
     let v = seg_end - seg_begin;
     let w = point - seg_begin;
     let w_dot_v = w.dot(v);
-    if w_dot_v <= 0.0 {
+    if w_dot_v <= S::zero() {
         return false;
     }
     let v_dot_v = v.dot(v);
@@ -174,19 +181,19 @@ pub fn is_point_on_segment(point: Point3<f32>, seg_begin: Point3<f32>, seg_end:
     }
     let t = seg_begin + (v * (w_dot_v / v_dot_v));
     let dist = t.distance(point);
-    dist <= 0.00001
+    dist <= scalar(0.00001)
 }
 
-pub fn is_valid_norm(norm: Vector3<f32>) -> bool {
+pub fn is_valid_norm<S: BaseFloat>(norm: Vector3<S>) -> bool {
     !norm.x.is_nan() && !norm.y.is_nan() && !norm.z.is_nan()
 }
 
-pub fn pick_base_plane_norm(directs: Vec<Vector3<f32>>, positions: Vec<Point3<f32>>, weights: Vec<f32>) -> Option<Vector3<f32>> {
+pub fn pick_base_plane_norm<S: BaseFloat>(directs: Vec<Vector3<S>>, positions: Vec<Point3<S>>, weights: Vec<S>) -> Option<Vector3<S>> {
     if directs.len() <= 1 {
         None
     } else if directs.len() <= 2 {
         // >=15 degrees && <= 165 degrees
-        if directs[0].dot(directs[1]).abs() < 0.966 {
+        if directs[0].dot(directs[1]).abs() < scalar(0.966) {
             return Some(directs[0].cross(directs[1]).normalize())
         }
         None
@@ -196,11 +203,11 @@ pub fn pick_base_plane_norm(directs: Vec<Vector3<f32>>, positions: Vec<Point3<f3
             return Some(norm.normalize());
         }
         // >=15 degrees && <= 165 degrees
-        if directs[0].dot(directs[1]).abs() < 0.966 {
+        if directs[0].dot(directs[1]).abs() < scalar(0.966) {
             return Some(directs[0].cross(directs[1]).normalize())
-        } else if directs[1].dot(directs[2]).abs() < 0.966 {
+        } else if directs[1].dot(directs[2]).abs() < scalar(0.966) {
             return Some(directs[1].cross(directs[2]).normalize())
-        } else if directs[2].dot(directs[0]).abs() < 0.966 {
+        } else if directs[2].dot(directs[0]).abs() < scalar(0.966) {
             return Some(directs[2].cross(directs[0]).normalize())
         } else {
             None
@@ -208,7 +215,7 @@ pub fn pick_base_plane_norm(directs: Vec<Vector3<f32>>, positions: Vec<Point3<f3
     } else {
         let mut weighted_indices : Vec<(usize, usize)> = Vec::new();
         for i in 0..weights.len() {
-            weighted_indices.push((i, (weights[i] * 100.0) as usize));
+            weighted_indices.push((i, (weights[i] * scalar(100.0)).to_usize().unwrap_or(0)));
         }
         weighted_indices.sort_by(|a, b| b.1.cmp(&a.1));
         let i0 = weighted_indices[0].0;
@@ -219,11 +226,11 @@ pub fn pick_base_plane_norm(directs: Vec<Vector3<f32>>, positions: Vec<Point3<f3
             return Some(norm.normalize());
         }
         // >=15 degrees && <= 165 degrees
-        if directs[i0].dot(directs[i1]).abs() < 0.966 {
+        if directs[i0].dot(directs[i1]).abs() < scalar(0.966) {
             return Some(directs[i0].cross(directs[i1]).normalize())
-        } else if directs[i1].dot(directs[i2]).abs() < 0.966 {
+        } else if directs[i1].dot(directs[i2]).abs() < scalar(0.966) {
             return Some(directs[i1].cross(directs[i2]).normalize())
-        } else if directs[i2].dot(directs[i0]).abs() < 0.966 {
+        } else if directs[i2].dot(directs[i0]).abs() < scalar(0.966) {
             return Some(directs[i2].cross(directs[i0]).normalize())
         } else {
             None
@@ -231,22 +238,22 @@ pub fn pick_base_plane_norm(directs: Vec<Vector3<f32>>, positions: Vec<Point3<f3
     }
 }
 
-pub fn world_perp(direct: Vector3<f32>) -> Vector3<f32> {
-    const WORLD_Y_AXIS : Vector3<f32> = Vector3 {x: 0.0, y: 1.0, z: 0.0};
-    const WORLD_X_AXIS : Vector3<f32> = Vector3 {x: 1.0, y: 0.0, z: 0.0};
-    if direct.dot(WORLD_X_AXIS).abs() > 0.707 {
+pub fn world_perp<S: BaseFloat>(direct: Vector3<S>) -> Vector3<S> {
+    let world_y_axis : Vector3<S> = Vector3::unit_y();
+    let world_x_axis : Vector3<S> = Vector3::unit_x();
+    if direct.dot(world_x_axis).abs() > scalar(0.707) {
         // horizontal
         //println!("switch to WORLD_Y_AXIS");
-        direct.cross(WORLD_Y_AXIS)
+        direct.cross(world_y_axis)
     } else {
         // vertical
         //println!("switch to WORLD_X_AXIS");
-        direct.cross(WORLD_X_AXIS)
+        direct.cross(world_x_axis)
     }
 }
 
-pub fn calculate_deform_position(vert_position: Point3<f32>, vert_ray: Vector3<f32>, deform_norm: Vector3<f32>, deform_factor: f32) -> Point3<f32> {
-    let revised_norm = if vert_ray.dot(deform_norm) < 0.0 {
+pub fn calculate_deform_position<S: BaseFloat>(vert_position: Point3<S>, vert_ray: Vector3<S>, deform_norm: Vector3<S>, deform_factor: S) -> Point3<S> {
+    let revised_norm = if vert_ray.dot(deform_norm) < S::zero() {
         -deform_norm
     } else {
         deform_norm
@@ -258,14 +265,14 @@ pub fn calculate_deform_position(vert_position: Point3<f32>, vert_ray: Vector3<f
     Point3 {x: scaled_vert_ray.x, y: scaled_vert_ray.y, z: scaled_vert_ray.z}
 }
 
-pub fn make_quad(position: Point3<f32>, direct: Vector3<f32>, radius: f32, base_norm: Vector3<f32>) -> Vec<Point3<f32>> {
+pub fn make_quad<S: BaseFloat>(position: Point3<S>, direct: Vector3<S>, radius: S, base_norm: Vector3<S>) -> Vec<Point3<S>> {
     let direct_normalized = direct.normalize();
     let base_norm_normalized = base_norm.normalize();
     let dot = direct_normalized.dot(base_norm);
     //println!("make_quad begin dot:{:?} direct:{:?}(normalize:{:?}) base_norm:{:?}(normalize:{:?})", 
     //    dot, direct, direct_normalized, base_norm, base_norm_normalized);
     let oriented_base_norm = {
-        if dot > 0.0 {
+        if dot > S::zero() {
             base_norm_normalized
         } else {
             //println!("base_norm reversed");
@@ -273,7 +280,7 @@ pub fn make_quad(position: Point3<f32>, direct: Vector3<f32>, radius: f32, base_
         }
     };
     let u = {
-        if direct_normalized.dot(oriented_base_norm).abs() > 0.707 {
+        if direct_normalized.dot(oriented_base_norm).abs() > scalar(0.707) {
             // same direction with < 45 deg
             //println!("< 45 deg");
             let switched_base_norm = world_perp(oriented_base_norm);
@@ -298,13 +305,13 @@ pub fn make_quad(position: Point3<f32>, direct: Vector3<f32>, radius: f32, base_
     f
 }
 
-pub fn pick_most_not_obvious_vertex(vertices: Vec<Point3<f32>>) -> usize {
+pub fn pick_most_not_obvious_vertex<S: BaseFloat>(vertices: Vec<Point3<S>>) -> usize {
     if vertices.len() <= 1 {
         return 0;
     }
     let mut choosen_index = 0;
     let mut choosen_x = vertices[0].x;
-    let pick_max = choosen_x < 0.0;
+    let pick_max = choosen_x < S::zero();
     for i in 1..vertices.len() {
         let x = vertices[i].x;
         if pick_max {
@@ -321,3 +328,90 @@ pub fn pick_most_not_obvious_vertex(vertices: Vec<Point3<f32>>) -> usize {
     }
     choosen_index
 }
+
+// Modified from Christer Ericson's ClosestPtPointTriangle
+// Real-Time Collision Detection, 5.1.5 Closest Point on Triangle to Point
+pub fn closest_point_on_triangle<S: BaseFloat>(p: Point3<S>, a: Point3<S>, b: Point3<S>, c: Point3<S>) -> Point3<S> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= S::zero() && d2 <= S::zero() {
+        return a;
+    }
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= S::zero() && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= S::zero() && d1 >= S::zero() && d3 <= S::zero() {
+        let v = d1 / (d1 - d3);
+        return a + ab * v;
+    }
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= S::zero() && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= S::zero() && d2 >= S::zero() && d6 <= S::zero() {
+        let w = d2 / (d2 - d6);
+        return a + ac * w;
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= S::zero() && (d4 - d3) >= S::zero() && (d5 - d6) >= S::zero() {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return b + (c - b) * w;
+    }
+    let denom = S::one() / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+    a + ab * v + ac * w
+}
+
+// Signed solid angle of the triangle seen from p, by Van Oosterom and Strackee
+// https://en.wikipedia.org/wiki/Solid_angle#Tetrahedron
+pub fn triangle_solid_angle<S: BaseFloat>(p: Point3<S>, a: Point3<S>, b: Point3<S>, c: Point3<S>) -> S {
+    let va = a - p;
+    let vb = b - p;
+    let vc = c - p;
+    let la = va.magnitude();
+    let lb = vb.magnitude();
+    let lc = vc.magnitude();
+    let numerator = va.dot(vb.cross(vc));
+    let denominator = la * lb * lc + va.dot(vb) * lc + vb.dot(vc) * la + vc.dot(va) * lb;
+    numerator.atan2(denominator) * scalar(2.0)
+}
+
+// Modified from the Möller–Trumbore ray-triangle intersection algorithm
+// https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+pub fn intersect_of_segment_and_triangle<S: BaseFloat>(p0: Point3<S>, p1: Point3<S>, a: Point3<S>, b: Point3<S>, c: Point3<S>) -> Option<Point3<S>> {
+    let dir = p1 - p0;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let h = dir.cross(edge2);
+    let det = edge1.dot(h);
+    if det.abs() < scalar(SMALL_NUM) {
+        return None;
+    }
+    let inv_det = S::one() / det;
+    let s = p0 - a;
+    let u = inv_det * s.dot(h);
+    if !(S::zero()..=S::one()).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = inv_det * dir.dot(q);
+    if v < S::zero() || u + v > S::one() {
+        return None;
+    }
+    let t = inv_det * edge2.dot(q);
+    if !(S::zero()..=S::one()).contains(&t) {
+        return None;
+    }
+    Some(p0 + dir * t)
+}
@@ -0,0 +1,339 @@
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use std::cmp::Ordering;
+
+/// Size of the LRU cache modeled by the Forsyth optimizer. Scores of vertices
+/// beyond it drop to zero, so it only has to be at least as big as the cache
+/// of the target GPU.
+const FORSYTH_CACHE_SIZE: usize = 32;
+
+// Scoring constants from Tom Forsyth's "Linear-Speed Vertex Cache
+// Optimisation".
+const FORSYTH_CACHE_DECAY_POWER: f32 = 1.5;
+const FORSYTH_LAST_TRIANGLE_SCORE: f32 = 0.75;
+const FORSYTH_VALENCE_BOOST_SCALE: f32 = 2.0;
+const FORSYTH_VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Size of the FIFO cache simulated to find the cluster boundaries of
+/// optimize_overdraw.
+const OVERDRAW_CACHE_SIZE: usize = 16;
+
+/// Post transform cache efficiency of a triangle list, measured by
+/// simulating a FIFO cache.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexCacheStats {
+    /// Number of vertices transformed, counting every cache miss.
+    pub transformed_vertices: usize,
+
+    /// Average cache miss ratio: transformed vertices per triangle. Lies
+    /// between 0.5 and 3 for closed meshes, lower is better.
+    pub acmr: f32,
+
+    /// Average transform to vertex ratio: transformed vertices per referenced
+    /// vertex. 1 is the optimum, where every vertex is transformed once.
+    pub atvr: f32,
+}
+
+/// Simulates a FIFO cache of cache_size entries over the triangle list.
+pub fn vertex_cache_stats(indices: &[u32], vertex_count: usize, cache_size: usize) -> VertexCacheStats {
+    let mut cached_at = vec![None; vertex_count];
+    let mut referenced = vec![false; vertex_count];
+    let mut transformed_vertices = 0;
+    for &index in indices {
+        let index = index as usize;
+        referenced[index] = true;
+        let hit = match cached_at[index] {
+            Some(time) => transformed_vertices - time < cache_size,
+            None => false,
+        };
+        if !hit {
+            cached_at[index] = Some(transformed_vertices);
+            transformed_vertices += 1;
+        }
+    }
+    let triangle_count = indices.len() / 3;
+    let referenced_count = referenced.iter().filter(|&&used| used).count();
+    VertexCacheStats {
+        transformed_vertices: transformed_vertices,
+        acmr: if triangle_count > 0 { transformed_vertices as f32 / triangle_count as f32 } else { 0.0 },
+        atvr: if referenced_count > 0 { transformed_vertices as f32 / referenced_count as f32 } else { 0.0 },
+    }
+}
+
+fn forsyth_vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if 0 == remaining_triangles {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => FORSYTH_LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (FORSYTH_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(FORSYTH_CACHE_DECAY_POWER)
+        },
+    };
+    cache_score + FORSYTH_VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-FORSYTH_VALENCE_BOOST_POWER)
+}
+
+/// Reorders the triangles of a triangle list for the post transform vertex
+/// cache, with Tom Forsyth's greedy algorithm. The result holds the same
+/// triangles with the same winding, only their order changes.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+
+    // Triangles around each vertex, the first remaining_triangles[v] entries
+    // of each run are the ones not emitted yet.
+    let mut remaining_triangles = vec![0; vertex_count];
+    for &index in &indices[..triangle_count * 3] {
+        remaining_triangles[index as usize] += 1;
+    }
+    let mut first_triangle = vec![0; vertex_count + 1];
+    for vertex in 0..vertex_count {
+        first_triangle[vertex + 1] = first_triangle[vertex] + remaining_triangles[vertex];
+    }
+    let mut vertex_triangles = vec![0; triangle_count * 3];
+    let mut filled = first_triangle.clone();
+    for triangle in 0..triangle_count {
+        for &index in &indices[triangle * 3..triangle * 3 + 3] {
+            vertex_triangles[filled[index as usize]] = triangle;
+            filled[index as usize] += 1;
+        }
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count).map(|vertex| forsyth_vertex_score(None, remaining_triangles[vertex])).collect();
+    let mut triangle_scores: Vec<f32> = (0..triangle_count).map(|triangle| {
+        indices[triangle * 3..triangle * 3 + 3].iter().map(|&index| vertex_scores[index as usize]).sum()
+    }).collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<usize> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut new_cache: Vec<usize> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut best_triangle = None;
+    let mut search_start = 0;
+
+    while output.len() < triangle_count * 3 {
+        // Nothing around the cache is left, restart from the best of the
+        // remaining triangles.
+        let triangle = match best_triangle {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[search_start] {
+                    search_start += 1;
+                }
+                let mut best = search_start;
+                for candidate in search_start + 1..triangle_count {
+                    if !emitted[candidate] && triangle_scores[candidate] > triangle_scores[best] {
+                        best = candidate;
+                    }
+                }
+                best
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(corners);
+
+        new_cache.clear();
+        for &index in corners {
+            let vertex = index as usize;
+            let start = first_triangle[vertex];
+            let end = start + remaining_triangles[vertex];
+            let position = (start..end).find(|&i| vertex_triangles[i] == triangle).unwrap();
+            vertex_triangles.swap(position, end - 1);
+            remaining_triangles[vertex] -= 1;
+            new_cache.push(vertex);
+        }
+        for &vertex in &cache {
+            if !corners.contains(&(vertex as u32)) {
+                new_cache.push(vertex);
+            }
+        }
+        for &vertex in new_cache.iter().skip(FORSYTH_CACHE_SIZE) {
+            cache_position[vertex] = None;
+        }
+        new_cache.truncate(FORSYTH_CACHE_SIZE);
+        for (position, &vertex) in new_cache.iter().enumerate() {
+            cache_position[vertex] = Some(position);
+        }
+        for &vertex in cache.iter().chain(new_cache.iter()) {
+            vertex_scores[vertex] = forsyth_vertex_score(cache_position[vertex], remaining_triangles[vertex]);
+        }
+
+        best_triangle = None;
+        let mut best_score = -1.0;
+        for &vertex in &new_cache {
+            let start = first_triangle[vertex];
+            for &candidate in &vertex_triangles[start..start + remaining_triangles[vertex]] {
+                let score = indices[candidate * 3..candidate * 3 + 3].iter().map(|&index| vertex_scores[index as usize]).sum();
+                triangle_scores[candidate] = score;
+                if score > best_score {
+                    best_score = score;
+                    best_triangle = Some(candidate);
+                }
+            }
+        }
+        ::std::mem::swap(&mut cache, &mut new_cache);
+    }
+    output
+}
+
+/// One run of consecutive triangles, which optimize_overdraw moves as a
+/// whole.
+struct Cluster {
+    start: usize,
+    end: usize,
+    sort_key: f32,
+}
+
+fn triangle_position(vertices: &[f32], stride: usize, index: u32) -> Vector3<f32> {
+    let offset = index as usize * stride;
+    Vector3::new(vertices[offset], vertices[offset + 1], vertices[offset + 2])
+}
+
+/// Splits the triangles into clusters, a new cluster starts where all the
+/// vertices of a triangle miss the cache, and inside those where the cache
+/// misses of the cluster so far are within threshold times the average of
+/// the surrounding cluster.
+fn overdraw_clusters(indices: &[u32], vertex_count: usize, threshold: f32) -> Vec<(usize, usize)> {
+    let triangle_count = indices.len() / 3;
+    let mut hard_boundaries = Vec::new();
+    let mut cached_at = vec![None; vertex_count];
+    let mut transformed : usize = 0;
+    let mut triangle_misses = Vec::with_capacity(triangle_count);
+    for triangle in 0..triangle_count {
+        let mut misses = 0;
+        for &index in &indices[triangle * 3..triangle * 3 + 3] {
+            let index = index as usize;
+            let hit = match cached_at[index] {
+                Some(time) => transformed - time < OVERDRAW_CACHE_SIZE,
+                None => false,
+            };
+            if !hit {
+                cached_at[index] = Some(transformed);
+                transformed += 1;
+                misses += 1;
+            }
+        }
+        if 3 == misses {
+            hard_boundaries.push(triangle);
+        }
+        triangle_misses.push(misses);
+    }
+    hard_boundaries.push(triangle_count);
+
+    let mut clusters = Vec::new();
+    for window in hard_boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let cluster_misses : usize = triangle_misses[start..end].iter().sum();
+        let cluster_threshold = threshold * cluster_misses as f32 / (end - start) as f32;
+        // Each soft cluster starts with a cold cache once the clusters are
+        // sorted, so its misses are simulated again from its start.
+        let mut cache: Vec<u32> = Vec::with_capacity(OVERDRAW_CACHE_SIZE);
+        let mut soft_start = start;
+        let mut misses = 0;
+        for triangle in start..end {
+            for &index in &indices[triangle * 3..triangle * 3 + 3] {
+                if !cache.contains(&index) {
+                    if cache.len() == OVERDRAW_CACHE_SIZE {
+                        cache.remove(0);
+                    }
+                    cache.push(index);
+                    misses += 1;
+                }
+            }
+            if triangle + 1 < end && misses as f32 / (triangle + 1 - soft_start) as f32 <= cluster_threshold {
+                clusters.push((soft_start, triangle + 1));
+                soft_start = triangle + 1;
+                misses = 0;
+                cache.clear();
+            }
+        }
+        clusters.push((soft_start, end));
+    }
+    clusters
+}
+
+/// Reorders the clusters of a vertex cache optimized triangle list to reduce
+/// overdraw from any view direction, after Sander et al. "Fast Triangle
+/// Reordering for Vertex Locality and Reduced Overdraw". Clusters facing
+/// away from the center of the mesh are drawn first, as they tend to occlude
+/// the others. The threshold (usually 1.05) bounds how much worse the vertex
+/// cache efficiency may get, splitting into more clusters gives the sort more
+/// freedom. Positions are the first three floats of each vertex.
+pub fn optimize_overdraw(indices: &[u32], vertices: &[f32], stride: usize, threshold: f32) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let mut triangle_areas = Vec::with_capacity(triangle_count);
+    let mut triangle_centroids = Vec::with_capacity(triangle_count);
+    let mut triangle_norms = Vec::with_capacity(triangle_count);
+    let mut mesh_centroid = Vector3::zero();
+    let mut mesh_area = 0.0;
+    for triangle in 0..triangle_count {
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        let p0 = triangle_position(vertices, stride, corners[0]);
+        let p1 = triangle_position(vertices, stride, corners[1]);
+        let p2 = triangle_position(vertices, stride, corners[2]);
+        let cross = (p1 - p0).cross(p2 - p0);
+        let area = cross.magnitude() * 0.5;
+        let centroid = (p0 + p1 + p2) / 3.0;
+        mesh_centroid += centroid * area;
+        mesh_area += area;
+        triangle_areas.push(area);
+        triangle_centroids.push(centroid);
+        triangle_norms.push(cross);
+    }
+    if mesh_area > 0.0 {
+        mesh_centroid /= mesh_area;
+    }
+
+    let mut clusters : Vec<Cluster> = overdraw_clusters(indices, vertices.len() / stride, threshold).into_iter().map(|(start, end)| {
+        let mut centroid = Vector3::zero();
+        let mut norm = Vector3::zero();
+        let mut area = 0.0;
+        for triangle in start..end {
+            centroid += triangle_centroids[triangle] * triangle_areas[triangle];
+            norm += triangle_norms[triangle];
+            area += triangle_areas[triangle];
+        }
+        let sort_key = if area > 0.0 && norm.magnitude2() > 0.0 {
+            (centroid / area - mesh_centroid).dot(norm.normalize())
+        } else {
+            0.0
+        };
+        Cluster {
+            start: start,
+            end: end,
+            sort_key: sort_key,
+        }
+    }).collect();
+    clusters.sort_by(|a, b| b.sort_key.partial_cmp(&a.sort_key).unwrap_or(Ordering::Equal));
+
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    for cluster in clusters.iter() {
+        output.extend_from_slice(&indices[cluster.start * 3..cluster.end * 3]);
+    }
+    output
+}
+
+/// Reorders the interleaved vertices, stride floats each, into the order the
+/// indices first reference them and rewrites the indices to match, so the
+/// vertex fetches walk memory linearly. Vertices not referenced by any index
+/// are dropped.
+pub fn optimize_vertex_fetch(vertices: &[f32], stride: usize, indices: &mut [u32]) -> Vec<f32> {
+    let mut remap = vec![None; vertices.len() / stride];
+    let mut reordered = Vec::with_capacity(vertices.len());
+    for index in indices.iter_mut() {
+        let old_index = *index as usize;
+        let new_index = match remap[old_index] {
+            Some(new_index) => new_index,
+            None => {
+                let new_index = (reordered.len() / stride) as u32;
+                reordered.extend_from_slice(&vertices[old_index * stride..(old_index + 1) * stride]);
+                remap[old_index] = Some(new_index);
+                new_index
+            }
+        };
+        *index = new_index;
+    }
+    reordered
+}
@@ -36,8 +36,8 @@ impl Export for Mesh {
             if vertices_index_set.get(&vertex_id).is_none() {
                 i += 1;
                 vertices_index_set.insert(vertex_id, i);
-                let vertex = self.vertex(vertex_id).unwrap();
-                writeln!(f, "v {} {} {}", vertex.position.x, vertex.position.y, vertex.position.z)?;
+                let position = self.vertex_position(vertex_id).unwrap();
+                writeln!(f, "v {} {} {}", position.x, position.y, position.z)?;
             }
         }
         /*
@@ -55,8 +55,7 @@ impl Export for Mesh {
             //face_index += 1;
             while let Some(halfedge_id) = face_halfedge_iter.next() {
                 let halfedge = self.halfedge(halfedge_id).unwrap();
-                let vertex = self.vertex(halfedge.vertex).unwrap();
-                write!(f, " {}", vertices_index_set.get(&vertex.id).unwrap())?;
+                write!(f, " {}", vertices_index_set.get(&halfedge.vertex).unwrap())?;
                 //write!(f, " {}//{}", vertices_index_set.get(&vertex.id).unwrap(), face_index)?;
             }
             writeln!(f, "")?;
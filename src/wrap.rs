@@ -1,3 +1,4 @@
+use cgmath::BaseFloat;
 use cgmath::Point3;
 use cgmath::Vector3;
 use cgmath::prelude::*;
@@ -17,19 +18,19 @@ struct WrapItemKey {
 }
 
 #[derive(Clone)]
-pub struct WrapItem {
-    base_normal: Vector3<f32>,
+pub struct WrapItem<S> {
+    base_normal: Vector3<S>,
     pub p1: usize,
     pub p2: usize,
     pub p3: usize,
     processed: bool,
 }
 
-pub struct Face3 {
+pub struct Face3<S> {
     pub p1: usize,
     pub p2: usize,
     pub p3: usize,
-    pub norm: Vector3<f32>,
+    pub norm: Vector3<S>,
     pub index: usize,
 }
 
@@ -41,27 +42,35 @@ pub struct Face4 {
 }
 
 #[derive(Clone)]
-pub struct SourceVertex {
-    pub position: Point3<f32>,
+pub struct SourceVertex<S> {
+    pub position: Point3<S>,
     pub source_plane: Id,
     pub index: usize,
     pub tag: Id,
 }
 
-pub struct GiftWrapper {
-    items: Vec<WrapItem>,
+pub struct GiftWrapper<S = f32> {
+    items: Vec<WrapItem<S>>,
     items_map: HashMap<WrapItemKey, usize>,
     items_list: LinkedList<usize>,
     candidates: Vec<usize>,
-    pub source_vertices: Vec<SourceVertex>,
-    pub generated_faces: Vec<Face3>,
+    pub source_vertices: Vec<SourceVertex<S>>,
+    pub generated_faces: Vec<Face3<S>>,
     generated_face_edges_map: HashMap<WrapItemKey, Option<usize>>,
     generated_vertex_edges_map: HashMap<usize, Vec<usize>>,
     finalize_finished: bool,
 }
 
 impl GiftWrapper {
+    /// Creates a wrapper for f32 meshes, use GiftWrapper::default() for other
+    /// scalar types.
     pub fn new() -> Self {
+        GiftWrapper::default()
+    }
+}
+
+impl<S> Default for GiftWrapper<S> {
+    fn default() -> Self {
         GiftWrapper {
             items: Vec::new(),
             items_map: HashMap::new(),
@@ -74,22 +83,24 @@ impl GiftWrapper {
             finalize_finished: false,
         }
     }
+}
 
-    pub fn add_source_vertex(&mut self, position: Point3<f32>, source_plane: Id, tag: Id) -> usize {
+impl<S: BaseFloat> GiftWrapper<S> {
+    pub fn add_source_vertex(&mut self, position: Point3<S>, source_plane: Id, tag: Id) -> usize {
         let added_index = self.source_vertices.len();
         self.source_vertices.push(SourceVertex {position: position, source_plane: source_plane, tag: tag, index: added_index});
         self.candidates.push(added_index);
         added_index
     }
 
-    fn calculate_face_vector(&self, p1: usize, p2: usize, base_normal: Vector3<f32>) -> Vector3<f32> {
+    fn calculate_face_vector(&self, p1: usize, p2: usize, base_normal: Vector3<S>) -> Vector3<S> {
         let v1 = &self.source_vertices[p1];
         let v2 = &self.source_vertices[p2];
         let seg = v2.position - v1.position;
         seg.cross(base_normal)
     }
 
-    fn add_item(&mut self, p1: usize, p2: usize, base_normal: Vector3<f32>) {
+    fn add_item(&mut self, p1: usize, p2: usize, base_normal: Vector3<S>) {
         {
             let v1 = &self.source_vertices[p1];
             let v2 = &self.source_vertices[p2];
@@ -114,7 +125,7 @@ impl GiftWrapper {
         self.items_map.get(&key)
     }
 
-    pub fn add_startup(&mut self, p1: usize, p2: usize, base_normal: Vector3<f32>) {
+    pub fn add_startup(&mut self, p1: usize, p2: usize, base_normal: Vector3<S>) {
         if self.items.len() == 0 {
             self.add_item(p1, p2, base_normal);
         }
@@ -129,16 +140,16 @@ impl GiftWrapper {
         true
     }
 
-    fn angle_of_base_face_and_point(&self, item_index: usize, vertex_index: usize) -> f32 {
+    fn angle_of_base_face_and_point(&self, item_index: usize, vertex_index: usize) -> S {
         let item = &self.items[item_index].clone();
         if item.p1 == vertex_index || item.p2 == vertex_index {
-            return 0.0;
+            return S::zero();
         }
         let v1 = &self.source_vertices[item.p1].clone();
         let v2 = &self.source_vertices[item.p2].clone();
         let vp = &self.source_vertices[vertex_index].clone();
         if v1.source_plane == v2.source_plane && v1.source_plane == vp.source_plane {
-            return 0.0;
+            return S::zero();
         }
         let vd1 = self.calculate_face_vector(item.p1, item.p2, item.base_normal);
         let normal = norm(v2.position, v1.position, vp.position);
@@ -169,7 +180,7 @@ impl GiftWrapper {
     fn find_best_vertex_on_the_left(&mut self, item_index: usize) -> Option<usize> {
         let p1 = self.items[item_index].p1;
         let p2 = self.items[item_index].p2;
-        let mut max_angle = 0 as f32;
+        let mut max_angle = S::zero();
         let mut choosen_it = None;
         let mut rm_vec : Vec<usize> = Vec::new();
         for (i, &it) in self.candidates.iter().enumerate() {
@@ -252,11 +263,11 @@ impl GiftWrapper {
         }
     }
 
-    fn add_candidate_vertices(&mut self, mesh: &mut Mesh, vertices: &Vec<Id>, plane_norm: Vector3<f32>, plane_id: usize) {
+    fn add_candidate_vertices(&mut self, mesh: &mut Mesh<S>, vertices: &Vec<Id>, plane_norm: Vector3<S>, plane_id: usize) {
         let mut vertices_index_set : HashMap<Id, usize> = HashMap::new();
         for &old_vert_id in vertices {
-            let vertex = mesh.vertex(old_vert_id).unwrap();
-            vertices_index_set.entry(vertex.id).or_insert(self.add_source_vertex(vertex.position, plane_id, vertex.id));
+            let position = mesh.vertex_position(old_vert_id).unwrap();
+            vertices_index_set.entry(old_vert_id).or_insert(self.add_source_vertex(position, plane_id, old_vert_id));
         }
         for i in 0..vertices.len() {
             let old_vert_id = vertices[i];
@@ -269,11 +280,12 @@ impl GiftWrapper {
         }
     }
 
-    fn add_candidate_face(&mut self, mesh: &mut Mesh, face_id: Id, reverse: bool) {
+    fn add_candidate_face(&mut self, mesh: &mut Mesh<S>, face_id: Id, reverse: bool) {
         let mut vertices_index_set : HashMap<Id, usize> = HashMap::new();
         for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
-            let vertex = mesh.halfedge_start_vertex(halfedge_id).unwrap();
-            vertices_index_set.entry(vertex.id).or_insert(self.add_source_vertex(vertex.position, face_id, vertex.id));
+            let vert_id = mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
+            let position = mesh.vertex_position(vert_id).unwrap();
+            vertices_index_set.entry(vert_id).or_insert(self.add_source_vertex(position, face_id, vert_id));
         }
         let plane_norm = mesh.face_norm(face_id);
         for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
@@ -294,7 +306,7 @@ impl GiftWrapper {
         }
     }
 
-    fn another_vertex_index_of_face3(&self, f: &Face3, p1: usize, p2: usize) -> usize {
+    fn another_vertex_index_of_face3(&self, f: &Face3<S>, p1: usize, p2: usize) -> usize {
         let indices = vec![f.p1, f.p2, f.p3];
         for index in indices {
             if index != p1 && index != p2 {
@@ -304,7 +316,7 @@ impl GiftWrapper {
         0
     }
 
-    fn find_pair_face3(&self, f: &Face3, used_ids: &HashMap<usize, bool>, q: &mut Vec<Face4>) -> Option<usize> {
+    fn find_pair_face3(&self, f: &Face3<S>, used_ids: &HashMap<usize, bool>, q: &mut Vec<Face4>) -> Option<usize> {
         let indices = vec![f.p1, f.p2, f.p3];
         for i in 0..indices.len() {
             let next_i = (i + 1) % indices.len();
@@ -328,7 +340,7 @@ impl GiftWrapper {
         None
     }
 
-    fn finalize(&mut self, mesh: &mut Mesh) {
+    fn finalize(&mut self, mesh: &mut Mesh<S>) {
         let mut quards : Vec<Face4> = Vec::new();
         let mut used_ids: HashMap<usize, bool> = HashMap::new();
         self.finalize_finished = true;
@@ -362,7 +374,7 @@ impl GiftWrapper {
         }
     }
 
-    pub fn stitch_two_faces(&mut self, mesh: &mut Mesh, face1: Id, face2: Id) {
+    pub fn stitch_two_faces(&mut self, mesh: &mut Mesh<S>, face1: Id, face2: Id) {
         let mut remove_faces = Vec::new();
         self.add_candidate_face(mesh, face1, false);
         if !mesh.face_adj_id(face1).is_none() {
@@ -379,7 +391,7 @@ impl GiftWrapper {
         self.finalize(mesh);
     }
 
-    pub fn wrap_faces(&mut self, mesh: &mut Mesh, faces: &Vec<Id>) {
+    pub fn wrap_faces(&mut self, mesh: &mut Mesh<S>, faces: &Vec<Id>) {
         for &face_id in faces {
             self.add_candidate_face(mesh, face_id, true);
         }
@@ -387,7 +399,7 @@ impl GiftWrapper {
         self.finalize(mesh);
     }
 
-    pub fn wrap_vertices(&mut self, mesh: &mut Mesh, vertices: &Vec<(Vec<Id>, Vector3<f32>)>) {
+    pub fn wrap_vertices(&mut self, mesh: &mut Mesh<S>, vertices: &Vec<(Vec<Id>, Vector3<S>)>) {
         let mut next_plane_id = 1;
         for vert in vertices {
             self.add_candidate_vertices(mesh, &vert.0, vert.1, next_plane_id);
@@ -1,8 +1,53 @@
+extern crate cgmath;
 extern crate meshlite;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
+use cgmath::Point3;
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::prelude::*;
+use meshlite::bevel::Bevel;
+use meshlite::binary::BINARY_FORMAT_VERSION;
+use meshlite::binary::BinaryFormat;
+#[cfg(feature = "serde")]
+use meshlite::bmesh::Bmesh;
+use meshlite::bridge::Bridge;
+use meshlite::holefill::HoleFiller;
+use meshlite::iterator::BoundaryLoopIterator;
+use meshlite::iterator::EdgeIterator;
+use meshlite::iterator::EdgeLoopIterator;
+use meshlite::iterator::EdgeRingIterator;
+use meshlite::iterator::FaceHalfedgeIterator;
+use meshlite::iterator::FaceIterator;
+use meshlite::iterator::FaceNeighborIterator;
+use meshlite::iterator::FaceVertexIterator;
+use meshlite::iterator::HalfedgeIterator;
+use meshlite::iterator::VertexFaceIterator;
+use meshlite::iterator::VertexIterator;
+use meshlite::iterator::VertexNeighborIterator;
+use meshlite::iterator::VertexOutgoingIterator;
+use meshlite::journal::Undo;
+use meshlite::loopcut::LoopCut;
+use meshlite::mesh::InsetMode;
+use meshlite::mesh::Mesh;
 use meshlite::primitives::cube;
+use meshlite::query::MeshQuery;
+use meshlite::renderbuffer::RenderBufferBuilder;
+use meshlite::repair::Repair;
+use meshlite::selection::Select;
+use meshlite::selfintersect::SelfIntersect;
+#[cfg(feature = "serde")]
+use meshlite::skeletonmesh::SkeletonMesh;
+#[cfg(feature = "rayon")]
+use meshlite::subdivide::CatmullClarkSubdivider;
 use meshlite::subdivide::Subdivide;
 use meshlite::triangulate::Triangulate;
+use meshlite::vertexcache::optimize_overdraw;
+use meshlite::vertexcache::optimize_vertex_cache;
+use meshlite::vertexcache::vertex_cache_stats;
+use std::collections::HashMap;
+use std::io;
 
 /// Test added for https://github.com/huxingyi/meshlite/pull/2
 #[test]
@@ -13,6 +58,66 @@ fn verify_cube_subdivision() {
     assert_eq!(24, sub.face_count);
 }
 
+#[test]
+fn verify_f64_mesh_keeps_precision_far_from_origin() {
+    let mut mesh : Mesh<f64> = cube().cast();
+    mesh.translate(1.0e7, 0.0, 0.0);
+    let sub = mesh.subdivide().subdivide();
+    let reference = cube().subdivide().subdivide();
+    assert_eq!(reference.vertex_count, sub.vertex_count);
+    for (position, reference_position) in sub.vertices.position.iter().zip(reference.vertices.position.iter()) {
+        let offset = position - Point3::new(1.0e7, 0.0, 0.0);
+        assert!((offset - reference_position.cast::<f64>().unwrap().to_vec()).magnitude() < 1.0e-6);
+    }
+    let tri = sub.triangulate();
+    assert_eq!(reference.face_count * 2, tri.face_count);
+    assert!(tri.is_triangulated_mesh_manifold());
+    let area : f64 = FaceIterator::new(&tri).map(|face_id| tri.face_area(face_id)).sum();
+    let reference_tri = reference.triangulate();
+    let reference_area : f32 = FaceIterator::new(&reference_tri).map(|face_id| reference_tri.face_area(face_id)).sum();
+    assert!((area - reference_area as f64).abs() < 1.0e-4);
+
+    // The same cube in f32 loses its width, the spacing of f32 at 1e7 is 1
+    let mut far = cube();
+    far.translate(1.0e7, 0.0, 0.0);
+    let far_x : Vec<f32> = far.vertices.position.iter().map(|position| position.x).collect();
+    assert!(far_x.iter().all(|&x| x == far_x[0]));
+}
+
+#[test]
+fn verify_f64_weld_far_from_origin() {
+    for &offset in [1.0e7, -1.0e7].iter() {
+        let mut mesh : Mesh<f64> = cube().cast();
+        mesh.translate(offset, offset, 0.0);
+        let mut soup : Mesh<f64> = Mesh::default();
+        for face_id in FaceIterator::new(&mesh) {
+            let corners = FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap())
+                .map(|halfedge_id| soup.add_vertex(mesh.halfedge_start_vertex_position(halfedge_id).unwrap()))
+                .collect();
+            soup.add_vertices(corners);
+        }
+        assert_eq!(24, soup.vertex_count);
+        let welded = soup.weld();
+        assert_eq!(8, welded.vertex_count);
+        assert_eq!(6, welded.face_count);
+        assert!(welded.triangulate().is_triangulated_mesh_manifold());
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn verify_parallel_subdivision_matches_serial() {
+    let mut inset = cube();
+    let face_ids = FaceIterator::new(&inset).into_vec();
+    inset.inset_faces(&face_ids[0..2], 0.1, 0.05, InsetMode::Individual);
+    for mesh in [inset, cube().triangulate(), cube().subdivide().subdivide()].iter() {
+        let serial = CatmullClarkSubdivider::new(mesh).generate();
+        let parallel = CatmullClarkSubdivider::new(mesh).generate_parallel();
+        assert_eq!(mesh_state(&serial), mesh_state(&parallel));
+        assert_eq!(mesh_state(&serial.subdivide()), mesh_state(&CatmullClarkSubdivider::new(&parallel).generate_parallel()));
+    }
+}
+
 #[test]
 fn verify_cube_triangulation() {
     let cube = cube();
@@ -20,3 +125,843 @@ fn verify_cube_triangulation() {
     assert_eq!(8, tri.vertex_count);
     assert_eq!(12, tri.face_count);
 }
+
+#[test]
+fn verify_cube_distance_queries() {
+    let query = MeshQuery::new(&cube());
+    let closest = query.closest_point(Point3::new(0.0, 0.0, 2.0)).unwrap();
+    assert!(closest.position.distance(Point3::new(0.0, 0.0, 0.5)) < 0.0001);
+    assert!((closest.distance - 1.5).abs() < 0.0001);
+    assert!((query.winding_number(Point3::new(0.1, 0.2, 0.0)) - 1.0).abs() < 0.01);
+    assert!(query.winding_number(Point3::new(3.0, 0.0, 0.0)).abs() < 0.01);
+    assert!((query.signed_distance(Point3::new(0.0, 0.0, 0.25)).unwrap() + 0.25).abs() < 0.0001);
+}
+
+#[test]
+fn verify_overlapping_cubes_self_intersection() {
+    assert!(cube().self_intersections().is_empty());
+    let mut moved = cube();
+    moved.translate(0.5, 0.5, 0.5);
+    let mesh = cube() + moved;
+    let intersections = mesh.self_intersections();
+    assert!(!intersections.is_empty());
+    for intersection in intersections.iter() {
+        assert_ne!(intersection.first_face, intersection.second_face);
+    }
+}
+
+#[test]
+fn verify_subdivided_cube_hole_filling() {
+    let mut mesh = cube().subdivide().subdivide();
+    for face_id in FaceIterator::new(&mesh.clone()).into_vec() {
+        if mesh.face_center(face_id).z > 0.4 {
+            mesh.remove_face(face_id);
+        }
+    }
+    assert!(!mesh.broken_face_set().is_empty());
+    let mut filler = HoleFiller::new();
+    filler.set_max_hole_size(3);
+    assert_eq!(mesh.face_count, filler.fill_holes(&mesh).face_count);
+    filler.set_max_hole_size(0);
+    let filled = filler.fill_holes(&mesh);
+    assert!(filled.broken_face_set().is_empty());
+    assert!(filled.triangulate().is_triangulated_mesh_manifold());
+}
+
+#[test]
+fn verify_smoothing_shrinkage() {
+    let average_radius = |mesh: &Mesh| {
+        mesh.vertices.position.iter().map(|p| p.to_vec().magnitude()).sum::<f32>() / mesh.vertices.len() as f32
+    };
+    let mesh = cube().subdivide().subdivide();
+    let original_radius = average_radius(&mesh);
+    let mut laplacian = mesh.clone();
+    let mut cotangent = mesh.clone();
+    for _ in 0..10 {
+        laplacian.smooth(0.5, None);
+        cotangent.smooth_cotangent(0.5, None);
+    }
+    let mut taubin = mesh.clone();
+    taubin.smooth_taubin(0.5, -0.53, 10, None);
+    let mut hc = mesh.clone();
+    hc.smooth_hc(0.0, 0.5, 10, None);
+    let shrinkage = |smoothed: &Mesh| original_radius - average_radius(smoothed);
+    assert!(shrinkage(&laplacian) > 0.0);
+    assert!(shrinkage(&cotangent) > 0.0);
+    assert!(shrinkage(&taubin).abs() < shrinkage(&laplacian) * 0.5);
+    assert!(shrinkage(&hc).abs() < shrinkage(&laplacian) * 0.5);
+}
+
+#[test]
+fn verify_hc_smoothing_is_deterministic() {
+    let mesh = cube().subdivide().subdivide();
+    let mut first = mesh.clone();
+    let mut second = mesh.clone();
+    first.smooth_hc(0.0, 0.5, 5, None);
+    second.smooth_hc(0.0, 0.5, 5, None);
+    assert_eq!(first.vertices.position, second.vertices.position);
+}
+
+#[test]
+fn verify_noisy_cube_denoising() {
+    let mut mesh = cube().subdivide().subdivide().subdivide();
+    let original : Vec<Point3<f32>> = mesh.vertices.position.clone();
+    for (i, position) in mesh.vertices.position.iter_mut().enumerate() {
+        let direction = position.to_vec().normalize();
+        *position += direction * ((i as f32 * 12.9898).sin() * 0.01);
+    }
+    let error = |mesh: &Mesh| {
+        original.iter().zip(mesh.vertices.position.iter()).map(|(a, &b)| a.distance(b)).sum::<f32>() / original.len() as f32
+    };
+    let noisy_error = error(&mesh);
+    mesh.denoise(0.1, 0.35, 5);
+    assert!(error(&mesh) < noisy_error * 0.5);
+}
+
+fn mixed_winding_cube(reversed_faces: &[usize]) -> Mesh {
+    let source = cube();
+    let mut mesh = Mesh::new();
+    for &position in source.vertices.position.iter() {
+        mesh.add_vertex(position);
+    }
+    for (i, face_id) in FaceIterator::new(&source).enumerate() {
+        let mut vertices : Vec<usize> = FaceHalfedgeIterator::new(&source, source.face_first_halfedge_id(face_id).unwrap())
+            .map(|halfedge_id| source.halfedge_start_vertex_id(halfedge_id).unwrap())
+            .collect();
+        if reversed_faces.contains(&i) {
+            vertices.reverse();
+        }
+        mesh.add_vertices(vertices);
+    }
+    mesh
+}
+
+#[test]
+fn verify_mixed_winding_orientation() {
+    let (oriented, report) = mixed_winding_cube(&[1, 4]).orient_faces();
+    assert_eq!(1, report.component_count);
+    assert_eq!(vec![2, 5], report.flipped_faces);
+    assert!(oriented.broken_face_set().is_empty());
+    assert!(MeshQuery::new(&oriented).winding_number(Point3::new(0.0, 0.0, 0.0)) > 0.99);
+    let (oriented, report) = mixed_winding_cube(&[0, 1, 2, 3, 4, 5]).orient_faces();
+    assert_eq!(6, report.flipped_faces.len());
+    assert!(MeshQuery::new(&oriented).winding_number(Point3::new(0.0, 0.0, 0.0)) > 0.99);
+}
+
+#[test]
+fn verify_non_manifold_repair() {
+    let mut mesh = Mesh::new();
+    let center = mesh.add_vertex(Point3::new(0.0, 0.0, 0.0));
+    let a = mesh.add_vertex(Point3::new(1.0, 0.0, 0.0));
+    let b = mesh.add_vertex(Point3::new(1.0, 1.0, 0.0));
+    let c = mesh.add_vertex(Point3::new(-1.0, 0.0, 0.0));
+    let d = mesh.add_vertex(Point3::new(-1.0, -1.0, 0.0));
+    let e = mesh.add_vertex(Point3::new(0.0, 1.0, 1.0));
+    let f = mesh.add_vertex(Point3::new(0.0, 1.0, -1.0));
+    mesh.add_vertices(vec![center, a, b]);
+    mesh.add_vertices(vec![center, c, d]);
+    mesh.add_vertices(vec![a, center, e]);
+    mesh.add_vertices(vec![center, a, f]);
+    let (fixed, report) = mesh.fix_non_manifold();
+    assert_eq!(vec![(center, a)], report.non_manifold_edges);
+    assert!(report.bowtie_vertices.is_empty());
+    assert_eq!(vec![(center, 3), (a, 2)], report.split_vertices);
+    assert_eq!(4, fixed.face_count);
+    assert_eq!(12, fixed.vertex_count);
+    let mut bowtie = Mesh::new();
+    let center = bowtie.add_vertex(Point3::new(0.0, 0.0, 0.0));
+    let a = bowtie.add_vertex(Point3::new(1.0, 0.0, 0.0));
+    let b = bowtie.add_vertex(Point3::new(1.0, 1.0, 0.0));
+    let c = bowtie.add_vertex(Point3::new(-1.0, 0.0, 0.0));
+    let d = bowtie.add_vertex(Point3::new(-1.0, -1.0, 0.0));
+    bowtie.add_vertices(vec![center, a, b]);
+    bowtie.add_vertices(vec![center, c, d]);
+    let (fixed, report) = bowtie.fix_non_manifold();
+    assert_eq!(vec![center], report.bowtie_vertices);
+    assert_eq!(vec![2], report.touched_faces);
+    assert_eq!(6, fixed.vertex_count);
+    let (_, report) = cube().fix_non_manifold();
+    assert!(report.split_vertices.is_empty());
+    assert!(report.touched_faces.is_empty());
+}
+
+#[test]
+fn verify_degenerate_face_cleanup() {
+    let mut mesh = mixed_winding_cube(&[]);
+    let first_face : Vec<usize> = FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(1).unwrap())
+        .map(|halfedge_id| mesh.halfedge_start_vertex_id(halfedge_id).unwrap())
+        .collect();
+    mesh.add_vertices(first_face.clone());
+    mesh.add_vertices(vec![first_face[0], first_face[0], first_face[1]]);
+    let (cleaned, report) = mesh.remove_degenerate_faces(0.000001, 0.01);
+    assert_eq!(vec![7], report.duplicate_faces);
+    assert_eq!(vec![8], report.repeated_vertex_faces);
+    assert!(report.degenerate_faces.is_empty());
+    assert_eq!(6, cleaned.face_count);
+    assert!(cleaned.broken_face_set().is_empty());
+
+    let mut needle = Mesh::new();
+    let a = needle.add_vertex(Point3::new(0.0, 0.0, 0.0));
+    let b = needle.add_vertex(Point3::new(1.0, 0.0, 0.0));
+    let c = needle.add_vertex(Point3::new(1.0, 1.0, 0.0));
+    let d = needle.add_vertex(Point3::new(0.0, 1.0, 0.0));
+    let e = needle.add_vertex(Point3::new(0.0001, 0.0, 0.0));
+    needle.add_vertices(vec![a, e, c]);
+    needle.add_vertices(vec![e, b, c]);
+    needle.add_vertices(vec![a, c, d]);
+    let (cleaned, report) = needle.remove_degenerate_faces(0.000001, 0.01);
+    assert_eq!(vec![1], report.degenerate_faces);
+    assert_eq!(1, report.collapsed_edges);
+    assert_eq!(2, cleaned.face_count);
+    assert_eq!(4, cleaned.vertex_count);
+    for face_id in FaceIterator::new(&cleaned) {
+        assert!(!cleaned.face_norm(face_id).x.is_nan());
+    }
+}
+
+#[test]
+fn verify_mirror_and_symmetrize() {
+    let mut mesh = cube();
+    mesh.translate(0.2, 0.0, 0.0);
+    for (i, source) in mesh.vertices.source.iter_mut().enumerate() {
+        *source = 10 + i as i32;
+    }
+    let sorted_sources = |mesh: &Mesh, side: f32| {
+        let mut sources : Vec<i32> = VertexIterator::new(mesh)
+            .filter(|&vert_id| mesh.vertex_position(vert_id).unwrap().x * side > 0.01)
+            .map(|vert_id| mesh.vertex_source(vert_id).unwrap())
+            .collect();
+        sources.sort();
+        sources
+    };
+    let mirrored = mesh.mirror(Point3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0));
+    assert!(MeshQuery::new(&mirrored).winding_number(Point3::new(-0.2, 0.0, 0.0)) > 0.99);
+    let mut mirrored_sources = mirrored.vertices.source.clone();
+    mirrored_sources.sort();
+    assert_eq!((10..18).collect::<Vec<i32>>(), mirrored_sources);
+    let symmetric = mesh.symmetrize(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+    assert!(symmetric.broken_face_set().is_empty());
+    let query = MeshQuery::new(&symmetric);
+    assert!(query.is_inside(Point3::new(0.6, 0.0, 0.0)));
+    assert!(query.is_inside(Point3::new(-0.6, 0.0, 0.0)));
+    assert!(!query.is_inside(Point3::new(-0.8, 0.0, 0.0)));
+    let kept_sources = sorted_sources(&symmetric, 1.0);
+    assert_eq!(4, kept_sources.len());
+    assert!(kept_sources.iter().all(|&source| source >= 10));
+    assert_eq!(kept_sources, sorted_sources(&symmetric, -1.0));
+}
+
+#[test]
+fn verify_cube_inset_faces() {
+    let mut mesh = cube();
+    let face_ids = FaceIterator::new(&mesh).into_vec();
+    let inset_faces = mesh.inset_faces(&face_ids[0..2], 0.1, 0.05, InsetMode::Individual);
+    assert_eq!(2, inset_faces.len());
+    assert_eq!(6 + 2 * 4, mesh.face_count);
+    assert_eq!(8 + 2 * 4, mesh.vertex_count);
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+
+    let mut mesh = cube();
+    let face_ids = FaceIterator::new(&mesh).into_vec();
+    let neighbors : Vec<usize> = face_ids.iter().cloned()
+        .filter(|&face_id| face_id == face_ids[0] || mesh.face_norm(face_id).dot(mesh.face_norm(face_ids[0])).abs() < 0.5)
+        .take(2)
+        .collect();
+    let inset_faces = mesh.inset_faces(&neighbors, 0.1, 0.0, InsetMode::Region);
+    assert_eq!(2, inset_faces.len());
+    assert_eq!(6 + 6, mesh.face_count);
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+    assert!(MeshQuery::new(&mesh).winding_number(Point3::new(0.0, 0.0, 0.0)) > 0.99);
+
+    let mut mesh = Mesh::new();
+    let face_id = mesh.add_plane(1.0, 1.0);
+    let inset_faces = mesh.inset_faces(&[face_id], 0.1, 0.0, InsetMode::Individual);
+    assert_eq!(1, inset_faces.len());
+    assert_eq!(5, mesh.face_count);
+    assert_eq!(8, mesh.vertex_count);
+    assert_eq!(1, mesh.boundary_loops().len());
+}
+
+#[test]
+fn verify_cube_bevel() {
+    let mesh = cube();
+    let mut edges = Vec::new();
+    for face_id in FaceIterator::new(&mesh) {
+        for halfedge_id in FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            let from = mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
+            let to = mesh.halfedge_start_vertex_id(mesh.halfedge_next_id(halfedge_id).unwrap()).unwrap();
+            if from < to {
+                edges.push((from, to));
+            }
+        }
+    }
+    assert_eq!(12, edges.len());
+    let single = mesh.bevel_edges(&edges[0..1], 0.1, 1, 0.5);
+    assert_eq!(7, single.face_count);
+    assert_eq!(10, single.vertex_count);
+    assert!(single.triangulate().is_triangulated_mesh_manifold());
+    let touching : Vec<(usize, usize)> = edges.iter().cloned()
+        .filter(|&(from, to)| from == edges[0].0 || to == edges[0].0)
+        .take(2)
+        .collect();
+    let two = mesh.bevel_edges(&touching, 0.1, 3, 0.5);
+    assert_eq!(6 + 6, two.face_count);
+    assert!(two.triangulate().is_triangulated_mesh_manifold());
+    let chamfered = mesh.bevel_edges(&edges, 0.1, 1, 0.5);
+    assert_eq!(6 + 12 + 8, chamfered.face_count);
+    assert_eq!(24, chamfered.vertex_count);
+    assert!(chamfered.triangulate().is_triangulated_mesh_manifold());
+    let rounded = mesh.bevel_edges(&edges, 0.1, 2, 0.5);
+    assert_eq!(6 + 24 + 24, rounded.face_count);
+    assert!(rounded.triangulate().is_triangulated_mesh_manifold());
+    let query = MeshQuery::new(&rounded);
+    assert!(query.is_inside(Point3::new(0.0, 0.0, 0.0)));
+    assert!(!query.is_inside(Point3::new(0.49, 0.49, 0.49)));
+    let corners = mesh.bevel_vertices(&(1..9).collect::<Vec<usize>>(), 0.1, 1, 0.5);
+    assert_eq!(6 + 8, corners.face_count);
+    assert_eq!(24, corners.vertex_count);
+    assert!(corners.triangulate().is_triangulated_mesh_manifold());
+}
+
+fn open_box(mesh: &Mesh, open_below: bool) -> Mesh {
+    let mut mesh = mesh.clone();
+    for face_id in FaceIterator::new(&mesh.clone()).into_vec() {
+        let z = mesh.face_center(face_id).z;
+        if (open_below && z < -0.2) || (!open_below && z > 0.2) {
+            mesh.remove_face(face_id);
+        }
+    }
+    mesh
+}
+
+#[test]
+fn verify_bridge_loops() {
+    let mut mesh = open_box(&cube(), false);
+    let mut upper = open_box(&cube(), true);
+    upper.translate(0.0, 0.0, 2.0);
+    mesh.add_mesh(&upper);
+    let loops = mesh.boundary_loops();
+    assert_eq!(2, loops.len());
+    let added = mesh.bridge_loops(&loops[0], &loops[1], 3, 0);
+    assert_eq!(12, added.len());
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+    assert!(MeshQuery::new(&mesh).is_inside(Point3::new(0.0, 0.0, 1.0)));
+
+    let mut mesh = open_box(&cube(), false);
+    let mut upper = open_box(&cube().subdivide(), true);
+    upper.translate(0.0, 0.0, 2.0);
+    mesh.add_mesh(&upper);
+    let loops = mesh.boundary_loops();
+    assert_eq!(2, loops.len());
+    let added = mesh.bridge_loops(&loops[0], &loops[1], 2, 1);
+    assert_eq!(16, added.len());
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+}
+
+#[test]
+fn verify_subdivided_cube_edge_loops_and_rings() {
+    let mesh = cube().subdivide();
+    let valence = |halfedge_id: usize| mesh.halfedge_start_vertex(halfedge_id).unwrap().halfedges.len();
+    let mut regular = 0;
+    let mut pole = 0;
+    for face_id in FaceIterator::new(&mesh) {
+        for halfedge_id in FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            let next_id = mesh.halfedge_next_id(halfedge_id).unwrap();
+            if valence(halfedge_id) == 4 && valence(next_id) == 4 {
+                regular = halfedge_id;
+            } else if valence(next_id) == 3 {
+                pole = halfedge_id;
+            }
+        }
+    }
+    let edge_loop = EdgeLoopIterator::new(&mesh, regular).into_vec();
+    assert_eq!(8, edge_loop.len());
+    for i in 0..edge_loop.len() {
+        let next_id = mesh.halfedge_next_id(edge_loop[i]).unwrap();
+        assert_eq!(mesh.halfedge_start_vertex_id(next_id), mesh.halfedge_start_vertex_id(edge_loop[(i + 1) % edge_loop.len()]));
+    }
+    assert_eq!(vec![pole], EdgeLoopIterator::new(&mesh, pole).into_vec());
+    assert_eq!(8, EdgeRingIterator::new(&mesh, regular).count());
+    assert_eq!(8, EdgeRingIterator::new(&mesh, pole).count());
+}
+
+#[test]
+fn verify_edge_loop_stops_at_boundary_pole() {
+    // A 2x2 grid with two more quads below the bottom middle vertex, split
+    // by a gap, so that vertex has four faces and five edges
+    let mut mesh = Mesh::new();
+    let mut grid = Vec::new();
+    for y in 0..3 {
+        for x in 0..3 {
+            grid.push(mesh.add_vertex(Point3::new(x as f32, y as f32, 0.0)));
+        }
+    }
+    for y in 0..2 {
+        for x in 0..2 {
+            let i = y * 3 + x;
+            mesh.add_vertices(vec![grid[i], grid[i + 1], grid[i + 4], grid[i + 3]]);
+        }
+    }
+    let left = mesh.add_vertex(Point3::new(0.0, -1.0, 0.0));
+    let left_gap = mesh.add_vertex(Point3::new(0.8, -1.0, 0.0));
+    let right_gap = mesh.add_vertex(Point3::new(1.2, -1.0, 0.0));
+    let right = mesh.add_vertex(Point3::new(2.0, -1.0, 0.0));
+    mesh.add_vertices(vec![left, left_gap, grid[1], grid[0]]);
+    mesh.add_vertices(vec![right_gap, right, grid[2], grid[1]]);
+    let pole = grid[1];
+    assert_eq!(4, mesh.vertices.halfedges[pole - 1].len());
+
+    let mut start = 0;
+    for face_id in FaceIterator::new(&mesh) {
+        for halfedge_id in FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            let next_id = mesh.halfedge_next_id(halfedge_id).unwrap();
+            if mesh.halfedge_start_vertex_id(halfedge_id) == Some(grid[7]) && mesh.halfedge_start_vertex_id(next_id) == Some(grid[4]) {
+                start = halfedge_id;
+            }
+        }
+    }
+    let edge_loop = EdgeLoopIterator::new(&mesh, start).into_vec();
+    assert_eq!(2, edge_loop.len());
+    let last_next_id = mesh.halfedge_next_id(edge_loop[1]).unwrap();
+    assert_eq!(Some(pole), mesh.halfedge_start_vertex_id(last_next_id));
+}
+
+fn cube_halfedge(mesh: &Mesh, face_center: Vector3<f32>, direct: Vector3<f32>) -> usize {
+    for face_id in FaceIterator::new(mesh) {
+        if (mesh.face_center(face_id) - Point3::new(0.0, 0.0, 0.0)).dot(face_center) < 0.2 {
+            continue;
+        }
+        for halfedge_id in FaceHalfedgeIterator::new(mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            if mesh.halfedge_direct(halfedge_id).normalize().dot(direct) > 0.9 {
+                return halfedge_id;
+            }
+        }
+    }
+    0
+}
+
+#[test]
+fn verify_cube_loop_cut() {
+    let mut mesh = cube();
+    let start = cube_halfedge(&mesh, Vector3::unit_x(), Vector3::unit_z());
+    let cut = mesh.loop_cut(start, 0.25);
+    assert_eq!(4, cut.len());
+    assert_eq!(10, mesh.face_count);
+    assert_eq!(12, mesh.vertex_count);
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+    for &vert_id in cut.iter() {
+        assert!((mesh.vertex(vert_id).unwrap().position.z + 0.25).abs() < 0.0001);
+    }
+
+    let mut mesh = cube();
+    let start = cube_halfedge(&mesh, Vector3::unit_x(), Vector3::unit_z());
+    let cuts = mesh.loop_cut_multiple(start, 3);
+    assert_eq!(3, cuts.len());
+    assert_eq!(18, mesh.face_count);
+    assert_eq!(20, mesh.vertex_count);
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+
+    // The ring stops at the triangles on top, which get the new vertices
+    let mut mesh = cube();
+    for face_id in FaceIterator::new(&mesh.clone()).into_vec() {
+        if mesh.face_center(face_id).z > 0.2 {
+            let vertices : Vec<usize> = FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap())
+                .map(|halfedge_id| mesh.halfedge_start_vertex_id(halfedge_id).unwrap())
+                .collect();
+            mesh.remove_face(face_id);
+            for &vert_id in vertices.iter() {
+                mesh.revive_vertex(vert_id);
+            }
+            mesh.add_vertices(vec![vertices[0], vertices[1], vertices[2]]);
+            mesh.add_vertices(vec![vertices[2], vertices[3], vertices[0]]);
+        }
+    }
+    let start = cube_halfedge(&mesh, Vector3::unit_x(), Vector3::unit_y());
+    assert_eq!(8, mesh.vertex_count);
+    let cut = mesh.loop_cut(start, 0.5);
+    assert_eq!(4, cut.len());
+    assert_eq!(10, mesh.face_count);
+    assert_eq!(12, mesh.vertex_count);
+    assert_eq!(VertexIterator::new(&mesh).count(), mesh.vertex_count);
+    assert!(mesh.broken_face_set().is_empty());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+
+    let mut mesh = Mesh::new();
+    let face_id = mesh.add_plane(1.0, 1.0);
+    let cuts = mesh.loop_cut_multiple(mesh.face_first_halfedge_id(face_id).unwrap(), 2);
+    assert_eq!(vec![2, 2], cuts.iter().map(|cut| cut.len()).collect::<Vec<usize>>());
+    assert_eq!(3, mesh.face_count);
+    assert_eq!(8, mesh.vertex_count);
+    assert_eq!(1, mesh.boundary_loops().len());
+}
+
+#[test]
+fn verify_open_box_vertex_circulators() {
+    let mesh = open_box(&cube(), false);
+    let loops = mesh.boundary_loops();
+    assert_eq!(1, loops.len());
+    assert_eq!(loops[0], BoundaryLoopIterator::new(&mesh, loops[0][0]).into_vec());
+    let rim_vertex = mesh.halfedge_start_vertex_id(loops[0][0]).unwrap();
+    let outgoing = VertexOutgoingIterator::new(&mesh, rim_vertex).into_vec();
+    assert_eq!(2, outgoing.len());
+    assert_eq!(loops[0][0], *outgoing.last().unwrap());
+    let mut neighbors = VertexNeighborIterator::new(&mesh, rim_vertex).into_vec();
+    assert_eq!(3, neighbors.len());
+    neighbors.sort();
+    neighbors.dedup();
+    assert_eq!(3, neighbors.len());
+    assert_eq!(2, VertexFaceIterator::new(&mesh, rim_vertex).count());
+
+    let mesh = cube();
+    for face_id in FaceIterator::new(&mesh) {
+        for halfedge_id in FaceHalfedgeIterator::new(&mesh, mesh.face_first_halfedge_id(face_id).unwrap()) {
+            let vert_id = mesh.halfedge_start_vertex_id(halfedge_id).unwrap();
+            assert_eq!(3, VertexOutgoingIterator::new(&mesh, vert_id).count());
+            assert_eq!(3, VertexNeighborIterator::new(&mesh, vert_id).count());
+            let mut faces = VertexFaceIterator::new(&mesh, vert_id).into_vec();
+            faces.sort();
+            faces.dedup();
+            assert_eq!(3, faces.len());
+        }
+    }
+}
+
+#[test]
+fn verify_element_iterators() {
+    let mesh = open_box(&cube().subdivide(), false);
+    let vertices = VertexIterator::new(&mesh);
+    assert_eq!(mesh.vertex_count, vertices.len());
+    assert_eq!(mesh.vertex_count, vertices.count());
+    let faces = FaceIterator::new(&mesh);
+    assert_eq!(mesh.face_count, faces.len());
+    assert_eq!(mesh.face_count, faces.count());
+    let halfedges = HalfedgeIterator::new(&mesh);
+    assert_eq!(mesh.halfedge_count, halfedges.len());
+    assert_eq!(mesh.halfedge_count, halfedges.count());
+    let boundary_count = mesh.boundary_loops().iter().map(|boundary| boundary.len()).sum::<usize>();
+    let edge_count = EdgeIterator::new(&mesh).count();
+    assert_eq!(mesh.halfedge_count, edge_count * 2 - boundary_count);
+    // Euler characteristic of a disc
+    assert_eq!(1, mesh.vertex_count as isize - edge_count as isize + mesh.face_count as isize);
+
+    for face_id in FaceIterator::new(&mesh) {
+        let start_id = mesh.face_first_halfedge_id(face_id).unwrap();
+        let forward = FaceHalfedgeIterator::new(&mesh, start_id).into_vec();
+        let mut backward : Vec<usize> = FaceHalfedgeIterator::new(&mesh, start_id).rev().collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        let mut both = FaceHalfedgeIterator::new(&mesh, start_id);
+        let mut mixed = vec![both.next().unwrap()];
+        let mut tail = Vec::new();
+        while let Some(halfedge_id) = both.next_back() {
+            tail.insert(0, halfedge_id);
+            if let Some(halfedge_id) = both.next() {
+                mixed.push(halfedge_id);
+            }
+        }
+        mixed.extend(tail);
+        assert_eq!(forward, mixed);
+        let vertices = FaceVertexIterator::new(&mesh, face_id).into_vec();
+        assert_eq!(forward.len(), vertices.len());
+        assert_eq!(vertices.last().cloned(), FaceVertexIterator::new(&mesh, face_id).next_back());
+        let neighbors = FaceNeighborIterator::new(&mesh, face_id).into_vec();
+        let open_edges = forward.iter().filter(|&&halfedge_id| mesh.halfedge_opposite_id(halfedge_id).is_none()).count();
+        assert_eq!(forward.len() - open_edges, neighbors.len());
+        assert!(!neighbors.contains(&face_id));
+    }
+}
+
+#[test]
+fn verify_cube_selection_sets() {
+    let mut mesh = cube();
+    mesh.select_by_normal("top", Vector3::unit_z(), 0.1);
+    assert_eq!(1, mesh.selection("top").unwrap().faces.len());
+    mesh.grow_selection("top");
+    assert_eq!(5, mesh.selection("top").unwrap().faces.len());
+    mesh.shrink_selection("top");
+    assert_eq!(1, mesh.selection("top").unwrap().faces.len());
+    mesh.select_by_normal("linked", Vector3::unit_z(), 0.1);
+    mesh.select_linked("linked");
+    assert_eq!(6, mesh.selection("linked").unwrap().faces.len());
+    mesh.select_boundary("top");
+    assert_eq!(4, mesh.selection("top").unwrap().edges.len());
+    assert_eq!(4, mesh.selection("top").unwrap().vertices.len());
+    assert!(mesh.selection("top").unwrap().faces.is_empty());
+
+    let welded = mesh.weld();
+    let top = welded.selection("top").unwrap();
+    assert_eq!(4, top.edges.len());
+    assert_eq!(4, top.vertices.len());
+    for &vert_id in top.vertices.iter() {
+        assert!((welded.vertex(vert_id).unwrap().position.z - 0.5).abs() < 0.0001);
+    }
+    assert_eq!(6, welded.selection("linked").unwrap().faces.len());
+
+    let mut mesh = cube().triangulate();
+    mesh.select_by_normal("top", Vector3::unit_z(), 0.1);
+    assert_eq!(2, mesh.selection("top").unwrap().faces.len());
+    let combined = mesh.combine_coplanar_faces();
+    assert_eq!(6, combined.face_count);
+    let top = combined.selection("top").unwrap();
+    assert_eq!(1, top.faces.len());
+    for &face_id in top.faces.iter() {
+        assert!(combined.face_norm(face_id).z > 0.9);
+    }
+}
+
+fn mesh_state(mesh: &Mesh) -> String {
+    format!("{:?} {:?} {:?} {} {} {}", mesh.vertices, mesh.halfedges, mesh.faces,
+        mesh.vertex_count, mesh.halfedge_count, mesh.face_count)
+}
+
+#[test]
+fn verify_journal_undo_redo() {
+    let mut mesh = cube();
+    mesh.start_journal();
+    let original = mesh_state(&mesh);
+    let face_ids = FaceIterator::new(&mesh).into_vec();
+    mesh.inset_faces(&face_ids[0..1], 0.1, 0.05, InsetMode::Individual);
+    mesh.checkpoint();
+    let inset = mesh_state(&mesh);
+    let start = cube_halfedge(&mesh, Vector3::unit_x(), Vector3::unit_z());
+    mesh.loop_cut(start, 0.5);
+    mesh.translate(0.0, 0.0, 1.0);
+    let cut = mesh_state(&mesh);
+    assert!(mesh.can_undo());
+
+    assert!(mesh.undo());
+    assert_eq!(inset, mesh_state(&mesh));
+    assert!(mesh.undo());
+    assert_eq!(original, mesh_state(&mesh));
+    assert!(!mesh.undo());
+    assert!(mesh.redo());
+    assert_eq!(inset, mesh_state(&mesh));
+    assert!(mesh.redo());
+    assert_eq!(cut, mesh_state(&mesh));
+    assert!(!mesh.redo());
+    assert!(mesh.triangulate().is_triangulated_mesh_manifold());
+
+    assert!(mesh.undo());
+    mesh.translate(1.0, 0.0, 0.0);
+    assert!(!mesh.can_redo());
+    assert!(mesh.undo());
+    assert_eq!(inset, mesh_state(&mesh));
+
+    // Changes made through the mutable accessors are journaled when the
+    // guards are dropped
+    mesh.checkpoint();
+    let before = mesh_state(&mesh);
+    mesh.vertex_mut(1).unwrap().position = Point3::new(2.0, 2.0, 2.0);
+    mesh.vertex_mut(2).unwrap().source = 7;
+    assert_eq!(Point3::new(2.0, 2.0, 2.0), mesh.vertex_position(1).unwrap());
+    assert_eq!(Some(7), mesh.vertex_source(2));
+    assert!(mesh.undo());
+    assert_eq!(before, mesh_state(&mesh));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn verify_serde_round_trip() {
+    let mut mesh = cube();
+    let face_ids = FaceIterator::new(&mesh).into_vec();
+    mesh.inset_faces(&face_ids[0..1], 0.1, 0.05, InsetMode::Individual);
+    for (i, source) in mesh.vertices.source.iter_mut().enumerate() {
+        *source = i as i32 % 3;
+    }
+    mesh.select_by_normal("top", Vector3::unit_z(), 0.1);
+    let json = serde_json::to_string(&mesh).unwrap();
+    let restored : Mesh = serde_json::from_str(&json).unwrap();
+    assert_eq!(mesh_state(&mesh), mesh_state(&restored));
+    assert_eq!(mesh.selection("top").unwrap().faces, restored.selection("top").unwrap().faces);
+
+    let mut bmesh = Bmesh::new();
+    bmesh.set_cut_subdiv_count(1);
+    let first = bmesh.add_node(Point3::new(0.0, 0.0, 0.0), 0.4);
+    let second = bmesh.add_node(Point3::new(0.0, 1.0, 0.0), 0.3);
+    let third = bmesh.add_node(Point3::new(1.0, 1.5, 0.0), 0.2);
+    bmesh.add_edge(first, second);
+    bmesh.add_edge(second, third);
+    bmesh.set_node_round_way(third, 1);
+    let json = serde_json::to_string(&bmesh).unwrap();
+    let mut restored : Bmesh = serde_json::from_str(&json).unwrap();
+    let generated = bmesh.generate_mesh();
+    let restored_generated = restored.generate_mesh();
+    assert_eq!(generated.vertex_count, restored_generated.vertex_count);
+    assert_eq!(generated.face_count, restored_generated.face_count);
+
+    let mut skeleton = SkeletonMesh::new();
+    skeleton.set_end_radius(0.01);
+    skeleton.add_bone(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0));
+    let json = serde_json::to_string(&skeleton).unwrap();
+    let mut restored : SkeletonMesh = serde_json::from_str(&json).unwrap();
+    assert_eq!(mesh_state(skeleton.generate_mesh()), mesh_state(restored.generate_mesh()));
+}
+
+fn face_positions(mesh: &Mesh) -> Vec<Vec<(f32, f32, f32, i32)>> {
+    FaceIterator::new(mesh).map(|face_id| {
+        FaceVertexIterator::new(mesh, face_id).map(|vert_id| {
+            let vertex = mesh.vertex(vert_id).unwrap();
+            (vertex.position.x, vertex.position.y, vertex.position.z, vertex.source)
+        }).collect()
+    }).collect()
+}
+
+#[test]
+fn verify_binary_round_trip() {
+    let mut mesh = cube().subdivide();
+    let face_ids = FaceIterator::new(&mesh).into_vec();
+    mesh.inset_faces(&face_ids[0..3], 0.05, 0.02, InsetMode::Region);
+    for (i, source) in mesh.vertices.source.iter_mut().enumerate() {
+        *source = i as i32 % 5 - 1;
+    }
+    let mut bytes : Vec<u8> = Vec::new();
+    mesh.write_binary(&mut bytes).unwrap();
+    let restored = Mesh::read_binary(&bytes[..]).unwrap();
+    assert_eq!(mesh.vertex_count, restored.vertex_count);
+    assert_eq!(mesh.face_count, restored.face_count);
+    assert_eq!(face_positions(&mesh), face_positions(&restored));
+    assert!(restored.broken_face_set().is_empty());
+
+    let mut corrupted = bytes.clone();
+    corrupted[40] ^= 1;
+    assert_eq!(io::ErrorKind::InvalidData, Mesh::read_binary(&corrupted[..]).unwrap_err().kind());
+    let mut newer = bytes.clone();
+    newer[4] = (BINARY_FORMAT_VERSION + 1) as u8;
+    assert_eq!(io::ErrorKind::InvalidData, Mesh::read_binary(&newer[..]).unwrap_err().kind());
+    assert!(Mesh::read_binary(&bytes[..bytes.len() - 1]).is_err());
+
+    // A well-formed stream with a face too large to add is rejected
+    let size : u32 = 1000;
+    let mut huge_face : Vec<u8> = b"MLMB".to_vec();
+    huge_face.extend_from_slice(&1u16.to_le_bytes());
+    huge_face.extend_from_slice(&0u16.to_le_bytes());
+    for &count in [size, 1, size].iter() {
+        huge_face.extend_from_slice(&count.to_le_bytes());
+    }
+    for i in 0..size {
+        let angle = i as f32 / size as f32 * 2.0 * std::f32::consts::PI;
+        for &value in [angle.cos(), angle.sin(), 0.0].iter() {
+            huge_face.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    huge_face.extend_from_slice(&size.to_le_bytes());
+    for i in 0..size {
+        huge_face.extend_from_slice(&i.to_le_bytes());
+    }
+    huge_face.extend_from_slice(&0u32.to_le_bytes());
+    let checksum = huge_face.iter().fold(0x811c_9dc5u32, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193));
+    huge_face.extend_from_slice(&checksum.to_le_bytes());
+    assert_eq!(io::ErrorKind::InvalidData, Mesh::read_binary(&huge_face[..]).unwrap_err().kind());
+}
+
+#[test]
+fn verify_cube_render_buffer() {
+    let cube = cube();
+    let smooth = RenderBufferBuilder::new(&cube).build();
+    assert_eq!(8, smooth.vertex_count());
+    assert_eq!(36, smooth.indices.len());
+    assert_eq!(6, smooth.layout.stride());
+    let corner = &smooth.vertices[0..6];
+    let normal = Vector3::new(corner[3], corner[4], corner[5]);
+    let diagonal = Vector3::new(corner[0], corner[1], corner[2]).normalize();
+    assert!((normal - diagonal).magnitude() < 0.0001);
+
+    let flat = RenderBufferBuilder::new(&cube).crease_angle(30.0).build();
+    assert_eq!(24, flat.vertex_count());
+    assert_eq!(smooth.indices.len(), flat.indices.len());
+
+    let mut uvs = HashMap::new();
+    for (tile, face_id) in FaceIterator::new(&cube).enumerate() {
+        let corners = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 1.0), Vector2::new(0.0, 1.0)];
+        for (i, halfedge_id) in FaceHalfedgeIterator::new(&cube, cube.face_first_halfedge_id(face_id).unwrap()).enumerate() {
+            uvs.insert(halfedge_id, corners[i] + Vector2::new(2.0 * tile as f32, 0.0));
+        }
+    }
+    let textured = RenderBufferBuilder::new(&cube).uvs(&uvs).source(true).build();
+    assert_eq!(Some(6), textured.layout.uv_offset());
+    assert_eq!(Some(8), textured.layout.source_offset());
+    assert_eq!(9, textured.layout.stride());
+    assert_eq!(24, textured.vertex_count());
+    let uv_offset = textured.layout.uv_offset().unwrap();
+    let first_face_id = FaceIterator::new(&cube).next().unwrap();
+    for (i, halfedge_id) in FaceHalfedgeIterator::new(&cube, cube.face_first_halfedge_id(first_face_id).unwrap()).enumerate() {
+        let start = i * textured.layout.stride() + uv_offset;
+        assert_eq!(uvs[&halfedge_id], Vector2::new(textured.vertices[start], textured.vertices[start + 1]));
+    }
+    let indices_u16 = textured.indices_u16().unwrap();
+    assert!(indices_u16.iter().zip(textured.indices.iter()).all(|(&short, &long)| short as u32 == long));
+}
+
+fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+    let mut triangles : Vec<[u32; 3]> = indices.chunks(3).map(|triangle| {
+        let first = (0..3).min_by_key(|&i| triangle[i]).unwrap();
+        [triangle[first], triangle[(first + 1) % 3], triangle[(first + 2) % 3]]
+    }).collect();
+    triangles.sort();
+    triangles
+}
+
+#[test]
+fn verify_subdivided_cube_vertex_cache_optimization() {
+    let mesh = cube().subdivide().subdivide().subdivide().triangulate();
+    let mut buffer = RenderBufferBuilder::new(&mesh).build();
+    let vertex_count = buffer.vertex_count();
+    let before = vertex_cache_stats(&buffer.indices, vertex_count, 16);
+    let reordered = optimize_vertex_cache(&buffer.indices, vertex_count);
+    assert_eq!(sorted_triangles(&buffer.indices), sorted_triangles(&reordered));
+    let after = vertex_cache_stats(&reordered, vertex_count, 16);
+    assert!(after.acmr < before.acmr * 0.9);
+    assert!(after.acmr < 0.7);
+    assert!(after.atvr < before.atvr * 0.9);
+
+    let vertices = buffer.vertices.clone();
+    buffer.optimize();
+    assert!(vertex_cache_stats(&buffer.indices, vertex_count, 16).acmr <= after.acmr * 1.1);
+    let mut next_new_index = 0;
+    for &index in &buffer.indices {
+        assert!(index <= next_new_index);
+        if index == next_new_index {
+            next_new_index += 1;
+        }
+    }
+    let mut old_positions : Vec<Vec<u32>> = vertices.chunks(6).map(|v| v.iter().map(|x| x.to_bits()).collect()).collect();
+    let mut new_positions : Vec<Vec<u32>> = buffer.vertices.chunks(6).map(|v| v.iter().map(|x| x.to_bits()).collect()).collect();
+    old_positions.sort();
+    new_positions.sort();
+    assert_eq!(old_positions, new_positions);
+}
+
+#[test]
+fn verify_nested_cubes_overdraw_optimization() {
+    let mut inner = cube().subdivide().subdivide();
+    inner.scale(0.5);
+    let mut outer = cube().subdivide().subdivide();
+    outer.scale(2.0);
+    let mut mesh = Mesh::new();
+    mesh.add_mesh(&inner);
+    mesh.add_mesh(&outer);
+    let buffer = RenderBufferBuilder::new(&mesh.triangulate()).crease_angle(30.0).build();
+    let vertex_count = buffer.vertex_count();
+    let stride = buffer.layout.stride();
+    let cache_ordered = optimize_vertex_cache(&buffer.indices, vertex_count);
+    let reordered = optimize_overdraw(&cache_ordered, &buffer.vertices, stride, 1.05);
+    assert_eq!(sorted_triangles(&buffer.indices), sorted_triangles(&reordered));
+
+    // The outer cube occludes the inner one from every direction, so all of
+    // its triangles are drawn first
+    let is_outer = |index: u32| buffer.vertices[index as usize * stride].abs() > 0.5 ||
+        buffer.vertices[index as usize * stride + 1].abs() > 0.5 ||
+        buffer.vertices[index as usize * stride + 2].abs() > 0.5;
+    let first_inner = reordered.iter().position(|&index| !is_outer(index)).unwrap();
+    assert!(reordered[first_inner..].iter().all(|&index| !is_outer(index)));
+    assert!(first_inner > 0);
+
+    let cache_stats = vertex_cache_stats(&cache_ordered, vertex_count, 16);
+    let overdraw_stats = vertex_cache_stats(&reordered, vertex_count, 16);
+    assert!(overdraw_stats.acmr <= cache_stats.acmr * 1.1);
+}